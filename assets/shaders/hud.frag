@@ -0,0 +1,13 @@
+#version 140
+
+in vec2 v_uv;
+in vec4 v_color;
+
+out vec4 f_color;
+
+uniform sampler2D font;
+
+void main() {
+    // The font is white, so only its alpha matters
+    f_color = vec4(v_color.rgb, v_color.a * texture(font, v_uv).a);
+}
@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 position;
+in vec2 uv;
+in vec4 color;
+
+out vec2 v_uv;
+out vec4 v_color;
+
+void main() {
+    v_uv = uv;
+    v_color = color;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 140
+
+uniform mat4 model_matrix;
+uniform mat4 view_matrix;
+uniform mat4 projection_matrix;
+
+in vec3 position;
+
+void main() {
+    gl_Position = projection_matrix * view_matrix * model_matrix * vec4(position, 1.0);
+}
@@ -0,0 +1,5 @@
+#version 140
+
+void main() {
+    // Only the depth is written
+}
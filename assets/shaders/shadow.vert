@@ -0,0 +1,10 @@
+#version 140
+
+uniform mat4 model_matrix;
+uniform mat4 light_matrix;
+
+in vec3 position;
+
+void main() {
+    gl_Position = light_matrix * model_matrix * vec4(position, 1.0);
+}
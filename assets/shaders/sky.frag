@@ -0,0 +1,32 @@
+#version 140
+
+in vec3 v_direction;
+
+out vec4 f_color;
+
+uniform vec3 sun_direction;
+uniform vec3 sky_color;
+uniform vec3 horizon_color;
+
+const float SUN_SIZE = 0.9995;
+const vec3 SUN_COLOR = vec3(1.0, 0.95, 0.8);
+const vec3 MOON_COLOR = vec3(0.75, 0.8, 0.9);
+
+void main() {
+    vec3 direction = normalize(v_direction);
+
+    // Fade from the horizon up to the top of the sky; below the horizon stays the horizon color
+    // so it matches the fog
+    float height = max(direction.y, 0.0);
+    vec3 color = mix(horizon_color, sky_color, pow(height, 0.5));
+
+    // Add the glow around the sun and the sun itself
+    float sun = dot(direction, normalize(sun_direction));
+    color += SUN_COLOR * pow(max(sun, 0.0), 64.0) * 0.4 * step(0.0, sun_direction.y + 0.1);
+    color = mix(color, SUN_COLOR, smoothstep(SUN_SIZE - 0.0002, SUN_SIZE, sun));
+
+    // The moon is opposite of the sun
+    color = mix(color, MOON_COLOR, smoothstep(SUN_SIZE - 0.0002, SUN_SIZE, -sun));
+
+    f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 140
+
+uniform mat4 inverse_view_projection;
+
+in vec2 position;
+
+out vec3 v_direction;
+
+void main() {
+    // Find the direction through this corner of the screen by projecting it back in to the world
+    vec4 world_position = inverse_view_projection * vec4(position, 1.0, 1.0);
+    v_direction = world_position.xyz / world_position.w;
+    gl_Position = vec4(position, 1.0, 1.0);
+}
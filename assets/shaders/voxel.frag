@@ -3,17 +3,85 @@
 in vec3 v_normal;
 in vec3 v_color;
 in vec2 v_uv;
+in vec2 v_light;
+in float v_distance;
+in float v_depth;
+in vec3 v_shadow_position_0;
+in vec3 v_shadow_position_1;
+in vec3 v_shadow_position_2;
 
 out vec4 f_color;
 
 uniform sampler2D tex;
 
-const vec3 LIGHT = vec3(-0.2, 0.8, 0.1);
+// The direction towards the sun or moon and how bright the sky is
+uniform vec3 light_direction;
+uniform float sky_brightness;
+
+// Things fade in to the fog color between the start and end distance
+uniform vec3 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+
+// The cascades of the shadow map, how far away each one ends, and how much closer a surface has to
+// be than the shadow map to be lit
+uniform sampler2D shadow_map_0;
+uniform sampler2D shadow_map_1;
+uniform sampler2D shadow_map_2;
+uniform vec3 shadow_splits;
+uniform vec3 shadow_biases;
+
+const vec3 BLOCK_LIGHT_COLOR = vec3(1.0, 0.85, 0.6);
+
+// Each level of light is a bit dimmer than the last so light fades out smoothly
+float light_brightness(float level) {
+    return pow(0.8, (1.0 - level) * 15.0);
+}
+
+// Compares 4x4 texels around the position and weights them so the result is the same as a 3x3
+// box of bilinearly filtered comparisons, which gives soft edges to the shadows
+float shadow_pcf(sampler2D shadow_map, vec3 position, float bias) {
+    vec2 size = vec2(textureSize(shadow_map, 0));
+    vec2 texel = position.xy * size - 0.5;
+    vec2 base = floor(texel);
+    vec2 f = texel - base;
+
+    float lit = 0.0;
+    for (int y = -1; y <= 2; y++) {
+        float weight_y = y == -1 ? 1.0 - f.y : (y == 2 ? f.y : 1.0);
+        for (int x = -1; x <= 2; x++) {
+            float weight_x = x == -1 ? 1.0 - f.x : (x == 2 ? f.x : 1.0);
+            float depth = texture(shadow_map, (base + vec2(x, y) + 0.5) / size).r;
+            lit += weight_x * weight_y * (position.z - bias > depth ? 0.0 : 1.0);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Returns how much of the sun reaches the fragment, using the first cascade that covers it. The
+// last cascade fades out so the edge of the shadows isn't visible.
+float shadow() {
+    if (v_depth < shadow_splits.x) {
+        return shadow_pcf(shadow_map_0, v_shadow_position_0, shadow_biases.x);
+    } else if (v_depth < shadow_splits.y) {
+        return shadow_pcf(shadow_map_1, v_shadow_position_1, shadow_biases.y);
+    } else if (v_depth < shadow_splits.z) {
+        float fade = smoothstep(shadow_splits.z * 0.8, shadow_splits.z, v_depth);
+        return mix(shadow_pcf(shadow_map_2, v_shadow_position_2, shadow_biases.z), 1.0, fade);
+    } else {
+        return 1.0;
+    }
+}
 
 void main() {
-    // Determine the lighting
-    float lum = max(dot(normalize(v_normal), normalize(LIGHT)), 0.0);
-    vec3 color = (0.3 + 0.7 * lum) * vec3(1.0, 1.0, 1.0);
+    // Determine the lighting; the sun only shades blocks lit by the sky
+    float lum = max(dot(normalize(v_normal), normalize(light_direction)), 0.0);
+    if (lum > 0.0) {
+        lum *= shadow();
+    }
+    vec3 sky_color = light_brightness(v_light.x) * sky_brightness * (0.3 + 0.7 * lum) * vec3(1.0, 1.0, 1.0);
+    vec3 block_color = light_brightness(v_light.y) * BLOCK_LIGHT_COLOR;
+    vec3 color = max(sky_color, block_color);
 
     // Apply the vertex color
     color *= v_color;
@@ -23,4 +91,8 @@ void main() {
 
     // Apply the fragment color
     f_color *= texture(tex, v_uv);
+
+    // Fade in to the fog
+    float fog = smoothstep(fog_start, fog_end, v_distance);
+    f_color.rgb = mix(f_color.rgb, fog_color, fog);
 }
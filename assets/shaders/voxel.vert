@@ -4,10 +4,18 @@ uniform mat4 model_matrix;
 uniform mat4 view_matrix;
 uniform mat4 projection_matrix;
 
+// Transform positions in to each cascade of the shadow map, after pushing them out along the
+// normal by the offset for that cascade
+uniform mat4 shadow_matrix_0;
+uniform mat4 shadow_matrix_1;
+uniform mat4 shadow_matrix_2;
+uniform vec3 shadow_normal_offsets;
+
 in vec3 position;
 in vec3 normal;
 in vec3 color;
 in vec2 uv;
+in vec2 light;
 
 // TODO: Add uv
 
@@ -15,11 +23,33 @@ out vec3 v_position;
 out vec3 v_normal;
 out vec3 v_color;
 out vec2 v_uv;
+out vec2 v_light;
+out float v_distance;
+out float v_depth;
+out vec3 v_shadow_position_0;
+out vec3 v_shadow_position_1;
+out vec3 v_shadow_position_2;
+
+// Moves a position from clip space in to the texture coordinates and depth of a shadow map
+vec3 shadow_position(mat4 shadow_matrix, vec4 world_position, float normal_offset) {
+    vec4 position = shadow_matrix * (world_position + vec4(normal * normal_offset, 0.0));
+    return position.xyz * 0.5 + 0.5;
+}
 
 void main() {
     v_position = position;
     v_normal = normal;
     v_color = color;
     v_uv = uv;
-    gl_Position = projection_matrix * view_matrix * model_matrix * vec4(v_position, 1.0);
+    v_light = light;
+
+    vec4 world_position = model_matrix * vec4(v_position, 1.0);
+    v_shadow_position_0 = shadow_position(shadow_matrix_0, world_position, shadow_normal_offsets.x);
+    v_shadow_position_1 = shadow_position(shadow_matrix_1, world_position, shadow_normal_offsets.y);
+    v_shadow_position_2 = shadow_position(shadow_matrix_2, world_position, shadow_normal_offsets.z);
+
+    vec4 view_position = view_matrix * world_position;
+    v_distance = length(view_position.xyz);
+    v_depth = view_position.z;
+    gl_Position = projection_matrix * view_position;
 }
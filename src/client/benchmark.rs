@@ -0,0 +1,402 @@
+use super::world_renderer::WorldRenderer;
+use super::Options;
+use super::VoxelTest;
+use crate::utils;
+use crate::utils::AsFloatSeconds;
+use crate::world;
+use std::collections::HashSet;
+use std::fs;
+use std::time::Instant;
+
+/// What a benchmark measures.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BenchmarkKind {
+    /// Generates and meshes the chunks along the path without a GPU.
+    Mesh,
+
+    /// Flies along the path in a window, timing every stage including uploads and frames.
+    Render
+}
+
+impl BenchmarkKind {
+    pub fn from_name(name: &str) -> Option<BenchmarkKind> {
+        match name {
+            "mesh" => Some(BenchmarkKind::Mesh),
+            "render" => Some(BenchmarkKind::Render),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BenchmarkKind::Mesh => "mesh",
+            BenchmarkKind::Render => "render"
+        }
+    }
+}
+
+/// How long each step of the chunk pipeline took, in seconds, one sample per chunk or frame.
+#[derive(Debug, Clone, Default)]
+pub struct StageTimings {
+    pub generation: Vec<f64>,
+    pub sides: Vec<f64>,
+    pub meshing: Vec<f64>,
+    pub upload: Vec<f64>,
+    pub frames: Vec<f64>,
+
+    /// The number of vertices meshed.
+    pub vertices: usize
+}
+
+impl StageTimings {
+    pub fn new() -> StageTimings {
+        StageTimings::default()
+    }
+
+    /// Returns each stage with samples and its name.
+    fn get_stages(&self) -> Vec<(&'static str, &[f64])> {
+        let stages: [(&'static str, &[f64]); 5] = [
+            ("generation", &self.generation),
+            ("sides", &self.sides),
+            ("meshing", &self.meshing),
+            ("upload", &self.upload),
+            ("frame", &self.frames)
+        ];
+        stages.iter().cloned().filter(|(_, samples)| !samples.is_empty()).collect()
+    }
+}
+
+/// The spread of the samples of a stage, in milliseconds.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct StageSummary {
+    count: usize,
+    total: f64,
+    mean: f64,
+    min: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64
+}
+
+impl StageSummary {
+    fn new(samples: &[f64]) -> StageSummary {
+        let mut sorted: Vec<f64> = samples.iter().map(|sample| sample * 1000.).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Use the nearest rank, so every percentile is a real sample
+        let percentile = |percent: f64| {
+            let rank = (percent / 100. * sorted.len() as f64).ceil() as usize;
+            sorted[rank.max(1) - 1]
+        };
+
+        let total: f64 = sorted.iter().sum();
+        StageSummary {
+            count: sorted.len(),
+            total,
+            mean: total / sorted.len() as f64,
+            min: sorted[0],
+            p50: percentile(50.),
+            p90: percentile(90.),
+            p99: percentile(99.),
+            max: sorted[sorted.len() - 1]
+        }
+    }
+}
+
+/// The results of a benchmark run.
+struct BenchmarkReport {
+    kind: BenchmarkKind,
+    world: &'static str,
+    seed: u32,
+    view_distance: u32,
+
+    /// How many steps were taken along the path, and how long the whole run took in seconds.
+    steps: u32,
+    duration: f64,
+
+    timings: StageTimings
+}
+
+impl BenchmarkReport {
+    fn to_json(&self) -> String {
+        let stages: Vec<String> = self.timings.get_stages().iter().map(|(name, samples)| {
+            let summary = StageSummary::new(samples);
+            format!(
+                "    \"{}\": {{ \"count\": {}, \"total_ms\": {:.3}, \"mean_ms\": {:.3}, \"min_ms\": {:.3}, \"p50_ms\": {:.3}, \"p90_ms\": {:.3}, \"p99_ms\": {:.3}, \"max_ms\": {:.3} }}",
+                name, summary.count, summary.total, summary.mean, summary.min, summary.p50, summary.p90, summary.p99, summary.max
+            )
+        }).collect();
+
+        format!(
+            "{{\n  \"benchmark\": \"{}\",\n  \"world\": \"{}\",\n  \"seed\": {},\n  \"view_distance\": {},\n  \"steps\": {},\n  \"duration_s\": {:.3},\n  \"vertices\": {},\n  \"stages\": {{\n{}\n  }}\n}}\n",
+            self.kind.name(), self.world, self.seed, self.view_distance, self.steps, self.duration, self.timings.vertices, stages.join(",\n")
+        )
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("benchmark,stage,count,total_ms,mean_ms,min_ms,p50_ms,p90_ms,p99_ms,max_ms\n");
+        for (name, samples) in self.timings.get_stages() {
+            let summary = StageSummary::new(samples);
+            csv.push_str(&format!(
+                "{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3}\n",
+                self.kind.name(), name, summary.count, summary.total, summary.mean, summary.min, summary.p50, summary.p90, summary.p99, summary.max
+            ));
+        }
+        csv
+    }
+
+    /// Prints a table of the stages and saves the report as CSV if the path ends in `.csv`, or
+    /// as JSON otherwise.
+    fn save(&self, path: &str) -> Result<(), String> {
+        println!("{:<12}{:>8}{:>12}{:>10}{:>10}{:>10}{:>10}", "stage", "count", "total ms", "mean", "p50", "p90", "p99");
+        for (name, samples) in self.timings.get_stages() {
+            let summary = StageSummary::new(samples);
+            println!(
+                "{:<12}{:>8}{:>12.1}{:>10.3}{:>10.3}{:>10.3}{:>10.3}",
+                name, summary.count, summary.total, summary.mean, summary.p50, summary.p90, summary.p99
+            );
+        }
+
+        let report = if path.ends_with(".csv") { self.to_csv() } else { self.to_json() };
+        fs::write(path, report).map_err(|err| format!("Failed to save benchmark report `{}`: {}", path, err))?;
+        println!("Saved benchmark report {} - {:.2}s", path, self.duration);
+        Ok(())
+    }
+}
+
+/// The path flown when no other is given: a line over the terrain, keeping a fixed height above
+/// the highest ground near each keyframe so the camera never ends up inside of a hill.
+fn default_path(world: &world::World) -> utils::CameraPath {
+    const HEIGHT: u32 = 24;
+    const Y: u32 = 48;
+
+    let mut path = utils::CameraPath::new();
+    for step in 0..=8u32 {
+        let x = 32 + step * 64;
+        let ground = (x - 32..=x + 32).step_by(4)
+            .filter_map(|x| world.surface_height(x, Y))
+            .max()
+            .unwrap_or(0);
+
+        let keyframe = utils::CameraKeyframe {
+            time: step as f32 * 2.,
+            position: [x as f32, Y as f32, (ground + HEIGHT) as f32],
+            direction: [1., 0.2, -0.3]
+        };
+        path.add_keyframe(keyframe).unwrap();
+    }
+    path
+}
+
+/// A render benchmark in progress.
+pub struct RenderBenchmark {
+    path: utils::CameraPath,
+    frame_rate: u32,
+    step: u32,
+    start: Instant,
+    frames: Vec<f64>,
+
+    report_path: String,
+    world: &'static str,
+    seed: u32
+}
+
+impl RenderBenchmark {
+    pub fn new(options: &Options, world: &world::World) -> RenderBenchmark {
+        RenderBenchmark {
+            path: options.play_path.clone().unwrap_or_else(|| default_path(world)),
+            frame_rate: options.frame_rate,
+            step: 0,
+            start: Instant::now(),
+            frames: Vec::new(),
+
+            report_path: options.benchmark_report.clone(),
+            world: options.world_type.name(),
+            seed: options.seed
+        }
+    }
+}
+
+impl VoxelTest {
+    /// Moves the camera to the next step of the render benchmark. Every step moves the same
+    /// distance along the path however long the frames take, so each run renders the same frames.
+    pub fn step_benchmark(&mut self) {
+        let mut benchmark = match self.benchmark.take() {
+            Some(benchmark) => benchmark,
+            None => return
+        };
+
+        // Time the pipeline from the first frame
+        if benchmark.step == 0 {
+            self.world_renderer.start_timings();
+            benchmark.start = Instant::now();
+        }
+
+        if let Some((position, direction)) = benchmark.path.sample(benchmark.step as f32 / benchmark.frame_rate as f32) {
+            self.camera.set_position([position[0], position[2], position[1]]);  // Flip Y with Z
+            self.camera.set_direction([direction[0], direction[2], direction[1]]);  // Flip Y with Z
+        }
+        benchmark.step += 1;
+
+        self.benchmark = Some(benchmark);
+    }
+
+    /// Records how long a frame took and saves the report once the end of the path has been
+    /// rendered. Returns false once the benchmark is over.
+    pub fn finish_benchmark_frame(&mut self, frame_duration: f64) -> bool {
+        let mut benchmark = match self.benchmark.take() {
+            Some(benchmark) => benchmark,
+            None => return true
+        };
+        benchmark.frames.push(frame_duration);
+
+        if benchmark.step as f32 / benchmark.frame_rate as f32 <= benchmark.path.get_duration() {
+            self.benchmark = Some(benchmark);
+            return true;
+        }
+
+        let mut timings = self.world_renderer.take_timings().unwrap_or_default();
+        timings.frames = benchmark.frames;
+        let report = BenchmarkReport {
+            kind: BenchmarkKind::Render,
+            world: benchmark.world,
+            seed: benchmark.seed,
+            view_distance: VoxelTest::VIEW_DISTANCE,
+            steps: benchmark.step,
+            duration: benchmark.start.elapsed().as_float_seconds(),
+            timings
+        };
+        if let Err(err) = report.save(&benchmark.report_path) {
+            error!("{}", err);
+        }
+
+        false
+    }
+}
+
+/// Generates and meshes the chunks seen along the path passed with `--play-path`, or a default
+/// flight, taking `--frame-rate` steps a second, and saves a report of how long each stage took.
+/// This doesn't need a GPU, so it measures the world code alone.
+pub fn run_mesh_benchmark(options: &Options) -> Result<(), String> {
+    let mut world = world::World::new(options.create_world_delegate()?);
+    let path = options.play_path.clone().unwrap_or_else(|| default_path(&world));
+    let mut world_renderer = WorldRenderer::new(VoxelTest::VIEW_DISTANCE, options.mesh_options.clone());
+    world_renderer.start_timings();
+
+    // Load the chunks in view at each step, like the world renderer does
+    let start = Instant::now();
+    let steps = (path.get_duration() * options.frame_rate as f32).floor() as u32 + 1;
+    let mut meshed = HashSet::new();
+    for step in 0..steps {
+        let (position, _) = path.sample(step as f32 / options.frame_rate as f32).unwrap();
+        let current_chunk = WorldRenderer::get_current_chunk(&[position[0], position[2], position[1]]);  // Flip Y with Z
+        let chunks_in_view = world_renderer.get_chunks_in_view(&current_chunk);
+
+        world_renderer.create_chunks(&mut world, &chunks_in_view);
+        for chunk_index in chunks_in_view {
+            if meshed.insert(chunk_index.clone()) {
+                world_renderer.mesh_chunk(&mut world, &chunk_index);
+            }
+        }
+    }
+
+    let report = BenchmarkReport {
+        kind: BenchmarkKind::Mesh,
+        world: options.world_type.name(),
+        seed: options.seed,
+        view_distance: VoxelTest::VIEW_DISTANCE,
+        steps,
+        duration: start.elapsed().as_float_seconds(),
+        timings: world_renderer.take_timings().unwrap_or_default()
+    };
+    report.save(&options.benchmark_report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    /// Samples of 1 to `count` milliseconds, out of order.
+    fn samples(count: usize) -> Vec<f64> {
+        (1..=count).rev().map(|ms| ms as f64 / 1000.).collect()
+    }
+
+    fn report(timings: StageTimings) -> BenchmarkReport {
+        BenchmarkReport {
+            kind: BenchmarkKind::Mesh,
+            world: "flat",
+            seed: 7,
+            view_distance: 4,
+            steps: 100,
+            duration: 1.5,
+            timings
+        }
+    }
+
+    #[test]
+    fn summaries_use_the_nearest_rank() {
+        // Every percentile of one sample is that sample
+        let one = StageSummary::new(&[0.002]);
+        assert_eq!(one.count, 1);
+        for &value in [one.total, one.mean, one.min, one.p50, one.p90, one.p99, one.max].iter() {
+            assert_close(value, 2.);
+        }
+
+        // The median of two is the lower one, since ranks round up from half of the samples
+        let two = StageSummary::new(&samples(2));
+        assert_eq!(two.count, 2);
+        assert_close(two.total, 3.);
+        assert_close(two.mean, 1.5);
+        assert_close(two.min, 1.);
+        assert_close(two.p50, 1.);
+        assert_close(two.p90, 2.);
+        assert_close(two.p99, 2.);
+        assert_close(two.max, 2.);
+
+        let hundred = StageSummary::new(&samples(100));
+        assert_eq!(hundred.count, 100);
+        assert_close(hundred.total, 5050.);
+        assert_close(hundred.mean, 50.5);
+        assert_close(hundred.min, 1.);
+        assert_close(hundred.p50, 50.);
+        assert_close(hundred.p90, 90.);
+        assert_close(hundred.p99, 99.);
+        assert_close(hundred.max, 100.);
+    }
+
+    #[test]
+    fn writes_csv() {
+        let report = report(StageTimings { generation: samples(1), meshing: samples(2), frames: samples(100), ..StageTimings::new() });
+        assert_eq!(report.to_csv(), "\
+benchmark,stage,count,total_ms,mean_ms,min_ms,p50_ms,p90_ms,p99_ms,max_ms
+mesh,generation,1,1.000,1.000,1.000,1.000,1.000,1.000,1.000
+mesh,meshing,2,3.000,1.500,1.000,1.000,2.000,2.000,2.000
+mesh,frame,100,5050.000,50.500,1.000,50.000,90.000,99.000,100.000
+");
+    }
+
+    #[test]
+    fn writes_json() {
+        let timings = StageTimings { generation: samples(1), meshing: samples(2), frames: samples(100), vertices: 1234, ..StageTimings::new() };
+        assert_eq!(report(timings).to_json(), r#"{
+  "benchmark": "mesh",
+  "world": "flat",
+  "seed": 7,
+  "view_distance": 4,
+  "steps": 100,
+  "duration_s": 1.500,
+  "vertices": 1234,
+  "stages": {
+    "generation": { "count": 1, "total_ms": 1.000, "mean_ms": 1.000, "min_ms": 1.000, "p50_ms": 1.000, "p90_ms": 1.000, "p99_ms": 1.000, "max_ms": 1.000 },
+    "meshing": { "count": 2, "total_ms": 3.000, "mean_ms": 1.500, "min_ms": 1.000, "p50_ms": 1.000, "p90_ms": 2.000, "p99_ms": 2.000, "max_ms": 2.000 },
+    "frame": { "count": 100, "total_ms": 5050.000, "mean_ms": 50.500, "min_ms": 1.000, "p50_ms": 50.000, "p90_ms": 90.000, "p99_ms": 99.000, "max_ms": 100.000 }
+  }
+}
+"#);
+    }
+}
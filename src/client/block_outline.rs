@@ -0,0 +1,74 @@
+use super::cg;
+use crate::world;
+use glium::Surface;
+use glium::backend::Facade;
+use vecmath::*;
+
+/// Draws a wireframe around a single block.
+pub struct BlockOutline {
+    vertex_buffer: glium::VertexBuffer<cg::LineVertex>,
+    draw_params: glium::DrawParameters<'static>
+}
+
+impl BlockOutline {
+    /// How far the outline sticks out from the block so it doesn't z-fight with the faces.
+    const INFLATE: f32 = 0.002;
+
+    const COLOR: [f32; 4] = [0., 0., 0., 1.];
+
+    pub fn new<F>(facade: &F) -> BlockOutline where F: Facade {
+        // Build the 12 edges of a unit cube; each edge connects two corners that differ by one axis
+        let (low, high) = (-BlockOutline::INFLATE, 1. + BlockOutline::INFLATE);
+        let mut vertices = Vec::with_capacity(24);
+        for axis in 0..3 {
+            for corner in 0..4 {
+                // Pick the position on the other two axes from the corner's bits
+                let mut start = [0.; 3];
+                start[(axis + 1) % 3] = if corner & 1 == 0 { low } else { high };
+                start[(axis + 2) % 3] = if corner & 2 == 0 { low } else { high };
+                let mut end = start;
+                start[axis] = low;
+                end[axis] = high;
+
+                vertices.push(cg::LineVertex { position: start });
+                vertices.push(cg::LineVertex { position: end });
+            }
+        }
+
+        BlockOutline {
+            vertex_buffer: glium::VertexBuffer::new(facade, &vertices).unwrap(),
+            draw_params: glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::IfLessOrEqual,
+                    write: false,
+                    .. Default::default()
+                },
+                line_width: Some(2.),
+                .. Default::default()
+            }
+        }
+    }
+
+    pub fn draw<S>(&self, target: &mut S, program: &glium::Program, index: &world::WorldBlockIndex, view_matrix: Matrix4<f32>, projection_matrix: Matrix4<f32>) where S: Surface {
+        let model_matrix = [
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [index.x as f32, index.z as f32, index.y as f32, 1.]  // Flip Y with Z
+        ];
+        let uniforms = uniform! {
+            model_matrix: model_matrix,
+            view_matrix: view_matrix,
+            projection_matrix: projection_matrix,
+            color: BlockOutline::COLOR
+        };
+
+        target.draw(
+            &self.vertex_buffer,
+            glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+            program,
+            &uniforms,
+            &self.draw_params
+        ).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+use crate::world::Block;
+use crate::world::Chunk;
+use crate::world::ChunkBlockIndex;
+use crate::world::ChunkIndex;
+use crate::world::DeferredBlock;
+use crate::world::WorldDelegate;
+
+/// A world of flat, horizontal layers starting at the bottom of the world.
+pub struct FlatWorld {
+    /// The block of each layer, from the bottom up.
+    layers: Vec<Block>
+}
+
+impl FlatWorld {
+    pub const DEFAULT_LAYERS: &'static str = "stone*3,dirt*2,dirt_grass";
+
+    /// The most layers a flat world can have, so a typo in a count can't allocate a huge stack.
+    pub const MAX_HEIGHT: usize = 1024;
+
+    /// Parses layers in the form `block*count,block,...` from the bottom up, e.g.
+    /// `stone*3,dirt*2,dirt_grass`. At most `MAX_HEIGHT` layers are allowed.
+    pub fn from_config(config: &str) -> Result<FlatWorld, String> {
+        let mut layers = Vec::new();
+        for layer in config.split(',').map(|layer| layer.trim()).filter(|layer| !layer.is_empty()) {
+            // Get the block and the number of layers
+            let mut parts = layer.splitn(2, '*');
+            let id = parts.next().unwrap_or("").trim();
+            let count = match parts.next() {
+                Some(count) => count.trim().parse::<usize>().map_err(|_| format!("Invalid layer count in `{}`", layer))?,
+                None => 1
+            };
+            let block = Block::try_from_id(id).ok_or_else(|| format!("Unknown block ID `{}`", id))?;
+
+            if count > FlatWorld::MAX_HEIGHT - layers.len() {
+                return Err(format!("Flat worlds can't have more than {} layers", FlatWorld::MAX_HEIGHT));
+            }
+            layers.resize(layers.len() + count, block);
+        }
+
+        Ok(FlatWorld { layers })
+    }
+}
+
+impl WorldDelegate for FlatWorld {
+    fn create_chunk(&self, index: &ChunkIndex, _deferred: &mut Vec<DeferredBlock>) -> Chunk {
+        let mut chunk = Chunk::empty();
+
+        let chunk_bottom = index.z as usize * Chunk::SIZE_Z;
+        for z in chunk_bottom..self.layers.len().min(chunk_bottom + Chunk::SIZE_Z) {
+            let block = self.layers[z];
+            for x in 0..Chunk::SIZE_X {
+                for y in 0..Chunk::SIZE_Y {
+                    chunk.set_block(&ChunkBlockIndex::new(x, y, z - chunk_bottom), block);
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn surface_height(&self, _x: u32, _y: u32) -> Option<u32> {
+        Some(self.layers.len().saturating_sub(1) as u32)
+    }
+}
+
+/// A world of nothing but air.
+pub struct VoidWorld;
+
+impl WorldDelegate for VoidWorld {
+    fn create_chunk(&self, _index: &ChunkIndex, _deferred: &mut Vec<DeferredBlock>) -> Chunk {
+        Chunk::empty()
+    }
+}
+
+/// Lays every registered block out on a single layer, spaced out so every side of each block can
+/// be seen. Used to check textures.
+pub struct CheckerboardWorld {
+    blocks: Vec<Block>
+}
+
+impl CheckerboardWorld {
+    /// How many blocks are in each row before the pattern repeats.
+    const ROW_LENGTH: usize = 8;
+
+    pub fn new() -> CheckerboardWorld {
+        CheckerboardWorld {
+            blocks: Block::all().filter(|block| !block.is_invisible()).collect()
+        }
+    }
+}
+
+impl WorldDelegate for CheckerboardWorld {
+    fn create_chunk(&self, index: &ChunkIndex, _deferred: &mut Vec<DeferredBlock>) -> Chunk {
+        let mut chunk = Chunk::empty();
+        if index.z != 0 { return chunk; }
+
+        for x in (0..Chunk::SIZE_X).step_by(2) {
+            for y in (0..Chunk::SIZE_Y).step_by(2) {
+                // Every other block in each direction is air
+                let cell_x = (x + index.x as usize * Chunk::SIZE_X) / 2;
+                let cell_y = (y + index.y as usize * Chunk::SIZE_Y) / 2;
+                let block_index = (cell_x % CheckerboardWorld::ROW_LENGTH + cell_y * CheckerboardWorld::ROW_LENGTH) % self.blocks.len();
+                chunk.set_block(&ChunkBlockIndex::new(x, y, 0), self.blocks[block_index]);
+            }
+        }
+
+        chunk
+    }
+
+    fn surface_height(&self, _x: u32, _y: u32) -> Option<u32> {
+        Some(0)
+    }
+}
+
+/// The shapes built by `DebugShapesWorld`, each covering the kinds of geometry the mesher has to
+/// handle.
+#[derive(Copy, Clone)]
+enum DebugShape {
+    SingleBlock,
+    Cube,
+    HollowCube,
+    Sphere,
+    Pyramid,
+    Stairs,
+    Lattice,
+    Arch,
+    ChunkSpan,
+}
+
+impl DebugShape {
+    const ALL: [DebugShape; 9] = [
+        DebugShape::SingleBlock,
+        DebugShape::Cube,
+        DebugShape::HollowCube,
+        DebugShape::Sphere,
+        DebugShape::Pyramid,
+        DebugShape::Stairs,
+        DebugShape::Lattice,
+        DebugShape::Arch,
+        DebugShape::ChunkSpan,
+    ];
+
+    /// Half the width of the shapes.
+    const RADIUS: i32 = 6;
+
+    /// Determines if the shape contains a block. The position is relative to the center of the
+    /// bottom of the shape.
+    fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        let r = DebugShape::RADIUS;
+        let in_bounds = x.abs() <= r && y.abs() <= r && z >= 0 && z <= r * 2;
+        match self {
+            DebugShape::SingleBlock => x == 0 && y == 0 && z == 0,
+            DebugShape::Cube => in_bounds,
+            DebugShape::HollowCube => in_bounds && (x.abs() == r || y.abs() == r || z == 0 || z == r * 2),
+            DebugShape::Sphere => x * x + y * y + (z - r) * (z - r) <= r * r,
+            DebugShape::Pyramid => z >= 0 && x.abs() <= r - z && y.abs() <= r - z,
+            DebugShape::Stairs => in_bounds && z <= x + r,
+            DebugShape::Lattice => in_bounds && (x + y + z) % 2 == 0,
+            DebugShape::Arch => in_bounds && y.abs() <= 1 && (z >= r || x.abs() >= r - 1),
+            DebugShape::ChunkSpan => z == 0 && y.abs() <= 1,
+        }
+    }
+}
+
+/// Places one debug shape per chunk along the X axis on top of a flat floor.
+pub struct DebugShapesWorld {
+    floor: Block,
+    shape: Block
+}
+
+impl DebugShapesWorld {
+    pub fn new() -> DebugShapesWorld {
+        DebugShapesWorld {
+            floor: Block::from_id("stone"),
+            shape: Block::from_id("brick_stone")
+        }
+    }
+}
+
+impl WorldDelegate for DebugShapesWorld {
+    fn create_chunk(&self, index: &ChunkIndex, _deferred: &mut Vec<DeferredBlock>) -> Chunk {
+        let mut chunk = Chunk::empty();
+        if index.z != 0 { return chunk; }
+
+        // Add the floor
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                chunk.set_block(&ChunkBlockIndex::new(x, y, 0), self.floor);
+            }
+        }
+
+        // Add the shape in the first row of chunks
+        let shape = match DebugShape::ALL.get(index.x as usize) {
+            Some(shape) if index.y == 0 => *shape,
+            _ => return chunk
+        };
+        let center_x = Chunk::SIZE_X as i32 / 2;
+        let center_y = Chunk::SIZE_Y as i32 / 2;
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                for z in 1..Chunk::SIZE_Z {
+                    if shape.contains(x as i32 - center_x, y as i32 - center_y, z as i32 - 1) {
+                        chunk.set_block(&ChunkBlockIndex::new(x, y, z), self.shape);
+                    }
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn surface_height(&self, _x: u32, _y: u32) -> Option<u32> {
+        Some(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The block at a height in every column of a chunk, checking the chunk is made of layers.
+    fn layer(chunk: &Chunk, z: usize) -> Block {
+        let block = *chunk.get_block(&ChunkBlockIndex::new(0, 0, z));
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                assert_eq!(*chunk.get_block(&ChunkBlockIndex::new(x, y, z)), block, "{} {} {}", x, y, z);
+            }
+        }
+        block
+    }
+
+    #[test]
+    fn parses_layers() {
+        let world = FlatWorld::from_config(" stone*3, dirt * 2,,dirt_grass ").unwrap();
+        let ids: Vec<&str> = world.layers.iter().map(|block| block.id()).collect();
+        assert_eq!(ids, ["stone", "stone", "stone", "dirt", "dirt", "dirt_grass"]);
+        assert_eq!(world.surface_height(0, 0), Some(5));
+
+        assert_eq!(FlatWorld::from_config(FlatWorld::DEFAULT_LAYERS).unwrap().layers, world.layers);
+        assert!(FlatWorld::from_config("").unwrap().layers.is_empty());
+        assert!(FlatWorld::from_config("stone*0").unwrap().layers.is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_layers() {
+        assert!(FlatWorld::from_config("stone,cheese*2").is_err());
+        assert!(FlatWorld::from_config("stone*").is_err());
+        assert!(FlatWorld::from_config("stone*-1").is_err());
+        assert!(FlatWorld::from_config("stone*two").is_err());
+
+        // Counts are limited so huge ones don't abort on allocation
+        assert!(FlatWorld::from_config(&format!("stone*{}", FlatWorld::MAX_HEIGHT)).is_ok());
+        assert!(FlatWorld::from_config(&format!("stone*{}", FlatWorld::MAX_HEIGHT + 1)).is_err());
+        assert!(FlatWorld::from_config(&format!("dirt,stone*{}", FlatWorld::MAX_HEIGHT)).is_err());
+        assert!(FlatWorld::from_config(&format!("stone*{}", usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn layers_continue_in_to_the_chunk_above() {
+        let world = FlatWorld::from_config(&format!("stone*{},dirt*2,dirt_grass", Chunk::SIZE_Z - 1)).unwrap();
+        let (stone, dirt, grass) = (Block::from_id("stone"), Block::from_id("dirt"), Block::from_id("dirt_grass"));
+
+        // The first dirt layer is the top of the bottom chunk
+        let bottom = world.create_chunk(&ChunkIndex::new(0, 0, 0), &mut Vec::new());
+        assert_eq!(layer(&bottom, 0), stone);
+        assert_eq!(layer(&bottom, Chunk::SIZE_Z - 2), stone);
+        assert_eq!(layer(&bottom, Chunk::SIZE_Z - 1), dirt);
+
+        // The rest start at the bottom of the next chunk up, on every column
+        let top = world.create_chunk(&ChunkIndex::new(7, 2, 1), &mut Vec::new());
+        assert_eq!(layer(&top, 0), dirt);
+        assert_eq!(layer(&top, 1), grass);
+        assert_eq!(layer(&top, 2), Block::AIR);
+
+        let above = world.create_chunk(&ChunkIndex::new(0, 0, 2), &mut Vec::new());
+        assert_eq!(layer(&above, 0), Block::AIR);
+    }
+}
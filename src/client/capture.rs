@@ -0,0 +1,63 @@
+use super::VoxelTest;
+use crate::utils;
+use glium::backend::Facade;
+use image::RgbaImage;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A kind of screenshot waiting to be taken at the end of the frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Capture {
+    /// Reads back the frame shown in the window.
+    Screenshot,
+
+    /// Renders the view again at a multiple of the window size.
+    Tiled
+}
+
+impl VoxelTest {
+    /// Renders the view at `scale` times the given size by splitting it in to a grid of tiles the
+    /// size of the window, so the image can be larger than the GPU can render at once.
+    pub fn render_tiled_image<F>(&mut self, facade: &F, width: u32, height: u32, scale: u32) -> RgbaImage where F: Facade {
+        let mut image = RgbaImage::new(width * scale, height * scale);
+        for tile_y in 0..scale {
+            for tile_x in 0..scale {
+                self.camera.set_tile(Some(utils::ProjectionTile { count: scale, x: tile_x, y: tile_y }));
+                let tile = self.render_image(facade, width, height);
+
+                // Tiles are counted from the bottom but images from the top
+                image::imageops::replace(&mut image, &tile, tile_x * width, (scale - 1 - tile_y) * height);
+            }
+        }
+        self.camera.set_tile(None);
+
+        image
+    }
+}
+
+/// Saves a screenshot in the `screenshots` folder, named after the current time, and returns its
+/// path.
+pub fn save_screenshot(image: &RgbaImage) -> Result<PathBuf, String> {
+    let path = timestamped_path("screenshots", "screenshot", "png")?;
+    image.save(&path).map_err(|err| format!("Failed to save `{}`: {}", path.display(), err))?;
+    Ok(path)
+}
+
+/// Creates a folder if needed and returns an unused path in it named after the current time,
+/// e.g. `screenshots/screenshot-1554000000.png`.
+pub fn timestamped_path(dir: &str, name: &str, extension: &str) -> Result<PathBuf, String> {
+    let dir = PathBuf::from(dir);
+    fs::create_dir_all(&dir).map_err(|err| format!("Failed to create `{}`: {}", dir.display(), err))?;
+
+    // Add a number if a file was already saved this second
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    let mut path = dir.join(format!("{}-{}.{}", name, timestamp, extension));
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = dir.join(format!("{}-{}-{}.{}", name, timestamp, count, extension));
+    }
+
+    Ok(path)
+}
@@ -1,9 +1,39 @@
+#![allow(deprecated)]  // `implement_vertex!` uses `mem::uninitialized` internally
+
 #[derive(Copy, Clone)]
 pub struct Vertex {
     pub position: [f32; 3],
     pub color: [f32; 3],
     pub normal: [f32; 3],
-    pub uv: [f32; 2]
+    pub uv: [f32; 2],
+
+    /// The sky and block light, from 0 to 1.
+    pub light: [f32; 2]
+}
+
+implement_vertex!(Vertex, position, color, normal, uv, light);
+
+#[derive(Copy, Clone)]
+pub struct LineVertex {
+    pub position: [f32; 3]
+}
+
+implement_vertex!(LineVertex, position);
+
+/// A vertex in normalized device coordinates for drawing over the whole screen.
+#[derive(Copy, Clone)]
+pub struct ScreenVertex {
+    pub position: [f32; 2]
+}
+
+implement_vertex!(ScreenVertex, position);
+
+/// A vertex of the HUD in normalized device coordinates.
+#[derive(Copy, Clone)]
+pub struct HudVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4]
 }
 
-implement_vertex!(Vertex, position, color, normal, uv);
+implement_vertex!(HudVertex, position, uv, color);
@@ -0,0 +1,760 @@
+use super::capture;
+use super::day_cycle::DayCycle;
+use super::shadow::ShadowRenderer;
+use super::world_renderer::WorldRenderer;
+use super::Options;
+use super::VoxelTest;
+use super::WorldType;
+use crate::utils;
+use crate::world;
+use glium::backend::Facade;
+use glium::glutin;
+use std::fs;
+use vecmath::Vector3;
+
+/// A command that can be typed in to the console. Arguments in the usage are `<required>` or
+/// `[optional]`.
+struct CommandInfo {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str
+}
+
+const COMMANDS: [CommandInfo; 12] = [
+    CommandInfo { name: "help", usage: "help [command]", description: "List the commands or show how to use one" },
+    CommandInfo { name: "tp", usage: "tp <x> <y> <z>", description: "Move to a position in the world (Z is up)" },
+    CommandInfo { name: "set_block", usage: "set_block <x> <y> <z> <block>", description: "Change a block" },
+    CommandInfo { name: "fill", usage: "fill <x1> <y1> <z1> <x2> <y2> <z2> <block>", description: "Change every block in a box" },
+    CommandInfo { name: "seed", usage: "seed [seed]", description: "Show the seed, or generate the world again with a new one" },
+    CommandInfo { name: "view_distance", usage: "view_distance [chunks]", description: "Show or change how far away chunks are loaded" },
+    CommandInfo { name: "time", usage: "time [hour]", description: "Show or change the time of day" },
+    CommandInfo { name: "save", usage: "save [file]", description: "Save the blocks changed so far as commands" },
+    CommandInfo { name: "load", usage: "load <file>", description: "Run the commands in a file" },
+    CommandInfo { name: "bind", usage: "bind <action> [inputs]", description: "Show the inputs bound to an action, or bind it to comma separated inputs" },
+    CommandInfo { name: "reload_shaders", usage: "reload_shaders", description: "Compile the shaders in `assets/shaders` again" },
+    CommandInfo { name: "screenshot", usage: "screenshot [tiled]", description: "Save a screenshot, or a larger one with `tiled`" }
+];
+
+/// A command typed in to the console, with its arguments parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help(Option<String>),
+
+    /// Moves the camera and player to a position in world space.
+    Teleport(Vector3<f32>),
+
+    SetBlock(world::WorldBlockIndex, world::Block),
+
+    /// Fills the box between two corners, which are sorted so the first is the lowest.
+    Fill(world::WorldBlockIndex, world::WorldBlockIndex, world::Block),
+
+    Seed(Option<u32>),
+    ViewDistance(Option<u32>),
+    Time(Option<f32>),
+    Save(Option<String>),
+    Load(String),
+
+    /// Shows the bindings of an action, or replaces them.
+    Bind(utils::Action, Option<Vec<utils::Binding>>),
+
+    ReloadShaders,
+    Screenshot(capture::Capture)
+}
+
+impl Command {
+    /// The most blocks `fill` can change at once, so a typo can't stall the game.
+    pub const MAX_FILL: u64 = 65536;
+
+    pub const MAX_VIEW_DISTANCE: u32 = 8;
+
+    /// Parses a line typed in to the console. A `/` before the command is ignored.
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let words: Vec<&str> = line.strip_prefix('/').unwrap_or(line).split_whitespace().collect();
+        let (&name, args) = words.split_first().ok_or("Type `help` to list the commands")?;
+        let info = COMMANDS.iter().find(|info| info.name == name)
+            .ok_or_else(|| format!("Unknown command `{}`; type `help` to list the commands", name))?;
+
+        // Check the number of arguments against the usage
+        let required = info.usage.matches('<').count();
+        let optional = info.usage.matches('[').count();
+        if args.len() < required || args.len() > required + optional {
+            return Err(format!("Usage: {}", info.usage));
+        }
+
+        Ok(match name {
+            "help" => Command::Help(args.first().map(|name| name.to_string())),
+            "tp" => Command::Teleport([parse_number(args[0])?, parse_number(args[1])?, parse_number(args[2])?]),
+            "set_block" => Command::SetBlock(parse_block_index(&args[0..3])?, parse_block(args[3])?),
+            "fill" => {
+                let (a, b) = (parse_block_index(&args[0..3])?, parse_block_index(&args[3..6])?);
+                let lower = world::WorldBlockIndex::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+                let upper = world::WorldBlockIndex::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+                // Saturate, since a box across the whole world doesn't fit in a u64
+                let size = |lower: u32, upper: u32| u64::from(upper - lower) + 1;
+                let volume = size(lower.x, upper.x).saturating_mul(size(lower.y, upper.y)).saturating_mul(size(lower.z, upper.z));
+                if volume > Command::MAX_FILL {
+                    return Err(format!("Can't fill {} blocks at once; the most is {}", volume, Command::MAX_FILL));
+                }
+
+                Command::Fill(lower, upper, parse_block(args[6])?)
+            },
+            "seed" => Command::Seed(match args.first() {
+                Some(seed) => Some(seed.parse().map_err(|_| format!("Invalid seed `{}`", seed))?),
+                None => None
+            }),
+            "view_distance" => Command::ViewDistance(match args.first() {
+                Some(distance) => Some(distance.parse().ok().filter(|distance| (1..=Command::MAX_VIEW_DISTANCE).contains(distance))
+                    .ok_or_else(|| format!("Invalid view distance `{}`; expected 1 to {} chunks", distance, Command::MAX_VIEW_DISTANCE))?),
+                None => None
+            }),
+            "time" => Command::Time(match args.first() {
+                Some(hour) => Some(hour.parse().ok().filter(|hour| (0. ..=24.).contains(hour))
+                    .ok_or_else(|| format!("Invalid hour `{}`; expected 0 to 24", hour))?),
+                None => None
+            }),
+            "save" => Command::Save(args.first().map(|path| path.to_string())),
+            "load" => Command::Load(args[0].to_string()),
+            "bind" => {
+                let action = utils::Action::from_name(args[0]).ok_or_else(|| format!("Unknown action `{}`", args[0]))?;
+                let bindings = match args.get(1) {
+                    Some(inputs) => Some(inputs.split(',')
+                        .filter(|input| !input.is_empty())
+                        .map(|input| utils::Binding::from_name(input).ok_or_else(|| format!("Unknown input `{}`", input)))
+                        .collect::<Result<Vec<utils::Binding>, String>>()?),
+                    None => None
+                };
+                Command::Bind(action, bindings)
+            },
+            "reload_shaders" => Command::ReloadShaders,
+            "screenshot" => match args.first() {
+                None => Command::Screenshot(capture::Capture::Screenshot),
+                Some(&"tiled") => Command::Screenshot(capture::Capture::Tiled),
+                Some(_) => return Err(format!("Usage: {}", info.usage))
+            },
+            _ => unreachable!()
+        })
+    }
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value.parse::<f32>().ok().filter(|value| value.is_finite()).ok_or_else(|| format!("Invalid number `{}`", value))
+}
+
+/// Parses the X, Y and Z of a block in world space.
+fn parse_block_index(values: &[&str]) -> Result<world::WorldBlockIndex, String> {
+    let parse = |value: &str| value.parse::<u32>().map_err(|_| format!("Invalid block coordinate `{}`", value));
+    Ok(world::WorldBlockIndex::new(parse(values[0])?, parse(values[1])?, parse(values[2])?))
+}
+
+fn parse_block(id: &str) -> Result<world::Block, String> {
+    world::Block::try_from_id(id).ok_or_else(|| format!("Unknown block `{}`", id))
+}
+
+/// Returns the command that sets a block, as saved by `save`.
+pub fn set_block_command(index: &world::WorldBlockIndex, block: world::Block) -> String {
+    format!("set_block {} {} {} {}", index.x, index.y, index.z, block.id())
+}
+
+/// Returns the ways the last word of a partly typed line could be finished: command names for the
+/// first word, and block IDs or keywords for the arguments that take them.
+pub fn complete(line: &str) -> Vec<String> {
+    let line = line.trim_start();
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let (before, last) = match line.rfind(char::is_whitespace) {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => ("", line)
+    };
+
+    let previous: Vec<&str> = before.split_whitespace().collect();
+    let candidates: Vec<&str> = match (previous.first(), previous.len()) {
+        (None, _) | (Some(&"help"), 1) => COMMANDS.iter().map(|info| info.name).collect(),
+        (Some(&"set_block"), 4) | (Some(&"fill"), 7) => world::Block::all().map(|block| block.id()).collect(),
+        (Some(&"screenshot"), 1) => vec!["tiled"],
+        (Some(&"bind"), 1) => utils::Action::ALL.iter().map(|action| action.name()).collect(),
+        _ => Vec::new()
+    };
+
+    candidates.into_iter().filter(|candidate| candidate.starts_with(last)).map(String::from).collect()
+}
+
+/// The text typed in to the console, what it has printed and the commands run before.
+pub struct Console {
+    open: bool,
+    input: String,
+    output: Vec<String>,
+    history: Vec<String>,
+
+    /// The history entry shown in the input while stepping through it, counting from the start.
+    history_index: Option<usize>
+}
+
+impl Console {
+    /// How many lines of output and history are kept.
+    const MAX_OUTPUT: usize = 200;
+    const MAX_HISTORY: usize = 100;
+
+    pub fn new() -> Console {
+        Console {
+            open: false,
+            input: String::new(),
+            output: Vec::new(),
+            history: Vec::new(),
+            history_index: None
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens the console with an empty input, or closes it.
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.input.clear();
+        self.history_index = None;
+    }
+
+    pub fn get_input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn get_output(&self) -> &[String] {
+        &self.output
+    }
+
+    /// Adds a message to the output, which can span several lines.
+    pub fn print(&mut self, message: &str) {
+        self.output.extend(message.lines().map(String::from));
+        if self.output.len() > Console::MAX_OUTPUT {
+            let excess = self.output.len() - Console::MAX_OUTPUT;
+            self.output.drain(..excess);
+        }
+    }
+
+    /// Adds a typed character to the input, ignoring control characters like backspace.
+    pub fn type_char(&mut self, character: char) {
+        if !character.is_control() {
+            self.input.push(character);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Shows the command run before the one shown, or the last one run.
+    pub fn previous_history(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None if !self.history.is_empty() => self.history.len() - 1,
+            None => return
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// Shows the command run after the one shown, or an empty input after the last one.
+    pub fn next_history(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index + 1,
+            None => return
+        };
+        if index < self.history.len() {
+            self.history_index = Some(index);
+            self.input = self.history[index].clone();
+        } else {
+            self.history_index = None;
+            self.input.clear();
+        }
+    }
+
+    /// Finishes the last word of the input as far as every way of completing it agrees, and
+    /// prints them if there's more than one.
+    pub fn complete(&mut self) {
+        let candidates = complete(&self.input);
+        let last_len = self.input.len() - self.input.rfind(|c: char| c.is_whitespace() || c == '/').map(|i| i + 1).unwrap_or(0);
+        let prefix = match candidates.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.as_str(), |prefix, candidate| {
+                let common = prefix.chars().zip(candidate.chars()).take_while(|(a, b)| a == b).count();
+                &prefix[..common]
+            }),
+            None => return
+        };
+
+        let completed = format!("{}{}", &self.input[..self.input.len() - last_len], prefix);
+        if candidates.len() == 1 {
+            self.input = completed + " ";
+        } else {
+            self.input = completed;
+            self.print(&candidates.join("  "));
+        }
+    }
+
+    /// Takes the input to be run, adding it to the history and echoing it to the output. Returns
+    /// `None` if nothing was typed.
+    pub fn submit(&mut self) -> Option<String> {
+        let line = std::mem::take(&mut self.input).trim().to_string();
+        self.history_index = None;
+        if line.is_empty() { return None; }
+
+        self.print(&format!("> {}", line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > Console::MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+
+        Some(line)
+    }
+}
+
+/// Work a command needs the GPU for, which the game does once the command has run.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ConsoleEffect {
+    ReloadShaders,
+    Screenshot(capture::Capture),
+
+    /// The view distance changed, so the shadow cascades need to be split again.
+    ResizeShadows
+}
+
+/// The parts of the game that commands change. This leaves out the renderers that need a GPU, so
+/// commands can be run without a window.
+pub struct CommandContext<'a> {
+    pub world: &'a mut world::World,
+    pub world_renderer: &'a mut WorldRenderer,
+    pub camera: &'a mut utils::CameraState,
+    pub player: &'a mut world::Player,
+    pub day_cycle: &'a mut DayCycle,
+    pub input_map: &'a mut utils::InputMap,
+
+    /// The options the world was created with, for generating it again with a new seed.
+    pub options: &'a mut Options,
+
+    /// The blocks changed since the world was generated, as the commands that change them.
+    pub edits: &'a mut Vec<String>,
+
+    /// The work left for the game to do after the commands that have run.
+    pub effects: Vec<ConsoleEffect>
+}
+
+impl<'a> CommandContext<'a> {
+    /// Runs a command and returns what to print, which may be empty.
+    pub fn run(&mut self, command: Command) -> Result<String, String> {
+        match command {
+            Command::Help(None) => Ok(COMMANDS.iter()
+                .map(|info| format!("{} - {}", info.name, info.description))
+                .collect::<Vec<String>>()
+                .join("\n")),
+            Command::Help(Some(name)) => COMMANDS.iter().find(|info| info.name == name)
+                .map(|info| format!("{} - {}", info.usage, info.description))
+                .ok_or_else(|| format!("Unknown command `{}`", name)),
+
+            Command::Teleport(position) => {
+                self.camera.set_position([position[0], position[2], position[1]]);  // Flip Y with Z
+                *self.player = world::Player::new([position[0], position[1], position[2] - world::Player::EYE_HEIGHT]);
+                Ok(format!("Moved to {:.1}, {:.1}, {:.1}", position[0], position[1], position[2]))
+            },
+
+            Command::SetBlock(index, block) => {
+                self.world.set_block(&index, block);
+                self.edits.push(set_block_command(&index, block));
+                Ok(format!("Set {} to {}", index, block.id()))
+            },
+
+            Command::Fill(lower, upper, block) => {
+                self.world.fill_box(block, &lower, &upper);
+                self.edits.push(format!("fill {} {} {} {} {} {} {}", lower.x, lower.y, lower.z, upper.x, upper.y, upper.z, block.id()));
+
+                let count = (upper.x - lower.x + 1) * (upper.y - lower.y + 1) * (upper.z - lower.z + 1);
+                Ok(format!("Filled {} blocks with {}", count, block.id()))
+            },
+
+            Command::Seed(None) => Ok(format!("Seed {}", self.options.seed)),
+            Command::Seed(Some(seed)) => {
+                if self.options.world_type != WorldType::Procedural {
+                    return Err(format!("The {} world doesn't use a seed", self.options.world_type.name()));
+                }
+
+                // Start again with a new world; the changes were to the old one
+                self.options.seed = seed;
+                *self.world = world::World::new(self.options.create_world_delegate()?);
+                self.world_renderer.clear();
+                self.edits.clear();
+                Ok(format!("Generating the world with seed {}", seed))
+            },
+
+            Command::ViewDistance(None) => Ok(format!("View distance {} chunks", self.world_renderer.get_view_distance())),
+            Command::ViewDistance(Some(distance)) => {
+                self.world_renderer.set_view_distance(distance);
+                self.effects.push(ConsoleEffect::ResizeShadows);
+                Ok(format!("View distance set to {} chunks", distance))
+            },
+
+            Command::Time(None) => Ok(format!("Time {}", self.day_cycle.get_clock())),
+            Command::Time(Some(hour)) => {
+                self.day_cycle.set_hour(hour);
+                Ok(format!("Time set to {}", self.day_cycle.get_clock()))
+            },
+
+            Command::Save(path) => {
+                let path = match path {
+                    Some(path) => path,
+                    None => capture::timestamped_path("saves", "world", "txt")?.to_string_lossy().into_owned()
+                };
+
+                // Start from the same world, so loading the file gives the same result
+                let mut contents = format!("# Blocks changed in the {} world\n", self.options.world_type.name());
+                if self.options.world_type == WorldType::Procedural {
+                    contents.push_str(&format!("seed {}\n", self.options.seed));
+                }
+                for edit in self.edits.iter() {
+                    contents.push_str(edit);
+                    contents.push('\n');
+                }
+
+                fs::write(&path, contents).map_err(|err| format!("Failed to save `{}`: {}", path, err))?;
+                Ok(format!("Saved {} changes to {}", self.edits.len(), path))
+            },
+
+            Command::Load(path) => {
+                let contents = fs::read_to_string(&path).map_err(|err| format!("Failed to read `{}`: {}", path, err))?;
+                let mut count = 0;
+                for (number, line) in contents.lines().enumerate() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') { continue; }
+
+                    let error = |message: String| format!("`{}` line {}: {}", path, number + 1, message);
+                    match Command::parse(line).map_err(error)? {
+                        Command::Load(_) => return Err(error("Files can't load other files".to_string())),
+                        command => { self.run(command).map_err(error)?; }
+                    }
+                    count += 1;
+                }
+                Ok(format!("Ran {} commands from {}", count, path))
+            },
+
+            Command::Bind(action, None) => Ok(format!("{} = {}", action.name(), format_bindings(&self.input_map.get_bindings(action)))),
+            Command::Bind(action, Some(bindings)) => {
+                // This checks for conflicts with the other actions without changing anything
+                self.input_map.bind(action, &bindings)?;
+                Ok(format!("Bound {} to {}", action.name(), format_bindings(&bindings)))
+            },
+
+            Command::ReloadShaders => {
+                self.effects.push(ConsoleEffect::ReloadShaders);
+                Ok(String::new())
+            },
+
+            Command::Screenshot(capture) => {
+                self.effects.push(ConsoleEffect::Screenshot(capture));
+                Ok(String::new())
+            }
+        }
+    }
+}
+
+fn format_bindings(bindings: &[utils::Binding]) -> String {
+    if bindings.is_empty() { return "nothing".to_string(); }
+
+    bindings.iter().map(|binding| binding.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl VoxelTest {
+    /// Opens or closes the console. Held movement keys are let go of, since their release goes to
+    /// the console instead of the camera.
+    pub fn set_console_open(&mut self, open: bool) {
+        self.console.set_open(open);
+        for &action in utils::Action::ALL.iter() {
+            self.camera.process_action(action, false);
+        }
+    }
+
+    /// Types in to the console while it's open.
+    pub fn process_console_event<F>(&mut self, facade: &F, event: &glutin::Event) where F: Facade {
+        let event = match *event {
+            glutin::Event::WindowEvent { ref event, .. } => event,
+            _ => return
+        };
+
+        match *event {
+            glutin::WindowEvent::ReceivedCharacter(character) => self.console.type_char(character),
+            glutin::WindowEvent::KeyboardInput { input: glutin::KeyboardInput { state: glutin::ElementState::Pressed, virtual_keycode: Some(key), .. }, .. } => match key {
+                glutin::VirtualKeyCode::Escape => self.set_console_open(false),
+                glutin::VirtualKeyCode::Back => self.console.backspace(),
+                glutin::VirtualKeyCode::Tab => self.console.complete(),
+                glutin::VirtualKeyCode::Up => self.console.previous_history(),
+                glutin::VirtualKeyCode::Down => self.console.next_history(),
+                glutin::VirtualKeyCode::Return | glutin::VirtualKeyCode::NumpadEnter => {
+                    if let Some(line) = self.console.submit() {
+                        self.run_console_command(facade, &line);
+                    }
+                },
+                _ => { }
+            },
+            _ => { }
+        }
+    }
+
+    /// Runs a line typed in to the console and prints the result.
+    pub fn run_console_command<F>(&mut self, facade: &F, line: &str) where F: Facade {
+        let mut context = CommandContext {
+            world: &mut self.world,
+            world_renderer: &mut self.world_renderer,
+            camera: &mut self.camera,
+            player: &mut self.player,
+            day_cycle: &mut self.day_cycle,
+            input_map: &mut self.input_map,
+            options: &mut self.options,
+            edits: &mut self.edits,
+            effects: Vec::new()
+        };
+        let result = Command::parse(line).and_then(|command| context.run(command));
+        let effects = context.effects;
+        match result {
+            Ok(ref message) if message.is_empty() => { },
+            Ok(message) => self.console.print(&message),
+            Err(err) => self.console.print(&format!("Error: {}", err))
+        }
+
+        // Do the work that needs the GPU, even if a later command in a file failed
+        for effect in effects {
+            match effect {
+                ConsoleEffect::ReloadShaders => match self.program_register.reload(facade) {
+                    Ok(()) => self.console.print("Reloaded shaders"),
+                    Err(err) => self.console.print(&format!("Error: {}", err))
+                },
+                ConsoleEffect::Screenshot(capture) => {
+                    // Close the console so it isn't in the picture
+                    self.set_console_open(false);
+                    self.pending_capture = Some(capture);
+                },
+                ConsoleEffect::ResizeShadows => self.shadows = ShadowRenderer::new(facade, &self.world_renderer)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The parts of the game commands change, for a flat world.
+    struct TestGame {
+        world: world::World,
+        world_renderer: WorldRenderer,
+        camera: utils::CameraState,
+        player: world::Player,
+        day_cycle: DayCycle,
+        input_map: utils::InputMap,
+        options: Options,
+        edits: Vec<String>
+    }
+
+    impl TestGame {
+        fn new() -> TestGame {
+            let options = Options { world_type: WorldType::Flat, ..Options::default() };
+            TestGame {
+                world: world::World::new(options.create_world_delegate().unwrap()),
+                world_renderer: WorldRenderer::new(4, options.mesh_options.clone()),
+                camera: utils::CameraState::new([0., 0., 0.], [0., 0., 1.], options.camera_settings),
+                player: world::Player::new([0., 0., 0.]),
+                day_cycle: DayCycle::new(12., true),
+                input_map: utils::InputMap::default(),
+                options,
+                edits: Vec::new()
+            }
+        }
+
+        fn run(&mut self, line: &str) -> Result<String, String> {
+            let mut context = CommandContext {
+                world: &mut self.world,
+                world_renderer: &mut self.world_renderer,
+                camera: &mut self.camera,
+                player: &mut self.player,
+                day_cycle: &mut self.day_cycle,
+                input_map: &mut self.input_map,
+                options: &mut self.options,
+                edits: &mut self.edits,
+                effects: Vec::new()
+            };
+            Command::parse(line).and_then(|command| context.run(command))
+        }
+    }
+
+    #[test]
+    fn parse_bind() {
+        let binding = |name: &str| utils::Binding::from_name(name).unwrap();
+        assert_eq!(Command::parse("bind move_up").unwrap(), Command::Bind(utils::Action::MoveUp, None));
+        assert_eq!(
+            Command::parse("bind move_up space,shift+f2").unwrap(),
+            Command::Bind(utils::Action::MoveUp, Some(vec![binding("space"), binding("shift+f2")]))
+        );
+        assert!(Command::parse("bind fly space").is_err());
+        assert!(Command::parse("bind move_up not_a_key").is_err());
+        assert!(Command::parse("bind").is_err());
+        assert_eq!(complete("bind move_u"), vec!["move_up"]);
+    }
+
+    #[test]
+    fn bind_checks_conflicts() {
+        let mut game = TestGame::new();
+        let jump = game.input_map.get_bindings(utils::Action::MoveUp);
+        let jump_names: Vec<String> = jump.iter().map(|binding| binding.to_string()).collect();
+
+        // Taking another action's input fails without changing anything
+        assert!(game.run(&format!("bind move_down {}", jump_names[0])).is_err());
+        assert_eq!(game.input_map.get_bindings(utils::Action::MoveUp), jump);
+
+        game.run("bind move_up f12").unwrap();
+        assert_eq!(game.input_map.get_bindings(utils::Action::MoveUp), vec![utils::Binding::from_name("f12").unwrap()]);
+        assert_eq!(game.run("bind move_up").unwrap(), "move_up = f12");
+
+        // The old input is free again
+        game.run(&format!("bind move_down {}", jump_names[0])).unwrap();
+    }
+
+    #[test]
+    fn parse_checks_arguments() {
+        assert_eq!(Command::parse("tp 1 2 3").unwrap(), Command::Teleport([1., 2., 3.]));
+        assert_eq!(Command::parse("tp 1 2").unwrap_err(), "Usage: tp <x> <y> <z>");
+        assert_eq!(Command::parse("tp 1 2 3 4").unwrap_err(), "Usage: tp <x> <y> <z>");
+        assert!(Command::parse("tp 1 2 NaN").is_err());
+        assert!(Command::parse("tp 1 2 inf").is_err());
+
+        assert!(Command::parse("view_distance 0").is_err());
+        assert!(Command::parse(&format!("view_distance {}", Command::MAX_VIEW_DISTANCE + 1)).is_err());
+        assert_eq!(Command::parse("view_distance 1").unwrap(), Command::ViewDistance(Some(1)));
+
+        assert!(Command::parse("time 25").is_err());
+        assert!(Command::parse("time -1").is_err());
+        assert_eq!(Command::parse("time 24").unwrap(), Command::Time(Some(24.)));
+        assert_eq!(Command::parse("time").unwrap(), Command::Time(None));
+
+        assert!(Command::parse("set_block 1 2 -3 stone").is_err());
+        assert!(Command::parse("set_block 1 2 3 not_a_block").is_err());
+        assert!(Command::parse("screenshot big").is_err());
+        assert!(Command::parse("fly").is_err());
+        assert!(Command::parse("").is_err());
+    }
+
+    #[test]
+    fn parse_ignores_leading_slash() {
+        assert_eq!(Command::parse("/tp 1 2 3").unwrap(), Command::Teleport([1., 2., 3.]));
+        assert_eq!(Command::parse("  /help  ").unwrap(), Command::Help(None));
+        assert_eq!(complete("/se"), vec!["set_block", "seed"]);
+    }
+
+    #[test]
+    fn fill_sorts_corners() {
+        let stone = world::Block::from_id("stone");
+        assert_eq!(
+            Command::parse("fill 5 1 8 2 6 3 stone").unwrap(),
+            Command::Fill(world::WorldBlockIndex::new(2, 1, 3), world::WorldBlockIndex::new(5, 6, 8), stone)
+        );
+    }
+
+    #[test]
+    fn fill_has_a_limit() {
+        // 64 * 64 * 16 is exactly the limit
+        assert!(Command::parse("fill 0 0 0 63 63 15 stone").is_ok());
+        let err = Command::parse("fill 0 0 0 63 63 16 stone").unwrap_err();
+        assert_eq!(err, format!("Can't fill 69632 blocks at once; the most is {}", Command::MAX_FILL));
+
+        // The volume doesn't overflow at the edges of the world
+        assert!(Command::parse(&format!("fill 0 0 0 {0} {0} {0} stone", u32::MAX)).is_err());
+    }
+
+    #[test]
+    fn completes_commands_and_blocks() {
+        assert_eq!(complete("view"), vec!["view_distance"]);
+        assert_eq!(complete("help rel"), vec!["reload_shaders"]);
+        assert_eq!(complete("set_block 1 2 3 brick"), vec!["brick_stone"]);
+        assert_eq!(complete("fill 1 2 3 4 5 6 dirt_g"), vec!["dirt_grass", "dirt_gravel"]);
+        assert_eq!(complete("screenshot t"), vec!["tiled"]);
+        assert!(complete("tp 1").is_empty());
+
+        // One match is finished with a space, several are finished as far as they agree
+        let mut console = Console::new();
+        "/reload".chars().for_each(|character| console.type_char(character));
+        console.complete();
+        assert_eq!(console.get_input(), "/reload_shaders ");
+
+        let mut console = Console::new();
+        "fill 1 2 3 4 5 6 dirt_g".chars().for_each(|character| console.type_char(character));
+        console.complete();
+        assert_eq!(console.get_input(), "fill 1 2 3 4 5 6 dirt_gra");
+        assert_eq!(console.get_output().last().unwrap(), "dirt_grass  dirt_gravel");
+    }
+
+    #[test]
+    fn steps_through_history() {
+        let mut console = Console::new();
+        for line in ["time 6", "time 6", "seed", " ", "help"].iter() {
+            line.chars().for_each(|character| console.type_char(character));
+            console.submit();
+        }
+
+        // Running a command twice in a row only adds it once, and empty lines aren't added
+        console.previous_history();
+        assert_eq!(console.get_input(), "help");
+        console.previous_history();
+        assert_eq!(console.get_input(), "seed");
+        console.previous_history();
+        assert_eq!(console.get_input(), "time 6");
+        console.previous_history();
+        assert_eq!(console.get_input(), "time 6");
+
+        console.next_history();
+        assert_eq!(console.get_input(), "seed");
+        console.next_history();
+        console.next_history();
+        assert_eq!(console.get_input(), "");
+
+        // Submitting starts from the end of the history again
+        console.previous_history();
+        console.previous_history();
+        assert_eq!(console.submit(), Some("seed".to_string()));
+        console.previous_history();
+        assert_eq!(console.get_input(), "seed");
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("voxel-test-console-{}.txt", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let brick = world::Block::from_id("brick_stone");
+
+        let mut game = TestGame::new();
+        game.run("set_block 3 4 20 brick_stone").unwrap();
+        game.run("fill 10 10 20 8 9 21 stone").unwrap();
+        assert_eq!(game.run(&format!("save {}", path)).unwrap(), format!("Saved 2 changes to {}", path));
+
+        let mut loaded = TestGame::new();
+        let result = loaded.run(&format!("load {}", path));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap(), format!("Ran 2 commands from {}", path));
+
+        assert_eq!(loaded.edits, game.edits);
+        assert_eq!(loaded.world.get_block(&world::WorldBlockIndex::new(3, 4, 20)), brick);
+        for x in 8..=10 {
+            for y in 9..=10 {
+                for z in 20..=21 {
+                    assert_eq!(loaded.world.get_block(&world::WorldBlockIndex::new(x, y, z)), world::Block::from_id("stone"));
+                }
+            }
+        }
+        assert_eq!(loaded.world.get_block(&world::WorldBlockIndex::new(3, 4, 21)), world::Block::AIR);
+    }
+
+    #[test]
+    fn time_shows_clock() {
+        let mut game = TestGame::new();
+        assert_eq!(game.run("time 18.5").unwrap(), "Time set to 18:30");
+        assert_eq!(game.run("time").unwrap(), "Time 18:30");
+        assert!(game.run("seed 5").is_err());
+    }
+}
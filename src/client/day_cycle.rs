@@ -0,0 +1,119 @@
+use std::f32;
+use vecmath::*;
+
+/// Keeps track of the time of day and works out how the sun and sky look at that time.
+pub struct DayCycle {
+    /// The time of day from 0 to 1, where 0 is midnight and 0.5 is noon.
+    time: f32,
+
+    /// How many seconds a full day lasts.
+    day_length: f32,
+
+    /// If time stands still.
+    frozen: bool
+}
+
+impl DayCycle {
+    pub const DEFAULT_DAY_LENGTH: f32 = 600.;
+
+    /// How bright the sky light is at night, compared to the day.
+    const NIGHT_BRIGHTNESS: f32 = 0.15;
+
+    const DAY_SKY_COLOR: [f32; 3] = [0.38, 0.68, 0.96];
+    const DAY_HORIZON_COLOR: [f32; 3] = [0.623, 0.929, 0.988];
+    const NIGHT_SKY_COLOR: [f32; 3] = [0.01, 0.01, 0.04];
+    const NIGHT_HORIZON_COLOR: [f32; 3] = [0.04, 0.05, 0.1];
+    const SUNSET_COLOR: [f32; 3] = [0.98, 0.52, 0.28];
+
+    /// Creates a clock starting at the given hour of the day.
+    pub fn new(hour: f32, frozen: bool) -> DayCycle {
+        let mut day_cycle = DayCycle {
+            time: 0.,
+            day_length: DayCycle::DEFAULT_DAY_LENGTH,
+            frozen
+        };
+        day_cycle.set_hour(hour);
+        day_cycle
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if self.frozen { return; }
+
+        self.time = (self.time + dt / self.day_length).rem_euclid(1.);
+    }
+
+    /// Returns the hour of the day from 0 to 24.
+    pub fn get_hour(&self) -> f32 {
+        self.time * 24.
+    }
+
+    /// Returns the time of day as a 24 hour clock, e.g. `18:30`.
+    pub fn get_clock(&self) -> String {
+        let hour = self.get_hour();
+        format!("{:02}:{:02}", hour as u32, (hour.fract() * 60.) as u32)
+    }
+
+    pub fn set_hour(&mut self, hour: f32) {
+        self.time = (hour / 24.).rem_euclid(1.);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Returns the direction pointing towards the sun in camera space (Y is up). The sun rises
+    /// along +X at 6:00, is highest at noon, and sets along -X at 18:00.
+    pub fn get_sun_direction(&self) -> Vector3<f32> {
+        let angle = (self.time - 0.25) * 2. * f32::consts::PI;
+
+        // Tilt the path of the sun a little so shadows aren't lined up with the blocks
+        vec3_normalized([angle.cos(), angle.sin(), 0.35])
+    }
+
+    /// Returns the direction of whatever lights up the world, which is the sun during the day and
+    /// the moon, opposite of the sun, at night.
+    pub fn get_light_direction(&self) -> Vector3<f32> {
+        let sun = self.get_sun_direction();
+        if sun[1] >= 0. { sun } else { vec3_neg(sun) }
+    }
+
+    /// Returns how far it is in to the day, from 0 at night to 1 during the day, blending through
+    /// dawn and dusk.
+    pub fn get_daylight(&self) -> f32 {
+        let height = self.get_sun_direction()[1];
+        let t = ((height + 0.1) / 0.35).clamp(0., 1.);
+        t * t * (3. - 2. * t)  // Smoothstep
+    }
+
+    /// Returns how bright the sky light is from 0 to 1.
+    pub fn get_sky_brightness(&self) -> f32 {
+        DayCycle::NIGHT_BRIGHTNESS + (1. - DayCycle::NIGHT_BRIGHTNESS) * self.get_daylight()
+    }
+
+    /// Returns the color of the sky straight up.
+    pub fn get_sky_color(&self) -> [f32; 3] {
+        DayCycle::mix(DayCycle::NIGHT_SKY_COLOR, DayCycle::DAY_SKY_COLOR, self.get_daylight())
+    }
+
+    /// Returns the color of the sky at the horizon, which is orange when the sun is rising or
+    /// setting.
+    pub fn get_horizon_color(&self) -> [f32; 3] {
+        let color = DayCycle::mix(DayCycle::NIGHT_HORIZON_COLOR, DayCycle::DAY_HORIZON_COLOR, self.get_daylight());
+        let sunset = (1. - self.get_sun_direction()[1].abs() / 0.25).max(0.);
+        DayCycle::mix(color, DayCycle::SUNSET_COLOR, sunset * 0.6)
+    }
+
+    /// Returns the color far away things fade in to, which matches the horizon so the world blends
+    /// in to the sky.
+    pub fn get_fog_color(&self) -> [f32; 3] {
+        self.get_horizon_color()
+    }
+
+    fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+        [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+    }
+}
@@ -0,0 +1,144 @@
+use super::capture;
+use super::Options;
+use super::VoxelTest;
+use crate::utils;
+use std::fs;
+use std::path::Path;
+
+/// A camera path being recorded or played back, and how far through it is.
+pub struct PathProgress {
+    path: utils::CameraPath,
+    time: f32
+}
+
+impl PathProgress {
+    pub fn new(path: utils::CameraPath) -> PathProgress {
+        PathProgress { path, time: 0. }
+    }
+}
+
+impl VoxelTest {
+    /// How often a keyframe is recorded, in seconds.
+    const RECORD_INTERVAL: f32 = 0.1;
+
+    /// Starts recording the camera, or stops and saves the recording to the `paths` folder.
+    pub fn toggle_path_recording(&mut self) {
+        let mut recording = match self.path_recording.take() {
+            Some(recording) => recording,
+            None => {
+                info!("Recording camera path");
+                let mut recording = PathProgress::new(utils::CameraPath::new());
+                self.record_keyframe(&mut recording);
+                self.path_recording = Some(recording);
+                return;
+            }
+        };
+
+        // Finish on where the camera stopped
+        self.record_keyframe(&mut recording);
+
+        let saved = capture::timestamped_path("paths", "path", "txt")
+            .and_then(|path| recording.path.save(&path.to_string_lossy()).map(|_| path));
+        match saved {
+            Ok(path) => info!("Saved camera path {} - {:.1}s", path.display(), recording.path.get_duration()),
+            Err(err) => error!("{}", err)
+        }
+
+        // Play this path next
+        self.camera_path = Some(recording.path);
+    }
+
+    /// Adds a keyframe to the recording every `RECORD_INTERVAL`.
+    pub fn update_path_recording(&mut self, dt: f32) {
+        let mut recording = match self.path_recording.take() {
+            Some(recording) => recording,
+            None => return
+        };
+
+        recording.time += dt;
+        if recording.time - recording.path.get_duration() >= VoxelTest::RECORD_INTERVAL {
+            self.record_keyframe(&mut recording);
+        }
+
+        self.path_recording = Some(recording);
+    }
+
+    fn record_keyframe(&self, recording: &mut PathProgress) {
+        let keyframe = utils::CameraKeyframe {
+            time: recording.time,
+            position: self.camera.get_world_position(),
+            direction: self.camera.get_world_direction()
+        };
+
+        // This only fails if no time has passed since the last keyframe, which can be skipped
+        let _ = recording.path.add_keyframe(keyframe);
+    }
+
+    /// Plays the last recorded or loaded camera path from the start, or stops playing it.
+    pub fn toggle_path_playback(&mut self) {
+        if self.path_playback.take().is_some() {
+            info!("Stopped playing camera path");
+        } else if let Some(ref path) = self.camera_path {
+            info!("Playing camera path - {:.1}s", path.get_duration());
+            self.path_playback = Some(PathProgress::new(path.clone()));
+        } else {
+            warn!("There's no camera path to play; record one first");
+        }
+    }
+
+    /// Moves the camera along the path being played.
+    pub fn update_path_playback(&mut self, dt: f32) {
+        let mut playback = match self.path_playback.take() {
+            Some(playback) => playback,
+            None => return
+        };
+
+        playback.time += dt;
+        self.set_camera_from_path(&playback.path, playback.time);
+
+        if playback.time < playback.path.get_duration() {
+            self.path_playback = Some(playback);
+        } else {
+            info!("Finished playing camera path");
+        }
+    }
+
+    fn set_camera_from_path(&mut self, path: &utils::CameraPath, time: f32) {
+        if let Some((position, direction)) = path.sample(time) {
+            self.camera.set_position([position[0], position[2], position[1]]);  // Flip Y with Z
+            self.camera.set_direction([direction[0], direction[2], direction[1]]);  // Flip Y with Z
+        }
+    }
+}
+
+/// Renders the path passed with `--play-path` off screen at a fixed frame rate and saves each
+/// frame to `<dir>/frame-<n>.png`. Every step takes the same time whatever the machine, so the
+/// frames are the same each run and can be made in to a video.
+pub fn dump_frames(mut options: Options, dir: &str) -> Result<(), String> {
+    let path = options.play_path.take().ok_or("`--dump-frames` needs a camera path to play with `--play-path`")?;
+    options.gamepad = None;
+
+    let headless = utils::Headless::new()?;
+    let mut voxel_test = VoxelTest::new(&headless.renderer, options.create_world_delegate()?, &options);
+
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir).map_err(|err| format!("Failed to create `{}`: {}", dir.display(), err))?;
+
+    // Render a frame for every step, including one at the very end
+    let dt = 1. / options.frame_rate as f32;
+    let frames = (path.get_duration() * options.frame_rate as f32).floor() as u32 + 1;
+    let [width, height] = options.frame_size;
+    for frame in 0..frames {
+        voxel_test.set_camera_from_path(&path, frame as f32 * dt);
+        let image = voxel_test.render_image(&headless.renderer, width, height);
+
+        let frame_path = dir.join(format!("frame-{:05}.png", frame));
+        image.save(&frame_path).map_err(|err| format!("Failed to save `{}`: {}", frame_path.display(), err))?;
+
+        // Move the sun on for the next frame
+        voxel_test.day_cycle.update(dt);
+    }
+
+    info!("Saved {} frames to {}", frames, dir.display());
+    Ok(())
+}
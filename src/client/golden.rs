@@ -0,0 +1,183 @@
+use super::Options;
+use super::VoxelTest;
+use crate::utils;
+use glium::backend::Facade;
+use image::RgbaImage;
+use std::path::Path;
+use vecmath::Vector3;
+
+/// A fixed world and camera rendered for comparing against a golden image.
+struct GoldenScene {
+    name: &'static str,
+
+    /// Command line options for the world and renderer, as passed to `Options::from_args`. Time
+    /// is always frozen and the gamepad is ignored.
+    args: &'static str,
+
+    /// The position and direction of the camera in world space (Z is up).
+    position: Vector3<f32>,
+    direction: Vector3<f32>
+}
+
+impl GoldenScene {
+    fn render<F>(&self, facade: &F) -> Result<RgbaImage, String> where F: Facade {
+        let mut options = Options::from_args(self.args.split_whitespace().map(String::from))?;
+        options.freeze_time = true;
+        options.gamepad = None;
+        let mut voxel_test = VoxelTest::new(facade, options.create_world_delegate()?, &options);
+
+        let (position, direction) = (self.position, self.direction);
+        voxel_test.camera.set_position([position[0], position[2], position[1]]);  // Flip Y with Z
+        voxel_test.camera.set_direction([direction[0], direction[2], direction[1]]);  // Flip Y with Z
+
+        Ok(voxel_test.render_image(facade, GOLDEN_WIDTH, GOLDEN_HEIGHT))
+    }
+}
+
+const GOLDEN_WIDTH: u32 = 320;
+const GOLDEN_HEIGHT: u32 = 240;
+
+/// How far apart a channel of a pixel can be before the pixel counts as different. This allows
+/// for small differences between rasterizers.
+const PIXEL_TOLERANCE: u8 = 8;
+
+const SCENES: [GoldenScene; 4] = [
+    GoldenScene { name: "flat_noon", args: "--world flat --time 12", position: [20., 20., 14.], direction: [1., 1., -0.5] },
+    GoldenScene { name: "debug_shapes", args: "--world debug --time 9", position: [48., 44., 16.], direction: [0., -1., -0.4] },
+    GoldenScene { name: "procedural_sunset", args: "--world procedural --time 18.5", position: [32., 32., 185.], direction: [1., 0.3, -0.3] },
+    GoldenScene { name: "bevel_night", args: "--world debug --bevel 0.1 --time 22", position: [16., 36., 10.], direction: [0., -1., -0.3] },
+];
+
+/// How much an image differs from the golden image.
+struct ImageDifference {
+    /// The number of pixels that are too far apart.
+    mismatched_pixels: usize,
+
+    /// Highlights the mismatched pixels in red.
+    diff: RgbaImage
+}
+
+impl ImageDifference {
+    fn compare(actual: &RgbaImage, expected: &RgbaImage) -> Result<ImageDifference, String> {
+        if actual.dimensions() != expected.dimensions() {
+            return Err(format!("Expected a {:?} image but rendered {:?}", expected.dimensions(), actual.dimensions()));
+        }
+
+        let mut mismatched_pixels = 0;
+        let mut diff = RgbaImage::new(actual.width(), actual.height());
+        for (x, y, pixel) in actual.enumerate_pixels() {
+            let expected_pixel = expected.get_pixel(x, y);
+            let mismatched = (0..4).any(|channel| (i16::from(pixel[channel]) - i16::from(expected_pixel[channel])).abs() > i16::from(PIXEL_TOLERANCE));
+            if mismatched {
+                mismatched_pixels += 1;
+                diff.put_pixel(x, y, image::Rgba([255, 0, 0, 255]));
+            } else {
+                // Show the matching pixels faded so the mismatches are easy to place
+                let faded = |channel: u8| channel / 4;
+                diff.put_pixel(x, y, image::Rgba([faded(pixel[0]), faded(pixel[1]), faded(pixel[2]), 255]));
+            }
+        }
+
+        Ok(ImageDifference { mismatched_pixels, diff })
+    }
+}
+
+/// Renders each scene off screen and compares it against `<dir>/<name>.png`, or overwrites the
+/// golden images if `update` is set. `tolerance` is the fraction of pixels allowed to differ.
+/// When a scene doesn't match, the render and a diff are saved next to the golden image.
+pub fn run_golden_tests(dir: &str, update: bool, tolerance: f32) -> Result<(), String> {
+    let headless = utils::Headless::new()?;
+    let dir = Path::new(dir);
+
+    let mut failures = Vec::new();
+    for scene in SCENES.iter() {
+        let image = scene.render(&headless.renderer)?;
+        let golden_path = dir.join(format!("{}.png", scene.name));
+        let save = |image: &RgbaImage, suffix: &str| {
+            let path = dir.join(format!("{}{}.png", scene.name, suffix));
+            image.save(&path).map_err(|err| format!("Failed to save `{}`: {}", path.display(), err))
+        };
+
+        // Save the new golden image
+        if update {
+            save(&image, "")?;
+            println!("Updated {}", golden_path.display());
+            continue;
+        }
+
+        // Compare against the golden image
+        let expected = match image::open(&golden_path) {
+            Ok(expected) => expected.to_rgba(),
+            Err(err) => {
+                save(&image, ".actual")?;
+                println!("FAIL {} - failed to open golden image: {}", scene.name, err);
+                failures.push(scene.name);
+                continue;
+            }
+        };
+        let difference = ImageDifference::compare(&image, &expected)?;
+        let fraction = difference.mismatched_pixels as f32 / (image.width() * image.height()) as f32;
+        if fraction > tolerance {
+            save(&image, ".actual")?;
+            save(&difference.diff, ".diff")?;
+            println!("FAIL {} - {} pixels differ ({:.3}%)", scene.name, difference.mismatched_pixels, fraction * 100.);
+            failures.push(scene.name);
+        } else {
+            println!("ok {} - {} pixels differ ({:.3}%)", scene.name, difference.mismatched_pixels, fraction * 100.);
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} of {} golden images differ: {}", failures.len(), SCENES.len(), failures.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_image(pixel: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(4, 4, image::Rgba(pixel))
+    }
+
+    #[test]
+    fn compare_within_tolerance() {
+        let expected = solid_image([100, 100, 100, 255]);
+        let actual = solid_image([100 + PIXEL_TOLERANCE, 100 - PIXEL_TOLERANCE, 100, 255]);
+        assert_eq!(ImageDifference::compare(&actual, &expected).unwrap().mismatched_pixels, 0);
+    }
+
+    #[test]
+    fn compare_mismatched_pixels() {
+        let expected = solid_image([100, 100, 100, 255]);
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 2, image::Rgba([100, 100, 101 + PIXEL_TOLERANCE, 255]));
+        actual.put_pixel(3, 0, image::Rgba([0, 0, 0, 255]));
+
+        let difference = ImageDifference::compare(&actual, &expected).unwrap();
+        assert_eq!(difference.mismatched_pixels, 2);
+        assert_eq!(*difference.diff.get_pixel(1, 2), image::Rgba([255, 0, 0, 255]));
+        assert_eq!(*difference.diff.get_pixel(0, 0), image::Rgba([25, 25, 25, 255]));
+    }
+
+    #[test]
+    fn compare_different_sizes() {
+        let expected = solid_image([0, 0, 0, 255]);
+        let actual = RgbaImage::new(2, 2);
+        assert!(ImageDifference::compare(&actual, &expected).is_err());
+    }
+
+    /// Renders the scenes and compares them against the golden images in the repository. This
+    /// needs Mesa and is slow without optimizations, so run it with
+    /// `cargo test --release -- --ignored golden_images`.
+    #[test]
+    #[ignore]
+    fn golden_images() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+        if let Err(err) = run_golden_tests(dir, false, 0.001) {
+            panic!("{}", err);
+        }
+    }
+}
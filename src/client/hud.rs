@@ -0,0 +1,327 @@
+use super::cg;
+use super::world_renderer::WorldRenderer;
+use super::VoxelTest;
+use crate::world;
+use glium::backend::Facade;
+use glium::Surface;
+use std::collections::VecDeque;
+
+/// A 5x7 pixel font. Each glyph is seven rows from the top, with the leftmost pixel in the highest
+/// of the five bits. Lowercase letters are drawn as uppercase.
+const GLYPHS: [(char, [u8; 7]); 60] = [
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    ('A', [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('[', [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E]),
+    (']', [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E]),
+    ('<', [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02]),
+    ('>', [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08]),
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('\'', [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+    ('"', [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('`', [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00]),
+    ('*', [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00]),
+    ('|', [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
+
+/// Draws text and a graph of recent frame times over the game.
+pub struct HudRenderer {
+    /// The glyphs side by side, followed by a solid cell for drawing boxes.
+    font: glium::texture::Texture2d,
+
+    /// The most recent frame times in seconds, oldest first.
+    frame_times: VecDeque<f32>
+}
+
+impl HudRenderer {
+    const GLYPH_WIDTH: u32 = 5;
+    const GLYPH_HEIGHT: u32 = 7;
+
+    /// The space each character takes on screen before scaling, including the gap after it.
+    const CHAR_WIDTH: f32 = 6.;
+    const LINE_HEIGHT: f32 = 9.;
+
+    /// How many screen pixels each font pixel covers.
+    const SCALE: f32 = 2.;
+
+    /// The gap between the edge of the screen and the panel, and the panel and its contents.
+    const MARGIN: f32 = 8.;
+    const PADDING: f32 = 6.;
+
+    /// How many frames the graph shows, how wide each bar is and how tall a bar is at `GRAPH_MAX`
+    /// seconds.
+    const GRAPH_FRAMES: usize = 120;
+    const GRAPH_BAR_WIDTH: f32 = 2.;
+    const GRAPH_HEIGHT: f32 = 60.;
+    const GRAPH_MAX: f32 = 1. / 20.;
+
+    /// How many lines of output the console shows.
+    const CONSOLE_LINES: usize = 12;
+
+    pub fn new<F>(facade: &F) -> HudRenderer where F: Facade {
+        // Draw each glyph in to its own cell in white, so the shader can color it
+        let cells = GLYPHS.len() as u32 + 1;
+        let (width, height) = (cells * HudRenderer::GLYPH_WIDTH, HudRenderer::GLYPH_HEIGHT);
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        let mut set_pixel = |x: u32, y: u32| {
+            let i = ((y * width + x) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+        };
+        for (cell, (_, rows)) in GLYPHS.iter().enumerate() {
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..HudRenderer::GLYPH_WIDTH {
+                    if row & (1 << (HudRenderer::GLYPH_WIDTH - 1 - x)) != 0 {
+                        set_pixel(cell as u32 * HudRenderer::GLYPH_WIDTH + x, y as u32);
+                    }
+                }
+            }
+        }
+        for x in 0..HudRenderer::GLYPH_WIDTH {
+            for y in 0..height {
+                set_pixel(GLYPHS.len() as u32 * HudRenderer::GLYPH_WIDTH + x, y);
+            }
+        }
+
+        // Rows aren't reversed, so the top of each glyph is at V 0
+        let image = glium::texture::RawImage2d::from_raw_rgba(pixels, (width, height));
+        HudRenderer {
+            font: glium::texture::Texture2d::new(facade, image).unwrap(),
+            frame_times: VecDeque::with_capacity(HudRenderer::GRAPH_FRAMES)
+        }
+    }
+
+    /// Adds a frame to the graph.
+    pub fn record_frame(&mut self, dt: f32) {
+        if self.frame_times.len() == HudRenderer::GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt);
+    }
+
+    /// Returns the average frame time in seconds over the frames in the graph.
+    pub fn get_average_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() { return 0.; }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    /// Draws the lines of text in a panel in the top left, with the frame time graph under them.
+    pub fn draw<F, S>(&self, facade: &F, target: &mut S, program: &glium::Program, lines: &[String]) where F: Facade, S: Surface {
+        let (width, height) = target.get_dimensions();
+        let mut quads = HudQuads { vertices: Vec::new(), screen_size: [width as f32, height as f32] };
+
+        // Size the panel to fit the text and graph
+        let line_width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as f32 * HudRenderer::CHAR_WIDTH * HudRenderer::SCALE;
+        let graph_width = HudRenderer::GRAPH_FRAMES as f32 * HudRenderer::GRAPH_BAR_WIDTH;
+        let text_height = lines.len() as f32 * HudRenderer::LINE_HEIGHT * HudRenderer::SCALE;
+        let left = HudRenderer::MARGIN + HudRenderer::PADDING;
+        let top = HudRenderer::MARGIN + HudRenderer::PADDING;
+        quads.push_solid(
+            [HudRenderer::MARGIN, HudRenderer::MARGIN],
+            [left + line_width.max(graph_width) + HudRenderer::PADDING, top + text_height + HudRenderer::GRAPH_HEIGHT + HudRenderer::PADDING * 2.],
+            [0., 0., 0., 0.5]
+        );
+
+        // Draw the text
+        for (row, line) in lines.iter().enumerate() {
+            quads.push_text([left, top + row as f32 * HudRenderer::LINE_HEIGHT * HudRenderer::SCALE], line, [1., 1., 1., 1.]);
+        }
+
+        // Draw the frame times as bars growing up from the bottom of the graph, colored by how
+        // far below 60 FPS they fall
+        let graph_bottom = top + text_height + HudRenderer::PADDING + HudRenderer::GRAPH_HEIGHT;
+        for (i, &frame_time) in self.frame_times.iter().enumerate() {
+            let bar_height = (frame_time / HudRenderer::GRAPH_MAX).min(1.) * HudRenderer::GRAPH_HEIGHT;
+            let color = if frame_time <= 1. / 55. {
+                [0.3, 0.9, 0.3, 0.9]
+            } else if frame_time <= 1. / 28. {
+                [0.9, 0.8, 0.2, 0.9]
+            } else {
+                [0.9, 0.2, 0.2, 0.9]
+            };
+            let x = left + i as f32 * HudRenderer::GRAPH_BAR_WIDTH;
+            quads.push_solid([x, graph_bottom - bar_height], [x + HudRenderer::GRAPH_BAR_WIDTH, graph_bottom], color);
+        }
+
+        // Mark 60 FPS
+        let target_y = graph_bottom - (1. / 60.) / HudRenderer::GRAPH_MAX * HudRenderer::GRAPH_HEIGHT;
+        quads.push_solid([left, target_y], [left + graph_width, target_y + 1.], [1., 1., 1., 0.6]);
+
+        self.draw_quads(facade, target, program, &quads);
+    }
+
+    /// Draws the console across the top of the screen: the last lines it printed, and the line
+    /// being typed under them.
+    pub fn draw_console<F, S>(&self, facade: &F, target: &mut S, program: &glium::Program, output: &[String], input: &str) where F: Facade, S: Surface {
+        let (width, height) = target.get_dimensions();
+        let mut quads = HudQuads { vertices: Vec::new(), screen_size: [width as f32, height as f32] };
+
+        let line_height = HudRenderer::LINE_HEIGHT * HudRenderer::SCALE;
+        let lines = output.len().min(HudRenderer::CONSOLE_LINES);
+        let input_top = HudRenderer::PADDING + lines as f32 * line_height;
+        quads.push_solid([0., 0.], [width as f32, input_top + line_height + HudRenderer::PADDING], [0., 0., 0., 0.75]);
+
+        // Draw the most recent output, oldest at the top
+        for (row, line) in output[output.len() - lines..].iter().enumerate() {
+            quads.push_text([HudRenderer::PADDING, HudRenderer::PADDING + row as f32 * line_height], line, [0.8, 0.8, 0.8, 1.]);
+        }
+
+        // Draw the line being typed with a cursor at the end
+        quads.push_text([HudRenderer::PADDING, input_top], &format!("> {}_", input), [1., 1., 0.6, 1.]);
+
+        self.draw_quads(facade, target, program, &quads);
+    }
+
+    /// Draws the quads over everything.
+    fn draw_quads<F, S>(&self, facade: &F, target: &mut S, program: &glium::Program, quads: &HudQuads) where F: Facade, S: Surface {
+        let vertex_buffer = glium::VertexBuffer::new(facade, &quads.vertices).unwrap();
+        let uniforms = uniform! {
+            font: self.font.sampled()
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+        };
+        let params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+        target.draw(&vertex_buffer, glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList), program, &uniforms, &params).unwrap();
+    }
+
+    /// Returns the cell of a character in the font, drawing unknown characters as `?`.
+    fn glyph_index(character: char) -> usize {
+        let character = character.to_ascii_uppercase();
+        GLYPHS.iter().position(|&(glyph, _)| glyph == character)
+            .unwrap_or_else(|| GLYPHS.iter().position(|&(glyph, _)| glyph == '?').unwrap())
+    }
+}
+
+/// The quads of the HUD, built in pixels from the top left of the screen.
+struct HudQuads {
+    vertices: Vec<cg::HudVertex>,
+    screen_size: [f32; 2]
+}
+
+impl HudQuads {
+    /// Adds a quad from `min` to `max` in pixels, showing the font between `min_uv` and `max_uv`.
+    fn push_quad(&mut self, min: [f32; 2], max: [f32; 2], min_uv: [f32; 2], max_uv: [f32; 2], color: [f32; 4]) {
+        let screen_size = self.screen_size;
+        let vertex = |x: f32, y: f32, u: f32, v: f32| cg::HudVertex {
+            // Pixels count down from the top but device coordinates count up from the bottom
+            position: [x / screen_size[0] * 2. - 1., 1. - y / screen_size[1] * 2.],
+            uv: [u, v],
+            color
+        };
+
+        let top_left = vertex(min[0], min[1], min_uv[0], min_uv[1]);
+        let top_right = vertex(max[0], min[1], max_uv[0], min_uv[1]);
+        let bottom_left = vertex(min[0], max[1], min_uv[0], max_uv[1]);
+        let bottom_right = vertex(max[0], max[1], max_uv[0], max_uv[1]);
+        self.vertices.extend_from_slice(&[top_left, bottom_left, bottom_right, top_left, bottom_right, top_right]);
+    }
+
+    /// Adds a line of text with its top left at `position`.
+    fn push_text(&mut self, position: [f32; 2], text: &str, color: [f32; 4]) {
+        for (column, character) in text.chars().enumerate() {
+            let x = position[0] + column as f32 * HudRenderer::CHAR_WIDTH * HudRenderer::SCALE;
+            self.push_glyph([x, position[1]], HudRenderer::glyph_index(character), color);
+        }
+    }
+
+    fn push_glyph(&mut self, position: [f32; 2], index: usize, color: [f32; 4]) {
+        let cell_width = 1. / (GLYPHS.len() + 1) as f32;
+        let size = [HudRenderer::GLYPH_WIDTH as f32 * HudRenderer::SCALE, HudRenderer::GLYPH_HEIGHT as f32 * HudRenderer::SCALE];
+        self.push_quad(
+            position,
+            [position[0] + size[0], position[1] + size[1]],
+            [index as f32 * cell_width, 0.],
+            [(index + 1) as f32 * cell_width, 1.],
+            color
+        );
+    }
+
+    /// Adds a box of a single color using the middle of the solid cell in the font.
+    fn push_solid(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+        let uv = [(GLYPHS.len() as f32 + 0.5) / (GLYPHS.len() + 1) as f32, 0.5];
+        self.push_quad(min, max, uv, uv, color);
+    }
+}
+
+impl VoxelTest {
+    /// Returns the lines of debug information shown in the HUD.
+    pub fn get_hud_lines(&self) -> Vec<String> {
+        let frame_time = self.hud.get_average_frame_time();
+        let fps = if frame_time > 0. { 1. / frame_time } else { 0. };
+
+        let position = self.camera.get_world_position();
+        let (pitch, yaw) = self.camera.get_pitch_yaw();
+        let chunk = WorldRenderer::get_current_chunk(self.camera.get_position());
+        let biome = if position[0] >= 0. && position[1] >= 0. {
+            self.world.biome_at(&world::WorldBlockIndex::new(position[0] as u32, position[1] as u32, 0))
+        } else {
+            None
+        };
+        let target = match self.target_block {
+            Some(ref hit) => format!("{} {} - {:.1} away", hit.index, self.world.get_block(&hit.index).id(), hit.distance),
+            None => "none".to_string()
+        };
+
+        vec![
+            format!("{:.0} fps - {:.1} ms", fps, frame_time * 1000.),
+            format!("Position {:.1}, {:.1}, {:.1}", position[0], position[1], position[2]),
+            format!("Facing yaw {:.0} pitch {:.0}", yaw.to_degrees(), pitch.to_degrees()),
+            format!("Chunk {}", chunk),
+            format!("Biome {}", biome.map(|biome| biome.name()).unwrap_or("none")),
+            format!("Block {}", target),
+            format!("Chunks {} loaded, {} visible", self.world.get_chunk_count(), self.world_renderer.get_visible_chunks().len()),
+            format!("Vertices {}", self.world_renderer.get_vertex_count()),
+            format!("Pending {} deferred blocks", self.world.get_pending_block_count()),
+            format!("Time {}", self.day_cycle.get_clock()),
+        ]
+    }
+}
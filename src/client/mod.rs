@@ -1,11 +1,28 @@
+mod benchmark;
+mod block_outline;
+mod builtin_worlds;
+mod capture;
 mod cg;
+mod console;
+mod day_cycle;
+mod flythrough;
+mod golden;
+mod hud;
+mod options;
 mod procedural_world;
 mod program_register;
 mod render;
+mod shadow;
+mod sky;
 mod world_renderer;
 
+pub use benchmark::{BenchmarkKind, run_mesh_benchmark};
+pub use flythrough::dump_frames;
+pub use golden::run_golden_tests;
+pub use options::*;
 use crate::utils;
 use glium::{glutin, Surface};
+use glium::backend::Facade;
 use crate::world;
 use std::io::Cursor;
 use std::time::Instant;
@@ -18,34 +35,88 @@ pub struct VoxelTest {
 
     world: world::World,
     world_renderer: world_renderer::WorldRenderer,
-    tile_texture: glium::texture::Texture2d
+    tile_texture: glium::texture::Texture2d,
+
+    /// The block the camera is looking at.
+    target_block: Option<world::RaycastHit>,
+    block_outline: block_outline::BlockOutline,
+
+    /// The block placed with right click.
+    selected_block: world::Block,
+
+    player: world::Player,
+    movement_mode: world::MovementMode,
+
+    day_cycle: day_cycle::DayCycle,
+    sky: sky::SkyRenderer,
+    shadows: shadow::ShadowRenderer,
+
+    /// The debug overlay, which still records frame times while it's hidden.
+    hud: hud::HudRenderer,
+    show_hud: bool,
+
+    /// The console, the blocks changed so far as console commands, and the options the world was
+    /// created with, for generating it again.
+    console: console::Console,
+    edits: Vec<String>,
+    options: Options,
+
+    input_map: utils::InputMap,
+    gamepad: Option<utils::Gamepad>,
+    stick_settings: utils::StickSettings,
+
+    /// The screenshot to take at the end of the frame.
+    pending_capture: Option<capture::Capture>,
+
+    /// How many times larger than the window tiled screenshots are.
+    capture_scale: u32,
+
+    /// The camera path being recorded or played, and the last one recorded or loaded.
+    path_recording: Option<flythrough::PathProgress>,
+    path_playback: Option<flythrough::PathProgress>,
+    camera_path: Option<utils::CameraPath>,
+
+    /// The render benchmark being run, which flies the camera instead of the player.
+    benchmark: Option<benchmark::RenderBenchmark>
 }
 
 impl VoxelTest {
-    pub fn new(app: &mut utils::App) -> VoxelTest {
+    pub fn new<F>(facade: &F, world_delegate: Box<dyn world::WorldDelegate>, options: &Options) -> VoxelTest where F: Facade {
         // Create world
-        let delegate = procedural_world::ProceduralWorld::new(1234);
-        let mut world = world::World::new(Box::new(delegate));
+        let mut world = world::World::new(world_delegate);
 
         // Get the tile texture
         let image_start = Instant::now();
         let image = image::load(Cursor::new(&include_bytes!("../../assets/img/spritesheet_tiles.png")[..]), image::PNG).unwrap().to_rgba();
         let image_dimensions = image.dimensions();
         let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-        let texture = glium::texture::Texture2d::new(&app.display, image).unwrap();
-        println!("Spritesheet loaded - {:.3}", image_start.elapsed().as_float_seconds());
+        let texture = glium::texture::Texture2d::new(facade, image).unwrap();
+        info!(target: "render", "Spritesheet loaded - {:.3}", image_start.elapsed().as_float_seconds());
 
-        // Add sphere
+        // Add sphere; the other world types are test scenes, so they're left as they are
         let radius = 7;
-        world.fill_ellipsoid(
-            world::Block::from_id("brick_stone"),
-            &world::WorldBlockIndex::new(16 - radius, 16 - radius, 32 - radius),
-            &world::WorldBlockIndex::new(16 + radius, 16 + radius, 32 + radius)
-        );
+        let surface = world.surface_height(16, 16).unwrap_or(radius);
+        if options.world_type == WorldType::Procedural {
+            world.fill_ellipsoid(
+                world::Block::from_id("brick_stone"),
+                &world::WorldBlockIndex::new(16 - radius, 16 - radius, surface.saturating_sub(radius)),
+                &world::WorldBlockIndex::new(16 + radius, 16 + radius, surface + radius)
+            );
+        }
+
+        // Plan the benchmark's flight over the world before it's moved in to the app
+        let benchmark = match options.benchmark {
+            Some(BenchmarkKind::Render) => Some(benchmark::RenderBenchmark::new(options, &world)),
+            _ => None
+        };
+
+        // Create the renderers
+        let world_renderer = world_renderer::WorldRenderer::new(VoxelTest::VIEW_DISTANCE, options.mesh_options.clone());
+        let shadows = shadow::ShadowRenderer::new(facade, &world_renderer);
 
         // Create app
         VoxelTest {
-            program_register: program_register::ProgramRegister::new(&app.display),
+            program_register: program_register::ProgramRegister::new(facade),
             draw_params: glium::DrawParameters {
                 depth: glium::Depth {
                     test: glium::DepthTest::IfLess,
@@ -55,85 +126,341 @@ impl VoxelTest {
                 backface_culling: glium::draw_parameters::BackfaceCullingMode::CullClockwise,
                 .. Default::default()
             },
-            camera: utils::CameraState::new([32., 64., 32.], [0., 0., 1.]),
+            camera: utils::CameraState::new([32., surface as f32 + 32., 32.], [0., 0., 1.], options.camera_settings),
 
             world,
-            world_renderer: world_renderer::WorldRenderer::new(2),
-            tile_texture: texture
+            world_renderer,
+            tile_texture: texture,
+
+            target_block: None,
+            block_outline: block_outline::BlockOutline::new(facade),
+            selected_block: world::Block::from_id("brick_stone"),
+
+            player: world::Player::new([32., 32., surface as f32 + 32.]),
+            movement_mode: world::MovementMode::Fly,
+
+            day_cycle: day_cycle::DayCycle::new(options.time, options.freeze_time || options.benchmark.is_some()),
+            sky: sky::SkyRenderer::new(facade),
+            shadows,
+
+            hud: hud::HudRenderer::new(facade),
+            show_hud: false,
+
+            console: console::Console::new(),
+            edits: Vec::new(),
+            options: options.clone(),
+
+            input_map: options.input_map.clone(),
+            gamepad: options.gamepad.as_ref().map(|device| utils::Gamepad::new(device)),
+            stick_settings: options.stick_settings,
+            pending_capture: None,
+            capture_scale: options.capture_scale,
+
+            path_recording: None,
+            path_playback: options.play_path.clone().map(flythrough::PathProgress::new),
+            camera_path: options.play_path.clone(),
+
+            benchmark
         }
     }
 }
 
 impl utils::AppState for VoxelTest {
-    fn update(&mut self, _app: &mut utils::App, _dt: f32) {
+    fn update(&mut self, _app: &mut utils::App, dt: f32) {
+        // Move the sun
+        self.day_cycle.update(dt);
+
+        // Read the gamepad
+        self.update_gamepad(dt);
 
+        // Record the camera
+        self.update_path_recording(dt);
+
+        // Move the player
+        if self.movement_mode == world::MovementMode::Walk {
+            // Hold the player in place until the chunk they're in has loaded so they don't fall
+            // through the unloaded world
+            let position = self.player.position;
+            let block_index = world::WorldBlockIndex::new(position[0].max(0.) as u32, position[1].max(0.) as u32, position[2].max(0.) as u32);
+            if self.world.get_chunk(&block_index.get_chunk_index()).is_none() { return; }
+
+            let input = self.camera.get_player_input();
+            let world = &self.world;
+            self.player.update(&input, dt, |x, y, z| world.is_solid(x, y, z));
+
+            // Move the camera to the player's eyes
+            let eye = self.player.get_eye_position();
+            self.camera.set_position([eye[0], eye[2], eye[1]]);  // Flip Y with Z
+        }
     }
 
     fn render(&mut self, app: &mut utils::App, dt: f32) {
+        let frame_span = utils::Span::new("render", "frame");
+        let frame_start = Instant::now();
+
         // Update the camera
         self.camera.update(app, dt);
+        self.update_path_playback(dt);
+        self.step_benchmark();
 
-        // Prepare the target
+        // Find the block being looked at
+        self.target_block = self.world.raycast(self.camera.get_world_position(), self.camera.get_world_direction(), VoxelTest::REACH);
+
+        // Draw the frame
         let mut target: glium::Frame = app.display.draw();
-        target.clear_color_and_depth((0.623, 0.929, 0.988, 1.), 1.);
+        self.draw(&app.display, &mut target);
+
+        // Draw the HUD over the world
+        self.hud.record_frame(dt);
+        if self.show_hud {
+            let lines = self.get_hud_lines();
+            self.hud.draw(&app.display, &mut target, &self.program_register.hud_program, &lines);
+        }
+        if self.console.is_open() {
+            self.hud.draw_console(&app.display, &mut target, &self.program_register.hud_program, self.console.get_output(), self.console.get_input());
+        }
+        target.finish().unwrap();
+        drop(frame_span);
+
+        // Close once the benchmark is done
+        if !self.finish_benchmark_frame(frame_start.elapsed().as_float_seconds()) {
+            app.window_state.is_closing = true;
+        }
+
+        // Take a screenshot now that the frame is done
+        if let Some(capture) = self.pending_capture.take() {
+            self.capture(app, capture);
+        }
+    }
+
+    fn process_event(&mut self, app: &mut utils::App, event: glutin::Event) {
+        // Clicking while the cursor is unlocked only locks it, so check before the camera does
+        let cursor_locked = self.camera.is_cursor_locked();
+
+        // Update camera
+        self.camera.process_input(&event);
+
+        // Keys go to the console instead of actions while it's open
+        if self.console.is_open() {
+            self.process_console_event(&app.display, &event);
+            return;
+        }
+
+        // Run the actions bound to the event
+        for (action, pressed) in self.input_map.map_event(&event) {
+            self.camera.process_action(action, pressed);
+            if pressed {
+                self.process_action(action, cursor_locked);
+            }
+        }
+    }
+}
+
+impl VoxelTest {
+    /// How far away chunks are loaded, in chunks.
+    pub const VIEW_DISTANCE: u32 = 2;
+
+    /// How far away blocks can be broken and placed.
+    const REACH: f32 = 8.;
+
+    /// Runs the actions bound to the gamepad buttons and moves the camera with the sticks.
+    fn update_gamepad(&mut self, dt: f32) {
+        let gamepad = match self.gamepad {
+            Some(ref mut gamepad) => gamepad,
+            None => return
+        };
+        let changes = gamepad.poll();
+        let state = *gamepad.get_state();
+
+        // Run the actions bound to the buttons
+        let cursor_locked = self.camera.is_cursor_locked();
+        for (button, pressed) in changes {
+            for (action, pressed) in self.input_map.map_gamepad(button, pressed) {
+                self.camera.process_action(action, pressed);
+                if pressed {
+                    self.process_action(action, cursor_locked);
+                }
+            }
+        }
+
+        // Move and turn with the sticks
+        let motion = utils::gamepad_motion(&state, &self.stick_settings, dt);
+        self.camera.set_analog_movement(motion.movement);
+        if cursor_locked {
+            self.camera.rotate(motion.look[0], motion.look[1]);
+        }
+    }
+
+    /// How far through the view radius the fog starts.
+    const FOG_START: f32 = 0.6;
+
+    /// Updates the chunks around the camera and draws the world on to the target.
+    pub fn draw<F, S>(&mut self, facade: &F, target: &mut S) where F: Facade, S: Surface {
+        // Update the chunks and draw their shadows
+        let span = utils::Span::new("render", "update_chunks");
+        self.world_renderer.update(facade, &mut self.world, &self.camera);
+        drop(span);
+        let span = utils::Span::new("render", "shadows");
+        self.shadows.render(facade, &self.program_register.shadow_program, &self.world_renderer, &self.camera, self.day_cycle.get_light_direction());
+        drop(span);
+        let _span = utils::Span::new("render", "draw");
+
+        // Prepare the target
+        let fog_color = self.day_cycle.get_fog_color();
+        target.clear_color_and_depth((fog_color[0], fog_color[1], fog_color[2], 1.), 1.);
+
+        // Draw the sky behind everything else
+        self.sky.draw(target, &self.program_register.sky_program, &self.day_cycle, self.camera.get_view(), self.camera.get_perspective());
 
         // Render the chunks
-        self.world_renderer.update(app, &mut self.world, &mut self.camera);
+        let fog_end = self.world_renderer.get_view_radius();
+        let cascades = self.shadows.get_cascades();
         for (_, mesh) in self.world_renderer.get_visible_chunks().iter() {
             // Create uniforms
             let uniforms = uniform! {
                 model_matrix: mesh.transform,
                 view_matrix: self.camera.get_view(),
                 projection_matrix: self.camera.get_perspective(),
-                tex: &self.tile_texture
+                tex: &self.tile_texture,
+                light_direction: self.day_cycle.get_light_direction(),
+                sky_brightness: self.day_cycle.get_sky_brightness(),
+                fog_color: fog_color,
+                fog_start: fog_end * VoxelTest::FOG_START,
+                fog_end: fog_end,
+                shadow_map_0: cascades[0].get_map(),
+                shadow_map_1: cascades[1].get_map(),
+                shadow_map_2: cascades[2].get_map(),
+                shadow_matrix_0: cascades[0].get_matrix(),
+                shadow_matrix_1: cascades[1].get_matrix(),
+                shadow_matrix_2: cascades[2].get_matrix(),
+                shadow_splits: [cascades[0].get_far(), cascades[1].get_far(), cascades[2].get_far()],
+                shadow_normal_offsets: [cascades[0].get_normal_offset(), cascades[1].get_normal_offset(), cascades[2].get_normal_offset()],
+                shadow_biases: [cascades[0].get_bias(), cascades[1].get_bias(), cascades[2].get_bias()]
             };
 
             // Draw the mesh
             target.draw(
                 &mesh.vertex_buffer,
-                &glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
                 &self.program_register.default_program,
                 &uniforms,
                 &self.draw_params
             ).unwrap();
         }
 
-        // Finish rendering
-        target.finish().unwrap();
+        // Outline the targeted block
+        if let Some(ref hit) = self.target_block {
+            self.block_outline.draw(target, &self.program_register.outline_program, &hit.index, self.camera.get_view(), self.camera.get_perspective());
+        }
     }
 
-    fn process_event(&mut self, _app: &mut utils::App, event: glutin::Event) {
-        // Update camera
-        self.camera.process_input(&event);
+    /// Draws a frame in to an image instead of the window.
+    pub fn render_image<F>(&mut self, facade: &F, width: u32, height: u32) -> image::RgbaImage where F: Facade {
+        self.camera.set_view_size(width, height);
 
-        // Toggle debug data
-        match event {
-            glutin::Event::WindowEvent { ref event, .. } => match *event {
-                glutin::WindowEvent::KeyboardInput { ref input, .. } => {
-                    // Get key state
-                    if input.state != glutin::ElementState::Pressed { return }
-                    let key = match input.virtual_keycode {
-                        Some(key) => key,
-                        None => return,
-                    };
-
-                    // Toggle debug mode
-                    match key {
-                        glutin::VirtualKeyCode::Grave => self.toggle_debug_mode(),
-
-                        _ => { },
-                    };
-                },
+        // Draw in to a texture
+        let color = glium::texture::Texture2d::empty_with_format(
+            facade,
+            glium::texture::UncompressedFloatFormat::U8U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            width,
+            height
+        ).unwrap();
+        let depth = glium::framebuffer::DepthRenderBuffer::new(facade, glium::texture::DepthFormat::I24, width, height).unwrap();
+        let mut target = glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(facade, &color, &depth).unwrap();
+        self.draw(facade, &mut target);
 
-                _ => { }
+        // Read the pixels back; OpenGL stores rows from the bottom up
+        let pixels: glium::texture::RawImage2d<u8> = color.read();
+        let image = image::RgbaImage::from_raw(width, height, pixels.data.into_owned()).unwrap();
+        image::imageops::flip_vertical(&image)
+    }
+
+    fn capture(&mut self, app: &mut utils::App, capture: capture::Capture) {
+        let image = match capture {
+            capture::Capture::Screenshot => {
+                let pixels: glium::texture::RawImage2d<u8> = app.display.read_front_buffer();
+                let image = image::RgbaImage::from_raw(pixels.width, pixels.height, pixels.data.into_owned()).unwrap();
+                image::imageops::flip_vertical(&image)
             },
+            capture::Capture::Tiled => {
+                let (width, height) = app.display.get_framebuffer_dimensions();
+                self.render_tiled_image(&app.display, width, height, self.capture_scale)
+            }
+        };
+
+        match capture::save_screenshot(&image) {
+            Ok(path) => info!("Saved screenshot {} - {}x{}", path.display(), image.width(), image.height()),
+            Err(err) => error!("{}", err)
+        }
+    }
+
+    /// Runs a pressed action. Actions that change the world are ignored while the cursor is
+    /// unlocked, since clicking then only locks it.
+    fn process_action(&mut self, action: utils::Action, cursor_locked: bool) {
+        match action {
+            utils::Action::ToggleDebug => self.toggle_debug_mode(),
+            utils::Action::ToggleMovementMode => self.toggle_movement_mode(),
+            utils::Action::Screenshot => self.pending_capture = Some(capture::Capture::Screenshot),
+            utils::Action::TiledScreenshot => self.pending_capture = Some(capture::Capture::Tiled),
+            utils::Action::TogglePathRecording => self.toggle_path_recording(),
+            utils::Action::TogglePathPlayback => self.toggle_path_playback(),
+            utils::Action::ToggleHud => self.show_hud = !self.show_hud,
+            utils::Action::ToggleConsole => self.set_console_open(true),
+
+            // Change the time of day
+            utils::Action::ToggleTime => self.day_cycle.set_frozen(!self.day_cycle.is_frozen()),
+            utils::Action::TimeBackward => self.day_cycle.set_hour(self.day_cycle.get_hour() - 1.),
+            utils::Action::TimeForward => self.day_cycle.set_hour(self.day_cycle.get_hour() + 1.),
+
+            utils::Action::BreakBlock if cursor_locked => self.break_block(),
+            utils::Action::PlaceBlock if cursor_locked => self.place_block(),
+            utils::Action::NextBlock if cursor_locked => self.cycle_selected_block(1),
+            utils::Action::PreviousBlock if cursor_locked => self.cycle_selected_block(-1),
 
             _ => { }
         }
     }
-}
 
-impl VoxelTest {
+    fn break_block(&mut self) {
+        if let Some(hit) = self.target_block.take() {
+            self.world.set_block(&hit.index, world::Block::AIR);
+            self.edits.push(console::set_block_command(&hit.index, world::Block::AIR));
+        }
+    }
+
+    fn place_block(&mut self) {
+        if let Some(index) = self.target_block.take().and_then(|hit| hit.adjacent_index()) {
+            // Don't trap the player inside of the block
+            let in_player = self.player.get_aabb().intersects_block(index.x as i64, index.y as i64, index.z as i64);
+            if self.movement_mode == world::MovementMode::Walk && in_player && !self.selected_block.is_transparent() { return; }
+
+            self.world.set_block(&index, self.selected_block);
+            self.edits.push(console::set_block_command(&index, self.selected_block));
+        }
+    }
+
+    fn toggle_movement_mode(&mut self) {
+        self.movement_mode = match self.movement_mode {
+            world::MovementMode::Fly => {
+                // Drop the player in where the camera is
+                let eye = self.camera.get_world_position();
+                self.player = world::Player::new([eye[0], eye[1], eye[2] - world::Player::EYE_HEIGHT]);
+                world::MovementMode::Walk
+            },
+            world::MovementMode::Walk => world::MovementMode::Fly
+        };
+        self.camera.set_fly(self.movement_mode == world::MovementMode::Fly);
+    }
+
+    /// Selects the next or previous visible block.
+    fn cycle_selected_block(&mut self, direction: i32) {
+        let blocks: Vec<world::Block> = world::Block::all().filter(|block| !block.is_invisible()).collect();
+        let current = blocks.iter().position(|&block| block == self.selected_block).unwrap_or(0) as i32;
+        let next = (current + direction).rem_euclid(blocks.len() as i32);
+        self.selected_block = blocks[next as usize];
+    }
+
     fn toggle_debug_mode(&mut self) {
         // Get the next polygon mode
         let (next_mode, next_cull) = match self.draw_params.polygon_mode {
@@ -0,0 +1,344 @@
+use super::benchmark::BenchmarkKind;
+use super::builtin_worlds;
+use super::procedural_world;
+use super::render::MeshOptions;
+use crate::utils::CameraPath;
+use crate::utils::CameraSettings;
+use crate::utils::InputMap;
+use crate::utils::LogFilter;
+use crate::utils::StickSettings;
+use crate::world::WorldDelegate;
+
+/// The world generators that can be picked from the command line.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WorldType {
+    Procedural,
+    Flat,
+    Void,
+    Checkerboard,
+    Debug
+}
+
+impl WorldType {
+    fn from_name(name: &str) -> Option<WorldType> {
+        match name {
+            "procedural" => Some(WorldType::Procedural),
+            "flat" => Some(WorldType::Flat),
+            "void" => Some(WorldType::Void),
+            "checkerboard" => Some(WorldType::Checkerboard),
+            "debug" => Some(WorldType::Debug),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorldType::Procedural => "procedural",
+            WorldType::Flat => "flat",
+            WorldType::Void => "void",
+            WorldType::Checkerboard => "checkerboard",
+            WorldType::Debug => "debug"
+        }
+    }
+}
+
+/// Options passed on the command line.
+#[derive(Clone)]
+pub struct Options {
+    pub world_type: WorldType,
+    pub seed: u32,
+
+    /// The layers for the flat world; see `FlatWorld::from_config`.
+    pub flat_layers: String,
+
+    /// The stages for the procedural world; see `ProceduralWorld::from_config`.
+    pub stages: String,
+
+    pub mesh_options: MeshOptions,
+    pub camera_settings: CameraSettings,
+
+    /// The hour of the day to start at, from 0 to 24.
+    pub time: f32,
+
+    /// If time stands still.
+    pub freeze_time: bool,
+
+    /// The folder of golden images to render off screen and compare against instead of opening a
+    /// window; see `run_golden_tests`.
+    pub golden_dir: Option<String>,
+    pub update_golden: bool,
+    pub golden_tolerance: f32,
+
+    /// How many times larger than the window tiled screenshots are.
+    pub capture_scale: u32,
+
+    /// The camera path to play when the game starts, or to render with `--dump-frames`.
+    pub play_path: Option<CameraPath>,
+
+    /// The folder to render the frames of `play_path` to instead of opening a window; see
+    /// `dump_frames`.
+    pub dump_frames: Option<String>,
+    pub frame_rate: u32,
+    pub frame_size: [u32; 2],
+
+    /// The benchmark to run along `play_path`, or a default flight, and the file to save its
+    /// report to.
+    pub benchmark: Option<BenchmarkKind>,
+    pub benchmark_report: String,
+
+    /// Which messages to log, overriding `RUST_LOG`, and where to save a Chrome trace of the
+    /// spans.
+    pub log_filter: Option<LogFilter>,
+    pub trace_path: Option<String>,
+
+    /// The default bindings with the ones from `--bindings` on top.
+    pub input_map: InputMap,
+    pub print_bindings: bool,
+
+    /// The joystick device to read the gamepad from, if any.
+    pub gamepad: Option<String>,
+    pub stick_settings: StickSettings
+}
+
+impl Options {
+    pub const USAGE: &'static str = "\
+Usage: voxel-test [options]
+
+Options:
+    --world <type>      procedural, flat, void, checkerboard or debug (default: procedural)
+    --seed <seed>       The seed for the procedural world (default: 1234)
+    --layers <layers>   Layers for the flat world, e.g. stone*3,dirt*2,dirt_grass
+    --stages <stages>   Stages for the procedural world, e.g. shape,surface,caves
+    --ao-curve <curve>  Brightness of corners from boxed in to open, e.g. 0.45,0.65,0.82,1
+    --bevel <size>      Bevel the edges of blocks by a size from 0 to 0.5 (default: 0)
+    --fov <degrees>     The vertical field of view (default: 45)
+    --zoom-fov <degrees>
+                        The field of view while holding C to zoom (default: 15)
+    --near <distance>   The distance to the near clipping plane (default: 0.1)
+    --far <distance>    The distance to the far clipping plane (default: 1024)
+    --sensitivity <radians>
+                        How far the camera turns per pixel the mouse moves (default: 0.0025)
+    --acceleration <rate>
+                        How quickly the camera gets up to speed when flying (default: 10)
+    --damping <rate>    How quickly the camera slows down when flying (default: 6)
+    --time <hour>       The hour of the day to start at from 0 to 24 (default: 9)
+    --freeze-time       Stop the time of day from changing
+    --capture-scale <n> How many times larger than the window Shift+F2 screenshots are (default: 4)
+    --play-path <file>  Fly the camera along a path recorded with F6 when the game starts
+    --dump-frames <dir> Render the frames of --play-path off screen to a folder and exit
+    --frame-rate <fps>  The frame rate for --dump-frames and steps per second for --benchmark (default: 30)
+    --frame-size <size> The size of the frames for --dump-frames, e.g. 1920x1080 (default: 1280x720)
+    --benchmark <type>  Time generating and meshing chunks along --play-path, or a default flight, and exit;
+                        `mesh` runs without a window and `render` also times uploads and frames
+    --benchmark-report <file>
+                        Where to save the benchmark report, as CSV if it ends in .csv (default: benchmark.json)
+    --log <filter>      Which messages to log, e.g. info,mesh=debug (default: $RUST_LOG or info); the
+                        targets are world, gen, mesh and render, and trace also logs how long each step took
+    --trace <file>      Save a Chrome trace of each step to open in chrome://tracing or Perfetto
+    --bindings <file>   Load key bindings from a file; see --print-bindings for the format
+    --print-bindings    Print the key bindings, including any loaded with --bindings, and exit
+    --gamepad <device>  The joystick device to read a gamepad from, e.g. /dev/input/js0 (default: none)
+    --stick-dead-zone <fraction>
+                        How far the sticks have to be pushed from 0 to 1 before they move (default: 0.15)
+    --stick-curve <exponent>
+                        Values above 1 give the sticks finer control near the center (default: 2)
+    --look-speed <speed>
+                        How fast the look stick turns in radians per second (default: 3)
+    --golden <dir>      Render test scenes off screen and compare them to the images in a folder
+    --update-golden     Save the rendered test scenes as the new golden images
+    --golden-tolerance <fraction>
+                        The fraction of pixels allowed to differ from the golden images (default: 0.001)
+    --help              Print this message";
+
+    pub fn from_args<I>(args: I) -> Result<Options, String> where I: IntoIterator<Item=String> {
+        let mut options = Options::default();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Flags with values take the next argument
+            let mut value = || args.next().ok_or_else(|| format!("Missing value for `{}`", arg));
+            match arg.as_str() {
+                "--world" => {
+                    let name = value()?;
+                    options.world_type = WorldType::from_name(&name).ok_or_else(|| format!("Unknown world type `{}`", name))?;
+                },
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = seed.parse().map_err(|_| format!("Invalid seed `{}`", seed))?;
+                },
+                "--layers" => options.flat_layers = value()?,
+                "--stages" => options.stages = value()?,
+                "--ao-curve" => options.mesh_options.ao_curve = Options::parse_ao_curve(&value()?)?,
+                "--bevel" => {
+                    let bevel = value()?;
+                    options.mesh_options.bevel = bevel.parse().ok().filter(|bevel| (0. ..=0.5).contains(bevel))
+                        .ok_or_else(|| format!("Invalid bevel `{}`; expected a size from 0 to 0.5", bevel))?;
+                },
+                "--fov" => options.camera_settings.fov = Options::parse_fov(&value()?)?,
+                "--zoom-fov" => options.camera_settings.zoom_fov = Options::parse_fov(&value()?)?,
+                "--near" => options.camera_settings.near = Options::parse_positive("near distance", &value()?)?,
+                "--far" => options.camera_settings.far = Options::parse_positive("far distance", &value()?)?,
+                "--sensitivity" => options.camera_settings.sensitivity = Options::parse_positive("sensitivity", &value()?)?,
+                "--acceleration" => options.camera_settings.acceleration = Options::parse_positive("acceleration", &value()?)?,
+                "--damping" => options.camera_settings.damping = Options::parse_positive("damping", &value()?)?,
+                "--time" => {
+                    let time = value()?;
+                    options.time = time.parse().ok().filter(|time| (0. ..=24.).contains(time))
+                        .ok_or_else(|| format!("Invalid time `{}`; expected an hour from 0 to 24", time))?;
+                },
+                "--freeze-time" => options.freeze_time = true,
+                "--capture-scale" => {
+                    let scale = value()?;
+                    options.capture_scale = scale.parse().ok().filter(|scale| (1..=16).contains(scale))
+                        .ok_or_else(|| format!("Invalid capture scale `{}`; expected a number from 1 to 16", scale))?;
+                },
+                "--play-path" => options.play_path = Some(CameraPath::load(&value()?)?),
+                "--dump-frames" => options.dump_frames = Some(value()?),
+                "--frame-rate" => {
+                    let frame_rate = value()?;
+                    options.frame_rate = frame_rate.parse().ok().filter(|frame_rate| (1..=240).contains(frame_rate))
+                        .ok_or_else(|| format!("Invalid frame rate `{}`; expected a number from 1 to 240", frame_rate))?;
+                },
+                "--frame-size" => options.frame_size = Options::parse_size(&value()?)?,
+                "--benchmark" => {
+                    let name = value()?;
+                    options.benchmark = Some(BenchmarkKind::from_name(&name).ok_or_else(|| format!("Unknown benchmark `{}`; expected mesh or render", name))?);
+                },
+                "--benchmark-report" => options.benchmark_report = value()?,
+                "--log" => options.log_filter = Some(LogFilter::from_config(&value()?)?),
+                "--trace" => options.trace_path = Some(value()?),
+                "--bindings" => options.input_map = InputMap::load(&value()?)?,
+                "--print-bindings" => options.print_bindings = true,
+                "--gamepad" => {
+                    let device = value()?;
+                    options.gamepad = if device == "none" { None } else { Some(device) };
+                },
+                "--stick-dead-zone" => {
+                    let dead_zone = value()?;
+                    options.stick_settings.dead_zone = dead_zone.parse().ok().filter(|dead_zone| (0. ..1.).contains(dead_zone))
+                        .ok_or_else(|| format!("Invalid stick dead zone `{}`; expected a fraction from 0 to 1", dead_zone))?;
+                },
+                "--stick-curve" => {
+                    let curve = value()?;
+                    options.stick_settings.curve = curve.parse().ok().filter(|&curve: &f32| curve > 0.)
+                        .ok_or_else(|| format!("Invalid stick curve `{}`; expected a positive exponent", curve))?;
+                },
+                "--look-speed" => {
+                    let speed = value()?;
+                    options.stick_settings.look_speed = speed.parse().ok().filter(|&speed: &f32| speed > 0.)
+                        .ok_or_else(|| format!("Invalid look speed `{}`; expected a positive speed", speed))?;
+                },
+                "--golden" => options.golden_dir = Some(value()?),
+                "--update-golden" => options.update_golden = true,
+                "--golden-tolerance" => {
+                    let tolerance = value()?;
+                    options.golden_tolerance = tolerance.parse().ok().filter(|tolerance| (0. ..=1.).contains(tolerance))
+                        .ok_or_else(|| format!("Invalid golden tolerance `{}`; expected a fraction from 0 to 1", tolerance))?;
+                },
+                "--help" | "-h" => return Err(Options::USAGE.to_string()),
+                _ => return Err(format!("Unknown argument `{}`\n\n{}", arg, Options::USAGE))
+            }
+        }
+
+        if options.camera_settings.near >= options.camera_settings.far {
+            return Err(format!(
+                "The near distance ({}) must be less than the far distance ({})",
+                options.camera_settings.near, options.camera_settings.far
+            ));
+        }
+
+        Ok(options)
+    }
+
+    /// Parses a size in the form `<width>x<height>`.
+    fn parse_size(value: &str) -> Result<[u32; 2], String> {
+        let invalid = || format!("Invalid size `{}`; expected a width and height such as 1280x720", value);
+        let sizes = value.split('x')
+            .map(|size| size.trim().parse::<u32>().ok().filter(|&size| size > 0))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or_else(invalid)?;
+        if sizes.len() != 2 { return Err(invalid()); }
+
+        Ok([sizes[0], sizes[1]])
+    }
+
+    /// Parses a field of view between 0 and 180 degrees.
+    fn parse_fov(value: &str) -> Result<f32, String> {
+        value.parse().ok().filter(|&fov: &f32| fov > 0. && fov < 180.)
+            .ok_or_else(|| format!("Invalid field of view `{}`; expected degrees between 0 and 180", value))
+    }
+
+    /// Parses a finite number above 0.
+    fn parse_positive(name: &str, value: &str) -> Result<f32, String> {
+        value.parse().ok().filter(|&number: &f32| number > 0. && number.is_finite())
+            .ok_or_else(|| format!("Invalid {} `{}`; expected a number above 0", name, value))
+    }
+
+    /// Parses four comma separated brightnesses between 0 and 1.
+    fn parse_ao_curve(value: &str) -> Result<[f32; 4], String> {
+        let invalid = || format!("Invalid AO curve `{}`; expected four brightnesses from 0 to 1", value);
+        let levels = value.split(',')
+            .map(|level| level.trim().parse::<f32>().ok().filter(|level| (0. ..=1.).contains(level)))
+            .collect::<Option<Vec<f32>>>()
+            .ok_or_else(invalid)?;
+        if levels.len() != 4 { return Err(invalid()); }
+
+        Ok([levels[0], levels[1], levels[2], levels[3]])
+    }
+
+    pub fn create_world_delegate(&self) -> Result<Box<dyn WorldDelegate>, String> {
+        Ok(match self.world_type {
+            WorldType::Procedural => Box::new(procedural_world::ProceduralWorld::from_config(self.seed, &self.stages)?),
+            WorldType::Flat => Box::new(builtin_worlds::FlatWorld::from_config(&self.flat_layers)?),
+            WorldType::Void => Box::new(builtin_worlds::VoidWorld),
+            WorldType::Checkerboard => Box::new(builtin_worlds::CheckerboardWorld::new()),
+            WorldType::Debug => Box::new(builtin_worlds::DebugShapesWorld::new()),
+        })
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            world_type: WorldType::Procedural,
+            seed: 1234,
+            flat_layers: builtin_worlds::FlatWorld::DEFAULT_LAYERS.to_string(),
+            stages: procedural_world::DEFAULT_STAGES.join(","),
+            mesh_options: MeshOptions::default(),
+            camera_settings: CameraSettings::default(),
+            time: 9.,
+            freeze_time: false,
+            golden_dir: None,
+            update_golden: false,
+            golden_tolerance: 0.001,
+            capture_scale: 4,
+            play_path: None,
+            dump_frames: None,
+            frame_rate: 30,
+            frame_size: [1280, 720],
+            benchmark: None,
+            benchmark_report: "benchmark.json".to_string(),
+            log_filter: None,
+            trace_path: None,
+            input_map: InputMap::default(),
+            print_bindings: false,
+            gamepad: None,
+            stick_settings: StickSettings::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positive_numbers_are_finite() {
+        assert_eq!(Options::parse_positive("near distance", "0.25"), Ok(0.25));
+        assert_eq!(Options::parse_positive("far distance", "1e3"), Ok(1000.));
+
+        for value in ["0", "-1", "inf", "+inf", "infinity", "NaN", "1e40", "", "far"].iter() {
+            assert!(Options::parse_positive("far distance", value).is_err(), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::world::Biome;
+use crate::world::Block;
+use super::noise_seed;
+use noise::{NoiseFn, Seedable, MultiFractal};
+
+type NoiseType = noise::Fbm;
+
+pub struct BiomeConfig {
+    /// Where the biome sits on the temperature and humidity maps. Climate noise roughly spans
+    /// -0.7 to 0.7, so the centers should stay inside that range.
+    pub temperature: f64,
+    pub humidity: f64,
+
+    /// The block placed on the very top of the terrain.
+    pub surface_block: Block,
+
+    /// The block placed between the surface and the stone.
+    pub subsurface_block: Block,
+
+    /// The terrain height offset from `TerrainShape::SURFACE_HEIGHT`.
+    pub base_height: f64,
+
+    /// How much the height noise is scaled by.
+    pub height_amplitude: f64,
+
+    /// The chance of each surface column growing a tree, tall grass or a mushroom.
+    pub tree_chance: f64,
+    pub grass_chance: f64,
+    pub mushroom_chance: f64,
+
+    /// The block tree trunks are made of.
+    pub trunk_block: Block,
+}
+
+impl BiomeConfig {
+    pub fn for_biome(biome: Biome) -> BiomeConfig {
+        let (temperature, humidity, surface, subsurface, base_height, height_amplitude) = match biome {
+            Biome::Plains => (0.0, 0.0, "dirt_grass", "dirt", 0., 6.),
+            Biome::Forest => (0.0, 0.4, "dirt_grass", "dirt", 2., 10.),
+            Biome::Desert => (0.4, -0.3, "dirt_sand", "sand", -1., 4.),
+            Biome::Snowy => (-0.4, 0.1, "dirt_snow", "dirt", 8., 12.),
+            Biome::Mountains => (-0.2, -0.4, "stone", "stone", 48., 20.),
+        };
+
+        let (tree_chance, grass_chance, mushroom_chance, trunk) = match biome {
+            Biome::Plains => (0.002, 0.08, 0.002, "wood"),
+            Biome::Forest => (0.02, 0.04, 0.01, "wood"),
+            Biome::Desert => (0., 0., 0., "wood"),
+            Biome::Snowy => (0.008, 0., 0., "wood_red"),
+            Biome::Mountains => (0.001, 0.01, 0., "wood_red"),
+        };
+
+        BiomeConfig {
+            temperature,
+            humidity,
+            surface_block: Block::from_id(surface),
+            subsurface_block: Block::from_id(subsurface),
+            base_height,
+            height_amplitude,
+            tree_chance,
+            grass_chance,
+            mushroom_chance,
+            trunk_block: Block::from_id(trunk)
+        }
+    }
+}
+
+/// The contribution of each biome to a single column; the weights always add up to 1.
+#[derive(Copy, Clone)]
+pub struct BiomeSample {
+    pub weights: [f64; Biome::COUNT]
+}
+
+impl BiomeSample {
+    /// The biome with the largest weight in the column.
+    pub fn dominant(&self) -> Biome {
+        let mut dominant = 0;
+        for i in 1..Biome::COUNT {
+            if self.weights[i] > self.weights[dominant] {
+                dominant = i;
+            }
+        }
+
+        Biome::ALL[dominant]
+    }
+
+    /// Blends a value from each biome's config by the biome weights.
+    pub fn blend<F>(&self, configs: &[BiomeConfig; Biome::COUNT], value: F) -> f64 where F: Fn(&BiomeConfig) -> f64 {
+        let mut result = 0.;
+        for (config, weight) in configs.iter().zip(self.weights.iter()) {
+            result += value(config) * weight;
+        }
+
+        result
+    }
+}
+
+pub struct BiomeMap {
+    temperature_noise: NoiseType,
+    humidity_noise: NoiseType,
+    configs: [BiomeConfig; Biome::COUNT]
+}
+
+impl BiomeMap {
+    /// How far apart two climates can be before the biomes stop blending in to each other. Larger
+    /// values give wider, softer borders.
+    const BLEND_WIDTH: f64 = 0.12;
+
+    pub fn new(seed: u32) -> BiomeMap {
+        BiomeMap {
+            temperature_noise: NoiseType::new().set_seed(noise_seed(seed, 2)).set_frequency(0.004).set_octaves(3),
+            humidity_noise: NoiseType::new().set_seed(noise_seed(seed, 3)).set_frequency(0.004).set_octaves(3),
+            configs: [
+                BiomeConfig::for_biome(Biome::Plains),
+                BiomeConfig::for_biome(Biome::Forest),
+                BiomeConfig::for_biome(Biome::Desert),
+                BiomeConfig::for_biome(Biome::Snowy),
+                BiomeConfig::for_biome(Biome::Mountains),
+            ]
+        }
+    }
+
+    pub fn configs(&self) -> &[BiomeConfig; Biome::COUNT] {
+        &self.configs
+    }
+
+    pub fn config(&self, biome: Biome) -> &BiomeConfig {
+        &self.configs[biome as usize]
+    }
+
+    /// Returns the temperature and humidity at a world position.
+    pub fn climate(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.temperature_noise.get([x, y]), self.humidity_noise.get([x, y]))
+    }
+
+    pub fn sample(&self, x: f64, y: f64) -> BiomeSample {
+        let (temperature, humidity) = self.climate(x, y);
+
+        // Weight each biome with a gaussian falloff on its distance from the climate so the
+        // borders fade smoothly instead of snapping
+        let mut weights = [0.; Biome::COUNT];
+        let mut total = 0.;
+        for (weight, config) in weights.iter_mut().zip(self.configs.iter()) {
+            let distance_sq = (temperature - config.temperature).powi(2) + (humidity - config.humidity).powi(2);
+            *weight = (-distance_sq / (2. * BiomeMap::BLEND_WIDTH.powi(2))).exp();
+            total += *weight;
+        }
+
+        // Normalize the weights; if the climate is far from every biome, all the weights underflow,
+        // so fall back to the closest biome
+        if total > 0. {
+            for weight in weights.iter_mut() {
+                *weight /= total;
+            }
+        } else {
+            let closest = self.closest_biome(temperature, humidity);
+            weights[closest as usize] = 1.;
+        }
+
+        BiomeSample { weights }
+    }
+
+    pub fn biome_at(&self, x: f64, y: f64) -> Biome {
+        self.sample(x, y).dominant()
+    }
+
+    fn closest_biome(&self, temperature: f64, humidity: f64) -> Biome {
+        let mut closest = Biome::Plains;
+        let mut closest_distance = f64::MAX;
+        for (&biome, config) in Biome::ALL.iter().zip(self.configs.iter()) {
+            let distance_sq = (temperature - config.temperature).powi(2) + (humidity - config.humidity).powi(2);
+            if distance_sq < closest_distance {
+                closest = biome;
+                closest_distance = distance_sq;
+            }
+        }
+
+        closest
+    }
+}
@@ -0,0 +1,108 @@
+use super::GenerationContext;
+use super::GeneratorStage;
+use super::noise_seed;
+use crate::world::Block;
+use crate::world::Chunk;
+use crate::world::ChunkBlockIndex;
+use noise::{NoiseFn, Seedable, MultiFractal};
+
+type NoiseType = noise::Fbm;
+
+/// Carves caves out of the terrain using 3D density noise. Every check is done in world
+/// coordinates, so caves line up across chunk borders and regenerate identically for a seed.
+pub struct CaveCarver {
+    /// Large open caverns where the density noise passes a threshold.
+    cheese_noise: NoiseType,
+
+    /// Two independent noise fields; tunnels follow the lines where both are near zero.
+    tunnel_noise_a: NoiseType,
+    tunnel_noise_b: NoiseType,
+
+    /// 2D noise whose zero crossings form long, narrow ravines.
+    ravine_noise: NoiseType,
+
+    /// 2D noise that decides which parts of the world have ravines at all.
+    ravine_mask_noise: NoiseType,
+}
+
+impl CaveCarver {
+    /// How dense the cheese noise has to be for a cavern to open up.
+    const CHEESE_THRESHOLD: f64 = 0.45;
+
+    /// How many blocks below the surface caverns have to stay so the ground isn't swiss cheese.
+    const CHEESE_SURFACE_MARGIN: f64 = 6.;
+
+    /// How close to zero both tunnel fields need to be; larger values make wider tunnels.
+    const TUNNEL_WIDTH: f64 = 0.06;
+
+    /// How close to zero the ravine field needs to be; larger values make wider ravines.
+    const RAVINE_WIDTH: f64 = 0.025;
+
+    /// How deep a ravine cuts below the surface at its deepest point.
+    const RAVINE_DEPTH: f64 = 28.;
+
+    /// How much of the world has ravines, as a threshold on the mask noise.
+    const RAVINE_MASK_THRESHOLD: f64 = 0.2;
+
+    pub fn new(seed: u32) -> CaveCarver {
+        CaveCarver {
+            cheese_noise: NoiseType::new().set_seed(noise_seed(seed, 4)).set_frequency(0.035).set_octaves(2),
+            tunnel_noise_a: NoiseType::new().set_seed(noise_seed(seed, 5)).set_frequency(0.02).set_octaves(1),
+            tunnel_noise_b: NoiseType::new().set_seed(noise_seed(seed, 6)).set_frequency(0.02).set_octaves(1),
+            ravine_noise: NoiseType::new().set_seed(noise_seed(seed, 7)).set_frequency(0.006).set_octaves(2),
+            ravine_mask_noise: NoiseType::new().set_seed(noise_seed(seed, 8)).set_frequency(0.004).set_octaves(1),
+        }
+    }
+
+    /// Determines if the block at the world position should be hollowed out. `surface` is the
+    /// height of the terrain in that column.
+    pub fn is_cave(&self, x: f64, y: f64, z: f64, surface: f64) -> bool {
+        let depth = surface - z;
+        let position = [x, y, z];
+
+        // Caverns stay underground
+        if depth > CaveCarver::CHEESE_SURFACE_MARGIN && self.cheese_noise.get(position) > CaveCarver::CHEESE_THRESHOLD {
+            return true;
+        }
+
+        // Tunnels may break through the surface to make cave entrances; squash the vertical
+        // axis so they wind horizontally more than they dive
+        let tunnel_position = [x, y, z * 1.8];
+        if self.tunnel_noise_a.get(tunnel_position).abs() < CaveCarver::TUNNEL_WIDTH && self.tunnel_noise_b.get(tunnel_position).abs() < CaveCarver::TUNNEL_WIDTH {
+            return true;
+        }
+
+        // Ravines are columns, so they get narrower the deeper they go
+        if depth < CaveCarver::RAVINE_DEPTH {
+            let mask = self.ravine_mask_noise.get([x, y]);
+            if mask > CaveCarver::RAVINE_MASK_THRESHOLD {
+                let taper = 1. - depth / CaveCarver::RAVINE_DEPTH;
+                if self.ravine_noise.get([x, y]).abs() < CaveCarver::RAVINE_WIDTH * taper {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl GeneratorStage for CaveCarver {
+    fn name(&self) -> &'static str { "caves" }
+
+    fn generate(&self, context: &mut GenerationContext) {
+        let chunk_bottom = context.chunk_bottom();
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                let (world_x, world_y) = context.world_position(x, y);
+                let height = context.columns[x][y].height;
+                for z in chunk_bottom..=height.min(context.chunk_top()) {
+                    // Leave the bottom of the world solid so caves can't open in to the void
+                    if z > 0 && self.is_cave(world_x as f64, world_y as f64, z as f64, height as f64) {
+                        context.chunk.set_block(&ChunkBlockIndex::new(x, y, z - chunk_bottom), Block::AIR);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use super::BiomeMap;
+use super::GenerationContext;
+use super::GeneratorStage;
+use super::TerrainColumns;
+use crate::world::Block;
+use crate::world::Chunk;
+use crate::world::ChunkBlockIndex;
+use crate::world::ChunkIndex;
+use crate::world::DeferredBlock;
+use crate::world::WorldBlockIndex;
+use crate::utils::Random;
+
+/// Places blocks for a feature relative to the chunk being generated. Blocks that land outside of
+/// the chunk are deferred to the neighboring chunk so features aren't cut off at the border.
+pub struct FeatureWriter<'a> {
+    index: &'a ChunkIndex,
+    chunk: &'a mut Chunk,
+    deferred: &'a mut Vec<DeferredBlock>
+}
+
+impl<'a> FeatureWriter<'a> {
+    pub fn new(index: &'a ChunkIndex, chunk: &'a mut Chunk, deferred: &'a mut Vec<DeferredBlock>) -> FeatureWriter<'a> {
+        FeatureWriter { index, chunk, deferred }
+    }
+
+    /// Places a block at a position relative to the chunk's origin. Like deferred blocks, this
+    /// only replaces invisible blocks.
+    pub fn place(&mut self, x: i64, y: i64, z: i64, block: Block) {
+        let inside =
+            x >= 0 && x < Chunk::SIZE_X as i64 &&
+            y >= 0 && y < Chunk::SIZE_Y as i64 &&
+            z >= 0 && z < Chunk::SIZE_Z as i64;
+        if inside {
+            self.chunk.set_block_if_invisible(&ChunkBlockIndex::new(x as usize, y as usize, z as usize), block);
+            return;
+        }
+
+        // Get the world position; blocks past the edge of the world are dropped
+        let world_x = x + i64::from(self.index.x) * Chunk::SIZE_X as i64;
+        let world_y = y + i64::from(self.index.y) * Chunk::SIZE_Y as i64;
+        let world_z = z + i64::from(self.index.z) * Chunk::SIZE_Z as i64;
+        let in_world = |value: i64| value >= 0 && value <= i64::from(u32::MAX);
+        if !in_world(world_x) || !in_world(world_y) || !in_world(world_z) { return; }
+
+        self.deferred.push(DeferredBlock {
+            index: WorldBlockIndex::new(world_x as u32, world_y as u32, world_z as u32),
+            block
+        });
+    }
+}
+
+/// Grows trees, tall grass and mushrooms on the surface of the terrain.
+pub struct Decorator {
+    seed: u32,
+    leaves: Block,
+    grass: Block,
+    mushrooms: [Block; 2],
+}
+
+impl Decorator {
+    /// Salt for `Random::for_chunk` so decorations don't follow the same sequence as other features.
+    const RANDOM_SALT: u32 = 2;
+
+    pub fn new(seed: u32) -> Decorator {
+        Decorator {
+            seed,
+            leaves: Block::from_id("leaves"),
+            grass: Block::from_id("grass_large"),
+            mushrooms: [Block::from_id("mushroom_tan"), Block::from_id("mushroom_red")]
+        }
+    }
+
+    pub fn decorate(&self, biome_map: &BiomeMap, columns: &TerrainColumns, index: &ChunkIndex, chunk: &mut Chunk, deferred: &mut Vec<DeferredBlock>) {
+        let mut random = Random::for_chunk(self.seed, index, Decorator::RANDOM_SALT);
+
+        // Find where each decoration goes before writing anything, so features placed earlier
+        // don't change the surface for later columns
+        let mut features = Vec::new();
+        for (x, row) in columns.iter().enumerate() {
+            for (y, column) in row.iter().enumerate() {
+                // Always roll so the sequence doesn't depend on the terrain
+                let roll = random.next_f64();
+
+                // Only the chunk containing the top of the terrain decorates the column
+                let chunk_bottom = index.z as usize * Chunk::SIZE_Z;
+                if column.height < chunk_bottom || column.height >= chunk_bottom + Chunk::SIZE_Z { continue; }
+                let surface = column.height - chunk_bottom;
+
+                // Only decorate the biome's natural surface; this keeps features off of the ground
+                // that caves and ravines have cut away
+                let config = biome_map.config(column.biome);
+                if *chunk.get_block(&ChunkBlockIndex::new(x, y, surface)) != config.surface_block { continue; }
+
+                // Pick the feature
+                let feature = if roll < config.tree_chance {
+                    Feature::Tree(config.trunk_block)
+                } else if roll < config.tree_chance + config.mushroom_chance {
+                    Feature::Mushroom
+                } else if roll < config.tree_chance + config.mushroom_chance + config.grass_chance {
+                    Feature::Grass
+                } else {
+                    continue;
+                };
+                features.push((feature, x as i64, y as i64, surface as i64 + 1));
+            }
+        }
+
+        // Place the features
+        let mut writer = FeatureWriter::new(index, chunk, deferred);
+        for (feature, x, y, z) in features {
+            match feature {
+                Feature::Tree(trunk) => self.place_tree(&mut writer, &mut random, trunk, x, y, z),
+                Feature::Mushroom => {
+                    let mushroom = self.mushrooms[random.range(0, self.mushrooms.len() as u32) as usize];
+                    writer.place(x, y, z, mushroom);
+                },
+                Feature::Grass => writer.place(x, y, z, self.grass),
+            }
+        }
+    }
+
+    fn place_tree(&self, writer: &mut FeatureWriter, random: &mut Random, trunk: Block, x: i64, y: i64, z: i64) {
+        let height = random.range(4, 7) as i64;
+        let top = z + height - 1;
+
+        // Place the trunk first so the leaves don't take its place
+        for dz in 0..height {
+            writer.place(x, y, z + dz, trunk);
+        }
+
+        // Place the canopy; the bottom two layers are wide with the corners clipped, the top two
+        // layers are a small cross
+        for dz in -2..=1i64 {
+            let radius: i64 = if dz < 0 { 2 } else { 1 };
+            for dx in -radius..=radius {
+                for dy in -radius..=radius {
+                    // Round off the corners
+                    if dx.abs() == radius && dy.abs() == radius && (radius == 1 || random.chance(0.5)) { continue; }
+
+                    writer.place(x + dx, y + dy, top + dz, self.leaves);
+                }
+            }
+        }
+    }
+}
+
+impl GeneratorStage for Decorator {
+    fn name(&self) -> &'static str { "decorations" }
+
+    fn generate(&self, context: &mut GenerationContext) {
+        self.decorate(context.biome_map, context.columns, context.index, context.chunk, context.deferred);
+    }
+}
+
+enum Feature {
+    Tree(Block),
+    Mushroom,
+    Grass,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trees_cross_chunk_borders() {
+        let decorator = Decorator::new(7);
+        let trunk = Block::from_id("wood");
+        let index = ChunkIndex::new(2, 3, 4);
+        let mut chunk = Chunk::empty();
+        let mut deferred = Vec::new();
+
+        // Grow a tree on the edge of the chunk
+        let edge = Chunk::SIZE_X as i64 - 1;
+        let mut random = Random::new(1);
+        decorator.place_tree(&mut FeatureWriter::new(&index, &mut chunk, &mut deferred), &mut random, trunk, edge, 5, 10);
+
+        // The trunk stays in the chunk and the leaves past the edge go to the neighbor
+        assert_eq!(*chunk.get_block(&ChunkBlockIndex::new(Chunk::SIZE_X - 1, 5, 10)), trunk);
+        let neighbor = ChunkIndex::new(3, 3, 4);
+        assert!(!deferred.is_empty());
+        for block in deferred.iter() {
+            assert_eq!(block.index.get_chunk_index(), neighbor);
+            assert_eq!(block.block, decorator.leaves);
+        }
+
+        // The leaves in line with the trunk are never clipped, so some land right next to it
+        let (x, y) = (3 * Chunk::SIZE_X as u32, 5 + 3 * Chunk::SIZE_Y as u32);
+        assert!(deferred.iter().any(|block| block.index.x == x && block.index.y == y));
+    }
+
+    #[test]
+    fn features_past_the_world_are_dropped() {
+        let index = ChunkIndex::new(0, 0, 0);
+        let mut chunk = Chunk::empty();
+        let mut deferred = Vec::new();
+        let mut writer = FeatureWriter::new(&index, &mut chunk, &mut deferred);
+        writer.place(-1, 0, 0, Block::from_id("leaves"));
+        writer.place(0, 0, -1, Block::from_id("leaves"));
+        writer.place(0, Chunk::SIZE_Y as i64, 0, Block::from_id("leaves"));
+
+        assert_eq!(deferred.len(), 1);
+        assert_eq!(deferred[0].index, WorldBlockIndex::new(0, Chunk::SIZE_Y as u32, 0));
+    }
+
+    #[test]
+    fn features_only_replace_invisible_blocks() {
+        let index = ChunkIndex::new(0, 0, 0);
+        let mut chunk = Chunk::empty();
+        let mut deferred = Vec::new();
+        let stone = Block::from_id("stone");
+        chunk.set_block(&ChunkBlockIndex::new(1, 1, 1), stone);
+
+        FeatureWriter::new(&index, &mut chunk, &mut deferred).place(1, 1, 1, Block::from_id("leaves"));
+        assert_eq!(*chunk.get_block(&ChunkBlockIndex::new(1, 1, 1)), stone);
+    }
+}
@@ -0,0 +1,236 @@
+mod biome;
+mod caves;
+mod decorations;
+mod ores;
+mod pipeline;
+mod structures;
+mod terrain;
+
+pub use biome::*;
+pub use caves::*;
+pub use decorations::*;
+pub use ores::*;
+pub use pipeline::*;
+pub use structures::*;
+pub use terrain::*;
+use crate::world::Chunk;
+use crate::world::WorldDelegate;
+use crate::world::Biome;
+use crate::world::ChunkIndex;
+use crate::world::DeferredBlock;
+use std::time::Instant;
+use crate::utils::AsFloatSeconds;
+
+/// Returns the seed for one of a world's noise functions, offset so each function gets different
+/// noise. The noise crate adds the octave to the seed without wrapping, so this also keeps seeds
+/// far enough below `u32::MAX` for any number of octaves.
+fn noise_seed(seed: u32, offset: u32) -> u32 {
+    seed.wrapping_add(offset) % (u32::MAX - noise::Fbm::MAX_OCTAVES as u32)
+}
+
+/// Generates terrain by running a pipeline of `GeneratorStage`s over every chunk.
+pub struct ProceduralWorld {
+    biome_map: BiomeMap,
+    terrain: TerrainShape,
+    stages: Vec<Box<dyn GeneratorStage>>
+}
+
+impl ProceduralWorld {
+    /// Creates a world with an empty pipeline; add stages to it with `add_stage`.
+    pub fn new(seed: u32) -> ProceduralWorld {
+        ProceduralWorld {
+            biome_map: BiomeMap::new(seed),
+            terrain: TerrainShape::new(seed),
+            stages: Vec::new()
+        }
+    }
+
+    /// Creates a world from a comma separated list of stage names; see `create_stage`.
+    pub fn from_config(seed: u32, config: &str) -> Result<ProceduralWorld, String> {
+        let mut world = ProceduralWorld::new(seed);
+        for stage in parse_stages(config, seed)? {
+            world.add_stage(stage);
+        }
+
+        info!(target: "gen", "Generator stages - {}", world.stages().collect::<Vec<_>>().join(", "));
+
+        Ok(world)
+    }
+
+    /// The names of the stages in the order they run.
+    pub fn stages(&self) -> impl Iterator<Item=&'static str> + '_ {
+        self.stages.iter().map(|stage| stage.name())
+    }
+
+    /// Adds a stage to the end of the pipeline.
+    pub fn add_stage(&mut self, stage: Box<dyn GeneratorStage>) {
+        self.stages.push(stage);
+    }
+}
+
+impl WorldDelegate for ProceduralWorld {
+    fn create_chunk(&self, index: &ChunkIndex, deferred: &mut Vec<DeferredBlock>) -> Chunk {
+        let start_instant = Instant::now();
+
+        let mut chunk = Chunk::empty();
+
+        // Find the terrain shape for the chunk
+        let columns = self.terrain.columns(&self.biome_map, index);
+
+        // The chunk is all sky if it's above the terrain, so skip generation; stages that build
+        // above the terrain (like trees) reach in to it through deferred blocks from the chunk below
+        let chunk_bottom = index.z as usize * Chunk::SIZE_Z;
+        let max_height = columns.iter().flat_map(|row| row.iter()).map(|column| column.height).max().unwrap_or(0);
+        if chunk_bottom > max_height {
+            return chunk;
+        }
+
+        // Run each stage
+        let mut context = GenerationContext {
+            index,
+            chunk: &mut chunk,
+            columns: &columns,
+            biome_map: &self.biome_map,
+            deferred
+        };
+        for stage in self.stages.iter() {
+            stage.generate(&mut context);
+        }
+
+        debug!(target: "gen", "Generated chunk {} - {:.3}", index, start_instant.elapsed().as_float_seconds());
+
+        chunk
+    }
+
+    fn biome_at(&self, x: u32, y: u32) -> Option<Biome> {
+        Some(self.biome_map.biome_at(x as f64, y as f64))
+    }
+
+    fn surface_height(&self, x: u32, y: u32) -> Option<u32> {
+        Some(self.terrain.column(&self.biome_map, x as usize, y as usize).height as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{Block, ChunkBlockIndex, WorldBlockIndex};
+
+    /// Every block in a chunk.
+    pub fn chunk_blocks(chunk: &Chunk) -> Vec<Block> {
+        let mut blocks = Vec::with_capacity(Chunk::SIZE_X * Chunk::SIZE_Y * Chunk::SIZE_Z);
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                for z in 0..Chunk::SIZE_Z {
+                    blocks.push(*chunk.get_block(&ChunkBlockIndex::new(x, y, z)));
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Generates a chunk, returning its blocks and the blocks it placed in other chunks.
+    fn generate(world: &ProceduralWorld, index: &ChunkIndex) -> (Vec<Block>, Vec<(WorldBlockIndex, Block)>) {
+        let mut deferred = Vec::new();
+        let chunk = world.create_chunk(index, &mut deferred);
+        let deferred = deferred.into_iter().map(|block| (block.index, block.block)).collect();
+        (chunk_blocks(&chunk), deferred)
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        // The chunk with the surface in it, and the one below with caves and ores
+        let surface = TerrainShape::SURFACE_HEIGHT / Chunk::SIZE_Z_U32;
+        let indices = [ChunkIndex::new(3, 5, surface), ChunkIndex::new(3, 5, surface - 1)];
+
+        let first = ProceduralWorld::from_config(42, &DEFAULT_STAGES.join(",")).unwrap();
+        let second = ProceduralWorld::from_config(42, &DEFAULT_STAGES.join(",")).unwrap();
+        for index in indices.iter() {
+            // Regenerating with the same world or a new one with the same seed gives the same chunk
+            let expected = generate(&first, index);
+            assert_eq!(generate(&first, index), expected, "chunk {}", index);
+            assert_eq!(generate(&second, index), expected, "chunk {}", index);
+        }
+
+        // A different seed gives different terrain
+        let other = ProceduralWorld::from_config(43, &DEFAULT_STAGES.join(",")).unwrap();
+        assert_ne!(generate(&other, &indices[1]).0, generate(&first, &indices[1]).0);
+    }
+
+    /// Turns every stone block in the chunk in to bricks.
+    struct BrickStage;
+
+    impl GeneratorStage for BrickStage {
+        fn name(&self) -> &'static str { "bricks" }
+
+        fn generate(&self, context: &mut GenerationContext) {
+            for x in 0..Chunk::SIZE_X {
+                for y in 0..Chunk::SIZE_Y {
+                    for z in 0..Chunk::SIZE_Z {
+                        let index = ChunkBlockIndex::new(x, y, z);
+                        if *context.chunk.get_block(&index) == Block::from_id("stone") {
+                            context.chunk.set_block(&index, Block::from_id("brick_stone"));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stands in for the cave stage without carving anything.
+    struct SolidCaves;
+
+    impl GeneratorStage for SolidCaves {
+        fn name(&self) -> &'static str { "caves" }
+
+        fn generate(&self, _context: &mut GenerationContext) { }
+    }
+
+    #[test]
+    fn added_stages_run_last() {
+        let index = ChunkIndex::new(3, 5, TerrainShape::SURFACE_HEIGHT / Chunk::SIZE_Z_U32 - 1);
+        let (shaped, _) = generate(&ProceduralWorld::from_config(42, "shape").unwrap(), &index);
+
+        let mut world = ProceduralWorld::from_config(42, "shape").unwrap();
+        world.add_stage(Box::new(BrickStage));
+        assert_eq!(world.stages().collect::<Vec<_>>(), ["shape", "bricks"]);
+
+        // The new stage sees the shape stage's stone
+        let stone = Block::from_id("stone");
+        let bricks = Block::from_id("brick_stone");
+        assert!(shaped.contains(&stone));
+        let expected: Vec<Block> = shaped.iter().map(|&block| if block == stone { bricks } else { block }).collect();
+        assert_eq!(generate(&world, &index).0, expected);
+    }
+
+    #[test]
+    fn swapped_stages_take_the_place_of_built_in_ones() {
+        let index = ChunkIndex::new(3, 5, TerrainShape::SURFACE_HEIGHT / Chunk::SIZE_Z_U32 - 1);
+
+        // Build the start of the default pipeline with the caves swapped out
+        let mut world = ProceduralWorld::new(42);
+        for &name in ["shape", "surface"].iter() {
+            world.add_stage(create_stage(name, 42).unwrap());
+        }
+        world.add_stage(Box::new(SolidCaves));
+        world.add_stage(create_stage("ores", 42).unwrap());
+        assert_eq!(world.stages().collect::<Vec<_>>(), DEFAULT_STAGES[..4]);
+
+        // Ores don't depend on the caves, so it's the same as having no caves at all
+        let without_caves = ProceduralWorld::from_config(42, "shape,surface,ores").unwrap();
+        assert_eq!(generate(&world, &index), generate(&without_caves, &index));
+    }
+
+    #[test]
+    fn unknown_stages_are_rejected() {
+        assert!(ProceduralWorld::from_config(42, "shape,lakes").is_err());
+        assert_eq!(ProceduralWorld::from_config(42, " shape, ,surface ").unwrap().stages().collect::<Vec<_>>(), ["shape", "surface"]);
+        assert_eq!(ProceduralWorld::from_config(42, "").unwrap().stages().count(), 0);
+    }
+
+    #[test]
+    fn seeds_near_the_limit() {
+        let world = ProceduralWorld::from_config(u32::MAX, &DEFAULT_STAGES.join(",")).unwrap();
+        generate(&world, &ChunkIndex::new(0, 0, TerrainShape::SURFACE_HEIGHT / Chunk::SIZE_Z_U32));
+    }
+}
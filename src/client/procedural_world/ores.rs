@@ -0,0 +1,114 @@
+use super::GenerationContext;
+use super::GeneratorStage;
+use crate::world::Block;
+use crate::world::Chunk;
+use crate::world::ChunkBlockIndex;
+use crate::world::ChunkIndex;
+use crate::utils::Random;
+use std::ops::Range;
+
+pub struct OreConfig {
+    /// The block the vein is made of.
+    pub block: Block,
+
+    /// The world Z range veins can start in.
+    pub heights: Range<u32>,
+
+    /// How many blocks the vein tries to place. Blocks that land outside of stone or outside of
+    /// the chunk are skipped, so veins are often smaller.
+    pub vein_size: u32,
+
+    /// How many veins are attempted per chunk.
+    pub veins_per_chunk: u32,
+}
+
+impl OreConfig {
+    pub fn new(block_id: &str, heights: Range<u32>, vein_size: u32, veins_per_chunk: u32) -> OreConfig {
+        OreConfig {
+            block: Block::from_id(block_id),
+            heights,
+            vein_size,
+            veins_per_chunk
+        }
+    }
+}
+
+/// Scatters ore veins through the stone of a chunk.
+pub struct OreDistribution {
+    seed: u32,
+    ores: Vec<OreConfig>,
+    host_block: Block,
+}
+
+impl OreDistribution {
+    /// Salt for `Random::for_chunk` so ores don't follow the same sequence as other features.
+    const RANDOM_SALT: u32 = 1;
+
+    pub fn new(seed: u32, ores: Vec<OreConfig>) -> OreDistribution {
+        OreDistribution {
+            seed,
+            ores,
+            host_block: Block::from_id("stone")
+        }
+    }
+
+    pub fn default_ores() -> Vec<OreConfig> {
+        vec![
+            OreConfig::new("stone_gravel", 1..170, 24, 3),
+            OreConfig::new("stone_coal", 64..170, 10, 8),
+            OreConfig::new("stone_coal_alt", 32..128, 8, 4),
+            OreConfig::new("stone_diamond", 1..48, 4, 1),
+        ]
+    }
+
+    pub fn populate(&self, index: &ChunkIndex, chunk: &mut Chunk) {
+        let mut random = Random::for_chunk(self.seed, index, OreDistribution::RANDOM_SALT);
+        let chunk_bottom = index.z * Chunk::SIZE_Z_U32;
+        let chunk_top = chunk_bottom + Chunk::SIZE_Z_U32;
+
+        for ore in self.ores.iter() {
+            // Clip the ore heights to the chunk, skipping ores that can't start in it; the random
+            // sequence is unique to the chunk, so this doesn't change what other chunks generate
+            let start = ore.heights.start.max(chunk_bottom);
+            let end = ore.heights.end.min(chunk_top);
+            if start >= end { continue; }
+
+            for _ in 0..ore.veins_per_chunk {
+                let x = random.range(0, Chunk::SIZE_X_U32);
+                let y = random.range(0, Chunk::SIZE_Y_U32);
+                let z = random.range(start, end);
+                self.place_vein(&mut random, chunk, ore, [x as i32, y as i32, (z - chunk_bottom) as i32]);
+            }
+        }
+    }
+
+    /// Places a vein by randomly walking from the start position.
+    fn place_vein(&self, random: &mut Random, chunk: &mut Chunk, ore: &OreConfig, start: [i32; 3]) {
+        let mut position = start;
+        for _ in 0..ore.vein_size {
+            // Replace the block if it's stone inside of the chunk
+            let inside =
+                position[0] >= 0 && position[0] < Chunk::SIZE_X as i32 &&
+                position[1] >= 0 && position[1] < Chunk::SIZE_Y as i32 &&
+                position[2] >= 0 && position[2] < Chunk::SIZE_Z as i32;
+            if inside {
+                let index = ChunkBlockIndex::new(position[0] as usize, position[1] as usize, position[2] as usize);
+                if *chunk.get_block(&index) == self.host_block {
+                    chunk.set_block(&index, ore.block);
+                }
+            }
+
+            // Step one block along a random axis
+            let axis = random.range(0, 3) as usize;
+            position[axis] += if random.chance(0.5) { 1 } else { -1 };
+        }
+    }
+}
+
+impl GeneratorStage for OreDistribution {
+    fn name(&self) -> &'static str { "ores" }
+
+    fn generate(&self, context: &mut GenerationContext) {
+        self.populate(context.index, context.chunk);
+    }
+}
@@ -0,0 +1,80 @@
+use super::BiomeMap;
+use super::CaveCarver;
+use super::Decorator;
+use super::OreDistribution;
+use super::ShapeStage;
+use super::StructureStage;
+use super::SurfaceStage;
+use super::TerrainColumns;
+use crate::world::Chunk;
+use crate::world::ChunkIndex;
+use crate::world::DeferredBlock;
+
+/// Everything a stage can read or write while a chunk is being generated.
+pub struct GenerationContext<'a> {
+    pub index: &'a ChunkIndex,
+    pub chunk: &'a mut Chunk,
+
+    /// The terrain shape of each column in the chunk.
+    pub columns: &'a TerrainColumns,
+
+    pub biome_map: &'a BiomeMap,
+
+    /// Blocks placed outside of the chunk; see `FeatureWriter`.
+    pub deferred: &'a mut Vec<DeferredBlock>,
+}
+
+impl<'a> GenerationContext<'a> {
+    /// The world Z of the lowest block in the chunk.
+    pub fn chunk_bottom(&self) -> usize {
+        self.index.z as usize * Chunk::SIZE_Z
+    }
+
+    /// The world Z of the highest block in the chunk.
+    pub fn chunk_top(&self) -> usize {
+        self.chunk_bottom() + Chunk::SIZE_Z - 1
+    }
+
+    /// Converts a chunk X and Y to the world position used to sample noise.
+    pub fn world_position(&self, x: usize, y: usize) -> (usize, usize) {
+        (x + self.index.x as usize * Chunk::SIZE_X, y + self.index.y as usize * Chunk::SIZE_Y)
+    }
+}
+
+/// A single step of world generation. Stages run in order on the same chunk, so each one builds on
+/// the blocks the previous stages placed.
+pub trait GeneratorStage {
+    /// The name the stage is referred to by in pipeline configs.
+    fn name(&self) -> &'static str;
+
+    fn generate(&self, context: &mut GenerationContext);
+}
+
+/// The stages worlds are generated with unless `--stages` says otherwise. Structures run before
+/// decorations because features only replace invisible blocks; placed after, a tower's walls and
+/// hearth would have holes wherever a tree, tall grass or mushroom had already grown.
+pub const DEFAULT_STAGES: [&str; 6] = ["shape", "surface", "caves", "ores", "structures", "decorations"];
+
+/// Creates a built-in stage from its name.
+pub fn create_stage(name: &str, seed: u32) -> Option<Box<dyn GeneratorStage>> {
+    let stage: Box<dyn GeneratorStage> = match name {
+        "shape" => Box::new(ShapeStage::new()),
+        "surface" => Box::new(SurfaceStage),
+        "caves" => Box::new(CaveCarver::new(seed)),
+        "ores" => Box::new(OreDistribution::new(seed, OreDistribution::default_ores())),
+        "structures" => Box::new(StructureStage::new(seed)),
+        "decorations" => Box::new(Decorator::new(seed)),
+        _ => return None
+    };
+
+    Some(stage)
+}
+
+/// Parses a comma separated list of stage names, e.g. `shape,surface,ores`.
+pub fn parse_stages(config: &str, seed: u32) -> Result<Vec<Box<dyn GeneratorStage>>, String> {
+    config.split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| create_stage(name, seed).ok_or_else(|| format!("Unknown generator stage `{}`", name)))
+        .collect()
+}
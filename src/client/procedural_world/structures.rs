@@ -0,0 +1,85 @@
+use super::FeatureWriter;
+use super::GenerationContext;
+use super::GeneratorStage;
+use crate::world::Block;
+use crate::world::Chunk;
+use crate::world::ChunkBlockIndex;
+use crate::utils::Random;
+
+/// Builds rare ruined towers on the surface of the terrain. Towers near the edge of a chunk are
+/// finished in the neighboring chunks through deferred blocks.
+pub struct StructureStage {
+    seed: u32,
+    wall: Block,
+    hearth: Block,
+}
+
+impl StructureStage {
+    /// Salt for `Random::for_chunk` so structures don't follow the same sequence as other features.
+    const RANDOM_SALT: u32 = 3;
+
+    /// The chance of a chunk having a tower in it.
+    const TOWER_CHANCE: f64 = 0.04;
+
+    /// The distance from the center of a tower to its walls.
+    const TOWER_RADIUS: i64 = 2;
+
+    /// How far the walls extend below the base of the tower, so towers on slopes don't float.
+    const FOUNDATION_DEPTH: i64 = 4;
+
+    pub fn new(seed: u32) -> StructureStage {
+        StructureStage {
+            seed,
+            wall: Block::from_id("brick_stone"),
+            hearth: Block::from_id("furnace"),
+        }
+    }
+
+    fn place_tower(&self, writer: &mut FeatureWriter, random: &mut Random, x: i64, y: i64, z: i64) {
+        let height = random.range(3, 7) as i64;
+        let radius = StructureStage::TOWER_RADIUS;
+
+        // Place the hearth first so the walls don't take its place
+        writer.place(x, y, z, self.hearth);
+
+        // Place the walls; everything above the bottom layer crumbles away at random
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                if dx.abs() != radius && dy.abs() != radius { continue; }
+
+                // Leave a doorway in the middle of one side
+                let is_door = dx == 0 && dy == -radius;
+
+                for dz in -StructureStage::FOUNDATION_DEPTH..height {
+                    if is_door && (0..2).contains(&dz) { continue; }
+                    if dz > 0 && random.chance(0.3) { break; }
+
+                    writer.place(x + dx, y + dy, z + dz, self.wall);
+                }
+            }
+        }
+    }
+}
+
+impl GeneratorStage for StructureStage {
+    fn name(&self) -> &'static str { "structures" }
+
+    fn generate(&self, context: &mut GenerationContext) {
+        let mut random = Random::for_chunk(self.seed, context.index, StructureStage::RANDOM_SALT);
+        if !random.chance(StructureStage::TOWER_CHANCE) { return; }
+
+        // Only the chunk containing the top of the terrain builds on the column
+        let x = random.range(0, Chunk::SIZE_X as u32) as usize;
+        let y = random.range(0, Chunk::SIZE_Y as u32) as usize;
+        let column = &context.columns[x][y];
+        if column.height < context.chunk_bottom() || column.height > context.chunk_top() { return; }
+        let surface = column.height - context.chunk_bottom();
+
+        // Only build on the biome's natural surface, like decorations
+        let config = context.biome_map.config(column.biome);
+        if *context.chunk.get_block(&ChunkBlockIndex::new(x, y, surface)) != config.surface_block { return; }
+
+        let mut writer = FeatureWriter::new(context.index, context.chunk, context.deferred);
+        self.place_tower(&mut writer, &mut random, x as i64, y as i64, surface as i64 + 1);
+    }
+}
@@ -0,0 +1,132 @@
+use super::BiomeMap;
+use super::GenerationContext;
+use super::GeneratorStage;
+use super::noise_seed;
+use crate::world::Biome;
+use crate::world::Block;
+use crate::world::Chunk;
+use crate::world::ChunkBlockIndex;
+use crate::world::ChunkIndex;
+use noise::{NoiseFn, Seedable, MultiFractal};
+
+type NoiseType = noise::Fbm;
+
+/// The shape of the terrain in a single column of the world.
+#[derive(Copy, Clone)]
+pub struct TerrainColumn {
+    /// The world Z of the top block of the terrain.
+    pub height: usize,
+
+    /// The world Z where the stone ends and the biome's surface blocks begin.
+    pub stone_height: usize,
+
+    pub biome: Biome
+}
+
+pub type TerrainColumns = [[TerrainColumn; Chunk::SIZE_Y]; Chunk::SIZE_X];
+
+/// The 2D height field every stage builds on.
+pub struct TerrainShape {
+    height_noise: NoiseType,
+    dirt_depth_noise: NoiseType,
+}
+
+impl TerrainShape {
+    /// The world Z the terrain is centered around. This leaves a few layers of chunks below the
+    /// surface for caves and ores.
+    pub const SURFACE_HEIGHT: u32 = 160;
+
+    pub fn new(seed: u32) -> TerrainShape {
+        TerrainShape {
+            height_noise: NoiseType::new().set_seed(noise_seed(seed, 0)).set_frequency(0.05),
+            dirt_depth_noise: NoiseType::new().set_seed(noise_seed(seed, 1)).set_frequency(0.03),
+        }
+    }
+
+    pub fn column(&self, biome_map: &BiomeMap, world_x: usize, world_y: usize) -> TerrainColumn {
+        let noise_coords = [world_x as f64, world_y as f64];
+
+        // Blend the terrain shape of the surrounding biomes
+        let biome_sample = biome_map.sample(noise_coords[0], noise_coords[1]);
+        let height_noise = self.height_noise.get(noise_coords);
+        let height_offset = biome_sample.blend(biome_map.configs(), |c| c.base_height + c.height_amplitude * height_noise);
+
+        // Get the height of the terrain; saturating
+        let height = TerrainShape::SURFACE_HEIGHT as f64 + height_offset;
+        let height = height.max(0.) as usize;
+
+        // Determine the depth of the grass; saturating
+        let grass_depth = (self.dirt_depth_noise.get(noise_coords) + 1.5) * 3.;
+        let grass_depth = (grass_depth as usize).min(height);
+
+        TerrainColumn {
+            height,
+            stone_height: height - grass_depth,
+            biome: biome_sample.dominant()
+        }
+    }
+
+    pub fn columns(&self, biome_map: &BiomeMap, index: &ChunkIndex) -> TerrainColumns {
+        let mut columns = [[TerrainColumn { height: 0, stone_height: 0, biome: Biome::Plains }; Chunk::SIZE_Y]; Chunk::SIZE_X];
+        for (x, row) in columns.iter_mut().enumerate() {
+            for (y, column) in row.iter_mut().enumerate() {
+                let world_x = x + index.x as usize * Chunk::SIZE_X;
+                let world_y = y + index.y as usize * Chunk::SIZE_Y;
+                *column = self.column(biome_map, world_x, world_y);
+            }
+        }
+
+        columns
+    }
+}
+
+/// Fills every column with stone up to the terrain height.
+pub struct ShapeStage {
+    stone: Block
+}
+
+impl ShapeStage {
+    pub fn new() -> ShapeStage {
+        ShapeStage { stone: Block::from_id("stone") }
+    }
+}
+
+impl GeneratorStage for ShapeStage {
+    fn name(&self) -> &'static str { "shape" }
+
+    fn generate(&self, context: &mut GenerationContext) {
+        let chunk_bottom = context.chunk_bottom();
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                for z in chunk_bottom..=context.columns[x][y].height.min(context.chunk_top()) {
+                    context.chunk.set_block(&ChunkBlockIndex::new(x, y, z - chunk_bottom), self.stone);
+                }
+            }
+        }
+    }
+}
+
+/// Covers the stone with the blocks of each column's biome.
+pub struct SurfaceStage;
+
+impl GeneratorStage for SurfaceStage {
+    fn name(&self) -> &'static str { "surface" }
+
+    fn generate(&self, context: &mut GenerationContext) {
+        let chunk_bottom = context.chunk_bottom();
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                let column = &context.columns[x][y];
+                let biome_config = context.biome_map.config(column.biome);
+
+                // Replace the blocks above the stone
+                let start = column.stone_height.max(chunk_bottom);
+                for z in start..=column.height.min(context.chunk_top()) {
+                    let is_top = z == column.height;
+                    let block = if is_top { biome_config.surface_block } else { biome_config.subsurface_block };
+                    context.chunk.set_block(&ChunkBlockIndex::new(x, y, z - chunk_bottom), block);
+                }
+            }
+        }
+    }
+}
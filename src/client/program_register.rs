@@ -1,16 +1,72 @@
+use glium::backend::Facade;
+use std::fs;
+use std::path::Path;
+
 pub struct ProgramRegister {
-    pub default_program: glium::Program
+    pub default_program: glium::Program,
+    pub outline_program: glium::Program,
+    pub sky_program: glium::Program,
+    pub shadow_program: glium::Program,
+    pub hud_program: glium::Program
 }
 
 impl ProgramRegister {
-    pub fn new(display: &glium::Display) -> ProgramRegister {
+    pub fn new<F>(facade: &F) -> ProgramRegister where F: Facade {
         ProgramRegister {
-            default_program: program!(display,
+            default_program: program!(facade,
                 140 => {
                     vertex: include_str!("../../assets/shaders/voxel.vert"),
                     fragment: include_str!("../../assets/shaders/voxel.frag")
                 }
+            ).unwrap(),
+            outline_program: program!(facade,
+                140 => {
+                    vertex: include_str!("../../assets/shaders/outline.vert"),
+                    fragment: include_str!("../../assets/shaders/outline.frag")
+                }
+            ).unwrap(),
+            sky_program: program!(facade,
+                140 => {
+                    vertex: include_str!("../../assets/shaders/sky.vert"),
+                    fragment: include_str!("../../assets/shaders/sky.frag")
+                }
+            ).unwrap(),
+            shadow_program: program!(facade,
+                140 => {
+                    vertex: include_str!("../../assets/shaders/shadow.vert"),
+                    fragment: include_str!("../../assets/shaders/shadow.frag")
+                }
+            ).unwrap(),
+            hud_program: program!(facade,
+                140 => {
+                    vertex: include_str!("../../assets/shaders/hud.vert"),
+                    fragment: include_str!("../../assets/shaders/hud.frag")
+                }
             ).unwrap()
         }
     }
+
+    /// Compiles the shaders in `assets/shaders` again, so they can be changed while the game is
+    /// running. The programs are only replaced if all of them compile.
+    pub fn reload<F>(&mut self, facade: &F) -> Result<(), String> where F: Facade {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/shaders");
+        let load = |name: &str| {
+            let read = |extension: &str| {
+                let path = dir.join(format!("{}.{}", name, extension));
+                fs::read_to_string(&path).map_err(|err| format!("Failed to read `{}`: {}", path.display(), err))
+            };
+            let (vertex, fragment) = (read("vert")?, read("frag")?);
+            glium::Program::from_source(facade, &vertex, &fragment, None)
+                .map_err(|err| format!("Failed to compile the {} shader: {}", name, err))
+        };
+
+        *self = ProgramRegister {
+            default_program: load("voxel")?,
+            outline_program: load("outline")?,
+            sky_program: load("sky")?,
+            shadow_program: load("shadow")?,
+            hud_program: load("hud")?
+        };
+        Ok(())
+    }
 }
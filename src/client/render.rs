@@ -1,6 +1,34 @@
 use super::cg;
 use crate::world::Block;
 use crate::world::Chunk;
+use crate::world::Light;
+use crate::world::LightChannel;
+use crate::world::LightSampler;
+use vecmath::*;
+
+/// Settings for turning chunks in to meshes.
+#[derive(Debug, Clone)]
+pub struct MeshOptions {
+    /// The brightness of a vertex by how many of the three blocks around it in front of the face
+    /// are open, from boxed in (0) to fully open (3).
+    pub ao_curve: [f32; 4],
+
+    /// How far the exposed edges of blocks are bevelled in, or 0 for sharp edges.
+    pub bevel: f32
+}
+
+impl MeshOptions {
+    pub const DEFAULT_AO_CURVE: [f32; 4] = [0.45, 0.65, 0.82, 1.];
+}
+
+impl Default for MeshOptions {
+    fn default() -> MeshOptions {
+        MeshOptions {
+            ao_curve: MeshOptions::DEFAULT_AO_CURVE,
+            bevel: 0.
+        }
+    }
+}
 
 impl Block {
     const SPRITESHEET_WIDTH: usize = 1024;
@@ -36,6 +64,11 @@ impl Block {
         0, 3, 1, 1, 3, 2
     ];
 
+    /// The same as `FACE_ORDER`, but split along the other diagonal.
+    const FACE_ORDER_FLIPPED: [usize; 6] = [
+        0, 3, 2, 0, 2, 1
+    ];
+
     /// Determines the edges for each pair of vertices on a face. For sample, if the face points to
     /// vertices [a, b, c, d], the corresponding array [5, 7, 9, 11] says that the vertices d -> a
     /// are edge 5, vertices a -> b are edge 7, vertices b -> c are 9, and vertices c -> d are edge
@@ -50,6 +83,22 @@ impl Block {
         [ 9,  3, 11,  7]   // Bottom;  CB, LB, FB, RB
     ];
 
+    /// The two sides that meet at each edge, matching `Chunk::EDGE_DIRS`.
+    const EDGE_SIDES: [[usize; 2]; 12] = [
+        [0, 2],  //  0: CR
+        [0, 3],  //  1: CL
+        [0, 4],  //  2: CT
+        [0, 5],  //  3: CB
+        [1, 2],  //  4: FR
+        [1, 3],  //  5: FL
+        [1, 4],  //  6: FT
+        [1, 5],  //  7: FB
+        [2, 4],  //  8: RT
+        [2, 5],  //  9: RB
+        [3, 4],  // 10: LT
+        [3, 5],  // 11: LB
+    ];
+
     const NORMALS: [[f32; 3]; 6] = [
         [ 0.,  0., -1.],
         [ 0.,  0.,  1.],
@@ -66,70 +115,336 @@ impl Block {
 //        [0., 1.],
 //    ];
 
-    pub fn render(&self, vertices: &mut Vec<cg::Vertex>, x: f32, y: f32, z: f32, sides: u8, edges: u32, corners: u8) {
+    #[allow(clippy::too_many_arguments)]  // The neighbor flags come from the chunk separately
+    pub fn render(&self, vertices: &mut Vec<cg::Vertex>, x: f32, y: f32, z: f32, sides: u8, edges: u32, corners: u8, light: &LightSampler, options: &MeshOptions) {
         // If the block is empty, do nothing
         if sides == 0b000000 { return; }
 
+        let context = BlockContext { block: *self, x, y, z, sides, edges, corners, light, options };
+        let bevelled = context.bevelled_edges();
+
         // Add the vertices
         for side in 0..6 {
             // Make sure the side is visible
             if sides & (1 << side) == 0b000000 { continue; }
 
-            // Calculate UV coordinates for the face
-            let texture_pos = self.texture_pos(side);
-            let uv_lower = [
-                texture_pos.0 as f32 * Block::UV_TILE_SIZE_PADDED_X,
-                1. - texture_pos.1 as f32 * Block::UV_TILE_SIZE_PADDED_Y - Block::UV_TILE_SIZE_Y
-            ];
-            let uv_upper = [
-                uv_lower[0] + Block::UV_TILE_SIZE_X,
-                uv_lower[1] + Block::UV_TILE_SIZE_Y
-            ];
-            let uv_coords = [  // Flip the Y coords on these so the side of the blocks show the right dir
-                [uv_upper[0], uv_upper[1]],  // 1, 1
-                [uv_lower[0], uv_upper[1]],  // 0, 1
-                [uv_lower[0], uv_lower[1]],  // 0, 0
-                [uv_upper[0], uv_lower[1]],  // 1, 0
-            ];
-
-            // Add the vert data
-            let face_index = &Block::FACES[side];
-            for &pos in &Block::FACE_ORDER {
-                // Get position
-                let vertex_index = face_index[pos];  // Also used as the corner index
-                let mut position = Block::VERTICES[vertex_index];
-                position[0] += x;
-                position[1] += z;  // Swap Y with Z
-                position[2] += y;  // Swap Z with Y
-
-                // Get the color
-                let has_edge_a = edges & (1 << Block::FACE_EDGES[side][pos]) != 0;
-                let has_edge_b = edges & (1 << Block::FACE_EDGES[side][(pos + 1) % 4]) != 0;
-                let has_corner = corners & (1 << vertex_index) != 0;
-                let shade_corner = !has_edge_a || !has_edge_b || !has_corner;
-                let darkness = 0.5;
-                let color = if shade_corner { [darkness, darkness, darkness] } else { [1., 1., 1.] };
-
-                // Get normal
-                let normal = Block::NORMALS[side as usize];
-
-                // Get UV coords
-                let uv = uv_coords[pos];
-
-                vertices.push(cg::Vertex { position, color, normal, uv });
+            // Build the vert data, pulling the corners in from any bevelled edges
+            let mut face_vertices = Vec::with_capacity(4);
+            let mut occlusion = [0; 4];
+            for (pos, occlusion) in occlusion.iter_mut().enumerate() {
+                *occlusion = context.occlusion(side, pos);
+                let position = context.inset_corner(side, pos, bevelled);
+                face_vertices.push(context.vertex(side, pos, position, side, Block::NORMALS[side]));
+            }
+
+            // Split the face along the brighter diagonal, otherwise the shading of a dark vertex
+            // stretches across the face and looks different depending on which way it faces
+            let order = if occlusion[0] + occlusion[2] > occlusion[1] + occlusion[3] {
+                &Block::FACE_ORDER_FLIPPED
+            } else {
+                &Block::FACE_ORDER
+            };
+            for &pos in order {
+                vertices.push(face_vertices[pos]);
+            }
+        }
+
+        // Fill in the gaps left by pulling in the faces
+        if options.bevel > 0. {
+            context.render_bevels(vertices, bevelled);
+        }
+    }
+
+    /// Returns the UV coordinates of a point on one of the block's faces. Points off of the face
+    /// are projected on to it.
+    fn face_uv(&self, side: usize, point: [f32; 3]) -> [f32; 2] {
+        // Calculate UV coordinates for the face
+        let texture_pos = self.texture_pos(side);
+        let uv_lower = [
+            texture_pos.0 as f32 * Block::UV_TILE_SIZE_PADDED_X,
+            1. - texture_pos.1 as f32 * Block::UV_TILE_SIZE_PADDED_Y - Block::UV_TILE_SIZE_Y
+        ];
+
+        // Measure the point along the face; the first corner is at the top of the texture and the
+        // third is at the bottom, so the side of the blocks show the right dir
+        let face_index = &Block::FACES[side];
+        let origin = Block::VERTICES[face_index[2]];
+        let u_axis = vec3_sub(Block::VERTICES[face_index[3]], origin);
+        let v_axis = vec3_sub(Block::VERTICES[face_index[1]], origin);
+        let relative = vec3_sub(point, origin);
+        [
+            uv_lower[0] + vec3_dot(relative, u_axis) * Block::UV_TILE_SIZE_X,
+            uv_lower[1] + vec3_dot(relative, v_axis) * Block::UV_TILE_SIZE_Y
+        ]
+    }
+
+    /// Counts how open a vertex is from the two blocks on the edges next to it and the one in the
+    /// corner, each of which is open if its flag is set. Returns 0 when the vertex is boxed in,
+    /// which includes when both edges are blocked since the corner can't be seen past them, up to
+    /// 3 when all three are open.
+    fn ambient_occlusion(edge_a: bool, edge_b: bool, corner: bool) -> usize {
+        if !edge_a && !edge_b {
+            0
+        } else {
+            edge_a as usize + edge_b as usize + corner as usize
+        }
+    }
+
+    /// Averages the light of the blocks in front of a face that touch the vertex, so the light
+    /// blends smoothly from one block to the next.
+    fn vertex_light(light: &LightSampler, block: [i64; 3], side: usize, vertex_index: usize) -> [f32; 2] {
+        let normal = Block::NORMALS[side];
+        let normal = [normal[0] as i64, normal[2] as i64, normal[1] as i64];  // Flip Y with Z
+        let vertex = Block::VERTICES[vertex_index];
+        let vertex = [vertex[0], vertex[2], vertex[1]];  // Flip Y with Z
+
+        // Sample the block in front of the face, the two next to it that share the vertex, and the
+        // one diagonal to it
+        let mut samples = [None; 4];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let mut position = [block[0] + normal[0], block[1] + normal[1], block[2] + normal[2]];
+            let mut bit = 0;
+            for axis in 0..3 {
+                if normal[axis] != 0 { continue; }
+                if i & (1 << bit) != 0 {
+                    position[axis] += if vertex[axis] > 0.5 { 1 } else { -1 };
+                }
+                bit += 1;
+            }
+
+            // Unloaded chunks are lit like the open sky, same as when lighting the world
+            let (block, block_light) = light.get(position[0], position[1], position[2]).unwrap_or((Block::AIR, Light::SKY));
+            if block.is_transparent() {
+                *sample = Some(block_light);
+            }
+        }
+
+        // Light can't get to the diagonal block through two solid blocks
+        if samples[1].is_none() && samples[2].is_none() {
+            samples[3] = None;
+        }
+
+        // Average the samples
+        let mut total = [0.; 2];
+        let mut count = 0.;
+        for sample in samples.iter().flatten() {
+            total[0] += sample.get(LightChannel::Sky) as f32;
+            total[1] += sample.get(LightChannel::Block) as f32;
+            count += 1.;
+        }
+        if count == 0. { return [0., 0.]; }
+        [total[0] / count / Light::MAX as f32, total[1] / count / Light::MAX as f32]
+    }
+}
+
+/// A block and what's around it, which is everything needed to build its vertices.
+struct BlockContext<'a> {
+    block: Block,
+    x: f32,
+    y: f32,
+    z: f32,
+    sides: u8,
+    edges: u32,
+    corners: u8,
+    light: &'a LightSampler<'a>,
+    options: &'a MeshOptions
+}
+
+impl<'a> BlockContext<'a> {
+    /// Builds a vertex at a position in the block. The shading comes from a corner of a face and
+    /// the texture from a (possibly different) face.
+    fn vertex(&self, side: usize, pos: usize, position: [f32; 3], uv_side: usize, normal: [f32; 3]) -> cg::Vertex {
+        let vertex_index = Block::FACES[side][pos];
+
+        // Get the color
+        let brightness = self.options.ao_curve[self.occlusion(side, pos)];
+        let color = [brightness, brightness, brightness];
+
+        // Get the light
+        let light = Block::vertex_light(self.light, [self.x as i64, self.y as i64, self.z as i64], side, vertex_index);
+
+        // Get UV coords
+        let uv = self.block.face_uv(uv_side, position);
+
+        // Move the position in to the chunk
+        let position = [
+            position[0] + self.x,
+            position[1] + self.z,  // Swap Y with Z
+            position[2] + self.y   // Swap Z with Y
+        ];
+
+        cg::Vertex { position, color, normal, uv, light }
+    }
+
+    /// Returns how open a corner of a face is; see `Block::ambient_occlusion`.
+    fn occlusion(&self, side: usize, pos: usize) -> usize {
+        let has_edge_a = self.edges & (1 << Block::FACE_EDGES[side][pos]) != 0;
+        let has_edge_b = self.edges & (1 << Block::FACE_EDGES[side][(pos + 1) % 4]) != 0;
+        let has_corner = self.corners & (1 << Block::FACES[side][pos]) != 0;
+        Block::ambient_occlusion(has_edge_a, has_edge_b, has_corner)
+    }
+
+    /// Returns the flags of the edges to bevel. An edge is bevelled when both faces on it are
+    /// showing and there's no block diagonal to it to leave a gap next to.
+    fn bevelled_edges(&self) -> u32 {
+        if self.options.bevel <= 0. { return 0; }
+
+        let mut bevelled = 0;
+        for (edge, edge_sides) in Block::EDGE_SIDES.iter().enumerate() {
+            let sides_showing = edge_sides.iter().all(|&side| self.sides & (1 << side) != 0);
+            if sides_showing && self.edges & (1 << edge) != 0 {
+                bevelled |= 1 << edge;
+            }
+        }
+
+        bevelled
+    }
+
+    /// Returns the position of a face's corner, moved along the face away from the bevelled edges
+    /// that touch it.
+    fn inset_corner(&self, side: usize, pos: usize, bevelled: u32) -> [f32; 3] {
+        let face_index = &Block::FACES[side];
+        let corner = Block::VERTICES[face_index[pos]];
+        let previous = Block::VERTICES[face_index[(pos + 3) % 4]];
+        let next = Block::VERTICES[face_index[(pos + 1) % 4]];
+
+        // Moving away from an edge means moving along the other edge on the corner
+        let mut position = corner;
+        if bevelled & (1 << Block::FACE_EDGES[side][pos]) != 0 {
+            position = vec3_add(position, vec3_scale(vec3_sub(next, corner), self.options.bevel));
+        }
+        if bevelled & (1 << Block::FACE_EDGES[side][(pos + 1) % 4]) != 0 {
+            position = vec3_add(position, vec3_scale(vec3_sub(previous, corner), self.options.bevel));
+        }
+
+        position
+    }
+
+    /// Adds a slanted strip along each bevelled edge and a triangle in the corners where they meet.
+    /// Also caps the ends of the neighboring blocks' bevels that run in to the side of this block.
+    fn render_bevels(&self, vertices: &mut Vec<cg::Vertex>, bevelled: u32) {
+        // Add the strips
+        for (edge, &[side_a, side_b]) in Block::EDGE_SIDES.iter().enumerate() {
+            if bevelled & (1 << edge) == 0 { continue; }
+
+            // Texture the strip like the top or bottom if it's on one so grass wraps over the edge
+            let uv_side = side_a.max(side_b);
+            let normal = vec3_normalized(vec3_add(Block::NORMALS[side_a], Block::NORMALS[side_b]));
+
+            // Connect the pulled in corners of both faces at each end of the edge
+            let ends: Vec<usize> = Block::FACES[side_a].iter().cloned().filter(|vertex| Block::FACES[side_b].contains(vertex)).collect();
+            let mut strip = Vec::with_capacity(4);
+            for &(side, end) in [(side_a, ends[0]), (side_a, ends[1]), (side_b, ends[1]), (side_b, ends[0])].iter() {
+                let pos = BlockContext::face_pos(side, end);
+                strip.push(self.vertex(side, pos, self.inset_corner(side, pos, bevelled), uv_side, normal));
             }
+            BlockContext::push_triangle(vertices, [strip[0], strip[1], strip[2]], normal);
+            BlockContext::push_triangle(vertices, [strip[0], strip[2], strip[3]], normal);
+        }
+
+        // Add the corners
+        for vertex_index in 0..8 {
+            // Only corners with all three faces showing have a gap
+            let sides: Vec<usize> = (0..6).filter(|&side| Block::FACES[side].contains(&vertex_index)).collect();
+            if !sides.iter().all(|&side| self.sides & (1 << side) != 0) { continue; }
+
+            // With two bevelled edges the strips already meet. With one, the gap is a missing
+            // piece of the face that isn't on the edge, and with three it's a slanted triangle.
+            let corner_edges: Vec<usize> = (0..12)
+                .filter(|&edge| sides.contains(&Block::EDGE_SIDES[edge][0]) && sides.contains(&Block::EDGE_SIDES[edge][1]))
+                .filter(|&edge| bevelled & (1 << edge) != 0)
+                .collect();
+            let (normal, uv_side) = match corner_edges.len() {
+                1 => {
+                    let edge_sides = &Block::EDGE_SIDES[corner_edges[0]];
+                    let side = *sides.iter().find(|side| !edge_sides.contains(side)).unwrap();
+                    (Block::NORMALS[side], side)
+                },
+                3 => {
+                    let normal = sides.iter().fold([0.; 3], |normal, &side| vec3_add(normal, Block::NORMALS[side]));
+                    (vec3_normalized(normal), sides[2])
+                },
+                _ => continue
+            };
+
+            let mut triangle = Vec::with_capacity(3);
+            for &side in sides.iter() {
+                let pos = BlockContext::face_pos(side, vertex_index);
+                triangle.push(self.vertex(side, pos, self.inset_corner(side, pos, bevelled), uv_side, normal));
+            }
+            BlockContext::push_triangle(vertices, [triangle[0], triangle[1], triangle[2]], normal);
+        }
+
+        // Add the caps on the hidden sides
+        for side in 0..6 {
+            if self.sides & (1 << side) != 0 { continue; }
+
+            for pos in 0..4 {
+                // The neighbor bevels the edge that lines up with this block's edge through the
+                // corner if both of the neighbor's faces on it are showing and the corner is open
+                let (edge_a, edge_b) = (Block::FACE_EDGES[side][pos], Block::FACE_EDGES[side][(pos + 1) % 4]);
+                let vertex_index = Block::FACES[side][pos];
+                let neighbor_bevelled = self.edges & (1 << edge_a) != 0 && self.edges & (1 << edge_b) != 0 && self.corners & (1 << vertex_index) != 0;
+                if !neighbor_bevelled { continue; }
+
+                // Find this block's edge through the corner; no cap is needed if it's bevelled too
+                let other_side = |edge: usize| Block::EDGE_SIDES[edge].iter().cloned().find(|&other| other != side).unwrap();
+                let (side_a, side_b) = (other_side(edge_a), other_side(edge_b));
+                let edge = Block::EDGE_SIDES.iter().position(|sides| sides.contains(&side_a) && sides.contains(&side_b)).unwrap();
+                if bevelled & (1 << edge) != 0 { continue; }
+
+                // Cover the notch the neighbor's bevel leaves in this side
+                let face_index = &Block::FACES[side];
+                let corner = Block::VERTICES[vertex_index];
+                let previous = Block::VERTICES[face_index[(pos + 3) % 4]];
+                let next = Block::VERTICES[face_index[(pos + 1) % 4]];
+                let positions = [
+                    corner,
+                    vec3_add(corner, vec3_scale(vec3_sub(previous, corner), self.options.bevel)),
+                    vec3_add(corner, vec3_scale(vec3_sub(next, corner), self.options.bevel))
+                ];
+                let normal = Block::NORMALS[side];
+                let triangle = [
+                    self.vertex(side, pos, positions[0], side, normal),
+                    self.vertex(side, pos, positions[1], side, normal),
+                    self.vertex(side, pos, positions[2], side, normal)
+                ];
+                BlockContext::push_triangle(vertices, triangle, normal);
+            }
+        }
+    }
+
+    /// Returns where a vertex is in a face's list of corners.
+    fn face_pos(side: usize, vertex_index: usize) -> usize {
+        Block::FACES[side].iter().position(|&vertex| vertex == vertex_index).unwrap()
+    }
+
+    /// Adds a triangle, winding it so it faces the direction of the normal.
+    fn push_triangle(vertices: &mut Vec<cg::Vertex>, triangle: [cg::Vertex; 3], normal: [f32; 3]) {
+        let [a, b, c] = triangle;
+        let facing = vec3_cross(vec3_sub(b.position, a.position), vec3_sub(c.position, a.position));
+        if vec3_dot(facing, normal) >= 0. {
+            vertices.extend_from_slice(&[a, b, c]);
+        } else {
+            vertices.extend_from_slice(&[a, c, b]);
         }
     }
 }
 
 impl Chunk {
     // TODO: Add offset for the chunk
-    pub fn render(&self, vertices: &mut Vec<cg::Vertex>) {
+    pub fn render(&self, vertices: &mut Vec<cg::Vertex>, light: &LightSampler, options: &MeshOptions) {
+        // Nothing to render in an empty chunk
+        let storage = match self.storage() {
+            Some(storage) => storage,
+            None => return
+        };
+
         // Render each blocks
         for x in 0..Chunk::SIZE_X {
             for y in 0..Chunk::SIZE_Y {
                 for z in 0..Chunk::SIZE_Z {
-                    self.data()[x][y][z].render(vertices, x as f32, y as f32, z as f32, self.sides()[x][y][z], self.edges()[x][y][z], self.corners()[x][y][z]);
+                    storage.data()[x][y][z].render(vertices, x as f32, y as f32, z as f32, storage.sides()[x][y][z], storage.edges()[x][y][z], storage.corners()[x][y][z], light, options);
                 }
             }
         }
@@ -0,0 +1,246 @@
+use super::world_renderer::WorldRenderer;
+use crate::utils;
+use crate::world;
+use glium::Surface;
+use glium::backend::Facade;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use vecmath::*;
+
+/// One slice of the view covered by its own shadow map. Closer slices are smaller so they get more
+/// detailed shadows.
+pub struct ShadowCascade {
+    map: glium::texture::DepthTexture2d,
+
+    /// Transforms a position in camera space (Y is up) in to the shadow map's clip space.
+    matrix: Matrix4<f32>,
+
+    /// How far away from the camera this cascade ends.
+    far: f32,
+
+    /// The size of a texel of the shadow map in blocks.
+    texel_size: f32,
+
+    /// How deep the shadow map is in blocks.
+    depth_range: f32
+}
+
+impl ShadowCascade {
+    pub fn get_map(&self) -> Sampler<'_, glium::texture::DepthTexture2d> {
+        self.map.sampled()
+            .minify_filter(MinifySamplerFilter::Nearest)
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .wrap_function(SamplerWrapFunction::Clamp)
+    }
+
+    pub fn get_matrix(&self) -> Matrix4<f32> {
+        self.matrix
+    }
+
+    pub fn get_far(&self) -> f32 {
+        self.far
+    }
+
+    /// How far to push positions out along their normal before looking them up so flat surfaces
+    /// don't shadow themselves.
+    pub fn get_normal_offset(&self) -> f32 {
+        self.texel_size * 1.5
+    }
+
+    /// How much closer than the stored depth a surface has to be to count as lit, in the shadow
+    /// map's depth units.
+    pub fn get_bias(&self) -> f32 {
+        self.texel_size / self.depth_range
+    }
+
+    /// Fits the shadow map around the slice of the view from `near` to the end of the cascade.
+    /// `corner_rays` are the directions through the corners of the screen in view space with a
+    /// depth of 1.
+    fn fit(&mut self, near: f32, corner_rays: &[Vector3<f32>; 4], inverse_view: Matrix4<f32>, light_rotation: Matrix4<f32>) {
+        // Get the corners of the slice in camera space
+        let mut corners = Vec::with_capacity(8);
+        for &depth in [near, self.far].iter() {
+            for ray in corner_rays.iter() {
+                let point = col_mat4_transform(inverse_view, [ray[0] * depth, ray[1] * depth, depth, 1.]);
+                corners.push([point[0], point[1], point[2]]);
+            }
+        }
+
+        // Surround the slice with a sphere. The radius only depends on the shape of the slice, so
+        // rounding it keeps the size of the texels from changing as the camera turns.
+        let center = vec3_scale(corners.iter().fold([0.; 3], |sum, &corner| vec3_add(sum, corner)), 1. / corners.len() as f32);
+        let radius = corners.iter().map(|&corner| vec3_len(vec3_sub(corner, center))).fold(0., f32::max);
+        let radius = (radius * 16.).ceil() / 16.;
+        let texel_size = radius * 2. / ShadowRenderer::MAP_SIZE as f32;
+
+        // Snap the center to the texels of the shadow map so the shadows don't shimmer as the
+        // camera moves
+        let light_center = col_mat4_transform(light_rotation, [center[0], center[1], center[2], 1.]);
+        let snap = |value: f32| (value / texel_size).round() * texel_size;
+        let depth_range = radius * 2. + ShadowRenderer::CASTER_DISTANCE;
+        let light_view = {
+            let mut matrix = light_rotation;
+            matrix[3] = [-snap(light_center[0]), -snap(light_center[1]), -(light_center[2] - radius - ShadowRenderer::CASTER_DISTANCE), 1.];
+            matrix
+        };
+
+        // Project the sphere orthographically; note that this is column-major
+        let light_projection = [
+            [1. / radius, 0., 0., 0.],
+            [0., 1. / radius, 0., 0.],
+            [0., 0., 2. / depth_range, 0.],
+            [0., 0., -1., 1.]
+        ];
+
+        self.matrix = col_mat4_mul(light_projection, light_view);
+        self.texel_size = texel_size;
+        self.depth_range = depth_range;
+    }
+}
+
+/// Renders cascaded shadow maps of the chunks from the direction of the sun.
+pub struct ShadowRenderer {
+    cascades: Vec<ShadowCascade>,
+    draw_params: glium::DrawParameters<'static>
+}
+
+impl ShadowRenderer {
+    pub const CASCADES: usize = 3;
+    const MAP_SIZE: u32 = 2048;
+
+    /// Where the first cascade starts.
+    const NEAR: f32 = 1.;
+
+    /// How much the splits between cascades are spaced out logarithmically instead of evenly.
+    const SPLIT_LAMBDA: f32 = 0.75;
+
+    /// How far behind a cascade blocks can be and still cast shadows in to it.
+    const CASTER_DISTANCE: f32 = 128.;
+
+    /// Creates the shadow maps, splitting the view up to the world renderer's view radius between
+    /// them.
+    pub fn new<F>(facade: &F, world_renderer: &WorldRenderer) -> ShadowRenderer where F: Facade {
+        let view_radius = world_renderer.get_view_radius();
+
+        let cascades = (0..ShadowRenderer::CASCADES)
+            .map(|i| {
+                // Blend between logarithmic and even splits; see "Parallel-Split Shadow Maps"
+                let t = (i + 1) as f32 / ShadowRenderer::CASCADES as f32;
+                let log_split = ShadowRenderer::NEAR * (view_radius / ShadowRenderer::NEAR).powf(t);
+                let even_split = ShadowRenderer::NEAR + (view_radius - ShadowRenderer::NEAR) * t;
+                let far = ShadowRenderer::SPLIT_LAMBDA * log_split + (1. - ShadowRenderer::SPLIT_LAMBDA) * even_split;
+
+                ShadowCascade {
+                    map: glium::texture::DepthTexture2d::empty_with_format(
+                        facade,
+                        glium::texture::DepthFormat::F32,
+                        glium::texture::MipmapsOption::NoMipmap,
+                        ShadowRenderer::MAP_SIZE,
+                        ShadowRenderer::MAP_SIZE
+                    ).unwrap(),
+                    matrix: mat4_id(),
+                    far,
+                    texel_size: 1.,
+                    depth_range: 1.
+                }
+            })
+            .collect();
+
+        ShadowRenderer {
+            cascades,
+            draw_params: glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::IfLess,
+                    write: true,
+                    .. Default::default()
+                },
+                .. Default::default()
+            }
+        }
+    }
+
+    pub fn get_cascades(&self) -> &[ShadowCascade] {
+        &self.cascades
+    }
+
+    /// Fits each cascade around its slice of the camera's view and draws the chunks in to it.
+    /// `light_direction` points towards the light in camera space.
+    pub fn render<F>(&mut self, facade: &F, program: &glium::Program, world_renderer: &WorldRenderer, camera: &utils::CameraState, light_direction: Vector3<f32>) where F: Facade {
+        let light_rotation = ShadowRenderer::light_rotation(light_direction);
+        let inverse_view = mat4_inv(camera.get_view());
+        let inverse_projection = mat4_inv(camera.get_full_perspective());
+
+        // Find the direction through each corner of the screen, scaled so its depth is 1
+        let mut corner_rays = [[0.; 3]; 4];
+        for (i, ray) in corner_rays.iter_mut().enumerate() {
+            let corner = [if i & 1 == 0 { -1. } else { 1. }, if i & 2 == 0 { -1. } else { 1. }, 1., 1.];
+            let point = col_mat4_transform(inverse_projection, corner);
+            *ray = [point[0] / point[2], point[1] / point[2], 1.];
+        }
+
+        let mut near = ShadowRenderer::NEAR;
+        for cascade in self.cascades.iter_mut() {
+            cascade.fit(near, &corner_rays, inverse_view, light_rotation);
+            near = cascade.far;
+
+            // Draw the chunks that can cast shadows in to the cascade
+            let mut target = glium::framebuffer::SimpleFrameBuffer::depth_only(facade, &cascade.map).unwrap();
+            target.clear_depth(1.);
+            for (_, mesh) in world_renderer.get_visible_chunks().iter() {
+                if !ShadowRenderer::mesh_in_view(cascade.matrix, mesh.transform) { continue; }
+
+                let uniforms = uniform! {
+                    model_matrix: mesh.transform,
+                    light_matrix: cascade.matrix
+                };
+
+                target.draw(
+                    &mesh.vertex_buffer,
+                    glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                    program,
+                    &uniforms,
+                    &self.draw_params
+                ).unwrap();
+            }
+        }
+    }
+
+    /// Creates a view matrix without a translation looking along the light.
+    fn light_rotation(light_direction: Vector3<f32>) -> Matrix4<f32> {
+        let f = vec3_normalized(vec3_neg(light_direction));
+
+        // Use a different up direction when the light is straight up so the cross product works
+        let up = if f[1].abs() > 0.99 { [0., 0., 1.] } else { [0., 1., 0.] };
+        let s = vec3_normalized(vec3_cross(f, up));
+        let u = vec3_cross(s, f);
+
+        // NOTE: Remember that this is column-major, so the lines of code are actually columns
+        [
+            [s[0], u[0], f[0], 0.],
+            [s[1], u[1], f[1], 0.],
+            [s[2], u[2], f[2], 0.],
+            [0., 0., 0., 1.]
+        ]
+    }
+
+    /// Determines if a chunk's bounding box overlaps the shadow map, ignoring the depth so chunks
+    /// between the cascade and the light still cast shadows.
+    fn mesh_in_view(light_matrix: Matrix4<f32>, transform: Matrix4<f32>) -> bool {
+        let size = [world::Chunk::SIZE_X_F32, world::Chunk::SIZE_Z_F32, world::Chunk::SIZE_Y_F32];  // Flip Y with Z
+        let mut min = [f32::INFINITY; 2];
+        let mut max = [f32::NEG_INFINITY; 2];
+        for corner in 0..8 {
+            let offset = [
+                if corner & 1 == 0 { 0. } else { size[0] },
+                if corner & 2 == 0 { 0. } else { size[1] },
+                if corner & 4 == 0 { 0. } else { size[2] }
+            ];
+            let point = col_mat4_transform(col_mat4_mul(light_matrix, transform), [offset[0], offset[1], offset[2], 1.]);
+            for axis in 0..2 {
+                min[axis] = min[axis].min(point[axis]);
+                max[axis] = max[axis].max(point[axis]);
+            }
+        }
+
+        (0..2).all(|axis| min[axis] <= 1. && max[axis] >= -1.)
+    }
+}
@@ -0,0 +1,59 @@
+use super::cg;
+use super::day_cycle::DayCycle;
+use glium::Surface;
+use glium::backend::Facade;
+use vecmath::*;
+
+/// Draws the sky behind everything as a gradient from the horizon up with the sun in it.
+pub struct SkyRenderer {
+    vertex_buffer: glium::VertexBuffer<cg::ScreenVertex>,
+    draw_params: glium::DrawParameters<'static>
+}
+
+impl SkyRenderer {
+    pub fn new<F>(facade: &F) -> SkyRenderer where F: Facade {
+        // Cover the screen with two triangles
+        let vertices = [
+            cg::ScreenVertex { position: [-1., -1.] },
+            cg::ScreenVertex { position: [ 1., -1.] },
+            cg::ScreenVertex { position: [ 1.,  1.] },
+            cg::ScreenVertex { position: [-1., -1.] },
+            cg::ScreenVertex { position: [ 1.,  1.] },
+            cg::ScreenVertex { position: [-1.,  1.] },
+        ];
+
+        SkyRenderer {
+            vertex_buffer: glium::VertexBuffer::new(facade, &vertices).unwrap(),
+            draw_params: glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::Overwrite,
+                    write: false,
+                    .. Default::default()
+                },
+                .. Default::default()
+            }
+        }
+    }
+
+    pub fn draw<S>(&self, target: &mut S, program: &glium::Program, day_cycle: &DayCycle, view_matrix: Matrix4<f32>, projection_matrix: Matrix4<f32>) where S: Surface {
+        // Only the rotation of the camera matters since the sky is infinitely far away
+        let mut view_matrix = view_matrix;
+        view_matrix[3] = [0., 0., 0., 1.];
+        let inverse_view_projection = mat4_inv(col_mat4_mul(projection_matrix, view_matrix));
+
+        let uniforms = uniform! {
+            inverse_view_projection: inverse_view_projection,
+            sun_direction: day_cycle.get_sun_direction(),
+            sky_color: day_cycle.get_sky_color(),
+            horizon_color: day_cycle.get_horizon_color()
+        };
+
+        target.draw(
+            &self.vertex_buffer,
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            program,
+            &uniforms,
+            &self.draw_params
+        ).unwrap();
+    }
+}
@@ -1,11 +1,14 @@
 use crate::world;
 use std::collections::HashMap;
 use vecmath::*;
+use crate::client::benchmark::StageTimings;
 use crate::client::cg;
+use crate::client::render::MeshOptions;
 use crate::utils;
 use utils::RangeContains;
-use std::u32;
+use std::ops::RangeInclusive;
 use std::time::Instant;
+use glium::backend::Facade;
 use crate::utils::AsFloatSeconds;
 use crate::utils::ChunkClamp;
 
@@ -17,15 +20,21 @@ pub struct ChunkMesh {
 pub struct WorldRenderer {
     view_distance: u32,
     vertical_view_distant: u32,
-    visible_chunks: HashMap<world::ChunkIndex, ChunkMesh>
+    visible_chunks: HashMap<world::ChunkIndex, ChunkMesh>,
+    mesh_options: MeshOptions,
+
+    /// How long each stage of loading chunks took, while benchmarking.
+    timings: Option<StageTimings>
 }
 
 impl WorldRenderer {
-    pub fn new(view_distance: u32) -> WorldRenderer {
+    pub fn new(view_distance: u32, mesh_options: MeshOptions) -> WorldRenderer {
         WorldRenderer {
             view_distance,
             vertical_view_distant: (view_distance / 2).max(1),  // Since chunks are twice as tall, make the vertical view range half as much
-            visible_chunks: HashMap::new()
+            visible_chunks: HashMap::new(),
+            mesh_options,
+            timings: None
         }
     }
 
@@ -33,19 +42,133 @@ impl WorldRenderer {
         &self.visible_chunks
     }
 
-    pub fn update(&mut self, app: &mut utils::App, world: &mut world::World, camera: &utils::CameraState) {
-        // Get the current chunk; saturating
-        let camera_pos = camera.get_position();
-        let current_chunk = world::ChunkIndex::new(
+    /// Returns the total number of vertices in the chunk meshes.
+    pub fn get_vertex_count(&self) -> usize {
+        self.visible_chunks.values().map(|mesh| mesh.vertex_buffer.len()).sum()
+    }
+
+    /// Returns how far away chunks are loaded, in chunks.
+    pub fn get_view_distance(&self) -> u32 {
+        self.view_distance
+    }
+
+    /// Changes how far away chunks are loaded. Chunks out of range are dropped on the next update.
+    pub fn set_view_distance(&mut self, view_distance: u32) {
+        self.view_distance = view_distance;
+        self.vertical_view_distant = (view_distance / 2).max(1);
+    }
+
+    /// Drops every chunk mesh, for when the world they were built from is replaced.
+    pub fn clear(&mut self) {
+        self.visible_chunks.clear();
+    }
+
+    /// Returns how far away from the camera chunks are always loaded, in blocks.
+    pub fn get_view_radius(&self) -> f32 {
+        self.view_distance as f32 * world::Chunk::SIZE_X_F32
+    }
+
+    /// Starts recording how long each stage of loading chunks takes.
+    pub fn start_timings(&mut self) {
+        self.timings = Some(StageTimings::new());
+    }
+
+    /// Returns the timings recorded since `start_timings`.
+    pub fn take_timings(&mut self) -> Option<StageTimings> {
+        self.timings.take()
+    }
+
+    /// Returns the chunk the position is in, in GL space, clamped to the world.
+    pub fn get_current_chunk(camera_pos: &Vector3<f32>) -> world::ChunkIndex {
+        world::ChunkIndex::new(
             (camera_pos[0] / world::Chunk::SIZE_X_F32).chunk_clamp_x() as u32,
             (camera_pos[2] / world::Chunk::SIZE_Y_F32).chunk_clamp_y() as u32,  // Flip Y with Z
             (camera_pos[1] / world::Chunk::SIZE_Z_F32).chunk_clamp_z() as u32,  // Flip Z with Y
-        );
+        )
+    }
+
+    /// Returns the chunks in view of a chunk along each axis.
+    fn get_view_ranges(&self, current_chunk: &world::ChunkIndex) -> [RangeInclusive<u32>; 3] {
+        [
+            current_chunk.x.saturating_sub(self.view_distance)..=current_chunk.x.saturating_add(self.view_distance),
+            current_chunk.y.saturating_sub(self.view_distance)..=current_chunk.y.saturating_add(self.view_distance),
+            current_chunk.z.saturating_sub(self.vertical_view_distant)..=current_chunk.z.saturating_add(self.vertical_view_distant)
+        ]
+    }
+
+    /// Returns the chunks in view of a chunk.
+    pub fn get_chunks_in_view(&self, current_chunk: &world::ChunkIndex) -> Vec<world::ChunkIndex> {
+        let [x_range, y_range, z_range] = self.get_view_ranges(current_chunk);
+        let mut chunks = Vec::new();
+        for chunk_x in x_range {
+            for chunk_y in y_range.clone() {
+                for chunk_z in z_range.clone() {
+                    chunks.push(world::ChunkIndex::new(chunk_x, chunk_y, chunk_z));
+                }
+            }
+        }
+        chunks
+    }
+
+    /// Generates the chunks that haven't been yet.
+    pub fn create_chunks(&mut self, world: &mut world::World, chunks: &[world::ChunkIndex]) {
+        for chunk_index in chunks {
+            if world.get_chunk(chunk_index).is_some() { continue; }
 
-        // Get view distance
-        let x_range = current_chunk.x.saturating_sub(self.view_distance)..=current_chunk.x.saturating_add(self.view_distance);
-        let y_range = current_chunk.y.saturating_sub(self.view_distance)..=current_chunk.y.saturating_add(self.view_distance);
-        let z_range = current_chunk.z.saturating_sub(self.vertical_view_distant)..=current_chunk.z.saturating_add(self.vertical_view_distant);
+            let span = utils::Span::new("gen", "generate_chunk");
+            let chunk_get_start = Instant::now();
+            let chunk = world.get_or_create_chunk(chunk_index);
+            let duration = chunk_get_start.elapsed().as_float_seconds();
+            drop(span);
+            debug!(target: "gen", "Fetched chunk {} - {} blocks - {:.3}", chunk_index, chunk.block_count(), duration);
+
+            if let Some(ref mut timings) = self.timings { timings.generation.push(duration); }
+        }
+    }
+
+    /// Processes the sides of a generated chunk and builds its vertices.
+    pub fn mesh_chunk(&mut self, world: &mut world::World, chunk_index: &world::ChunkIndex) -> Vec<cg::Vertex> {
+        // Process the chunk sides
+        let span = utils::Span::new("world", "process_sides");
+        let process_sides_start = Instant::now();
+        world.process_sides(chunk_index);
+        let sides_duration = process_sides_start.elapsed().as_float_seconds();
+        drop(span);
+        debug!(target: "world", "Processed sides {} - {:.3}", chunk_index, sides_duration);
+
+        // Get chunk vertices
+        let span = utils::Span::new("mesh", "mesh_chunk");
+        let render_start = Instant::now();
+        let mut vertices = Vec::new();
+        let chunk = world.get_chunk(chunk_index).unwrap();
+        chunk.render(&mut vertices, &world.light_sampler(chunk_index), &self.mesh_options);
+        let render_duration = render_start.elapsed().as_float_seconds();
+        drop(span);
+        debug!(target: "mesh", "Rendered chunk {} - {} tris - {:.3}", chunk_index, vertices.len() / 3, render_duration);
+
+        if let Some(ref mut timings) = self.timings {
+            timings.sides.push(sides_duration);
+            timings.meshing.push(render_duration);
+            timings.vertices += vertices.len();
+        }
+
+        vertices
+    }
+
+    pub fn update<F>(&mut self, facade: &F, world: &mut world::World, camera: &utils::CameraState) where F: Facade {
+        // Get the current chunk and the chunks in view of it
+        let current_chunk = WorldRenderer::get_current_chunk(camera.get_position());
+        let [x_range, y_range, z_range] = self.get_view_ranges(&current_chunk);
+        let chunks_in_view = self.get_chunks_in_view(&current_chunk);
+
+        // Create the chunks in view before rendering any of them, so the light has spread between
+        // them and they don't all need to be rendered again on the next frame
+        self.create_chunks(world, &chunks_in_view);
+
+        // Drop the meshes of chunks that changed so they get rendered again below
+        for chunk_index in world.take_modified_chunks() {
+            self.visible_chunks.remove(&chunk_index);
+        }
 
         // Remove chunks out of the view range
         let mut chunks_to_remove = Vec::new();
@@ -56,58 +179,41 @@ impl WorldRenderer {
         }
         for chunk_index in chunks_to_remove {
             self.visible_chunks.remove(&chunk_index);
-            println!("Removed chunk {}", chunk_index);
+            debug!(target: "mesh", "Removed chunk {}", chunk_index);
         }
 
         // Add new chunks
-        for chunk_x in *x_range.start()..=*x_range.end(){
-            for chunk_y in *y_range.start()..=*y_range.end() {
-                for chunk_z in *z_range.start()..=*z_range.end() {
-                    // Get the chunk index
-                    let chunk_index = world::ChunkIndex::new(chunk_x, chunk_y, chunk_z);
-
-                    // Make sure doesn't already have chunk
-                    if self.visible_chunks.contains_key(&chunk_index) { continue; }
-
-                    let start_instant = Instant::now();
-                    println!("Starting chunk {}", chunk_index);
-
-                    // Get or create the chunk
-                    let chunk_get_start = Instant::now();
-                    let chunk = world.get_or_create_chunk(&chunk_index);
-                    println!("> Fetched chunk {} - {} blocks - {:.3}", chunk_index, chunk.block_count(), chunk_get_start.elapsed().as_float_seconds());
-
-                    // Process the chunk sides
-                    let process_sides_start = Instant::now();
-                    chunk.process_sides();
-                    println!("> Processed sides {} - {:.3}", chunk_index, process_sides_start.elapsed().as_float_seconds());
-
-                    // Get chunk vertices
-                    let process_sides_start = Instant::now();
-                    let mut vertices = Vec::new();
-                    chunk.render(&mut vertices);
-                    println!("> Rendered chunk {} - {} tris - {:.3}", chunk_index, vertices.len() / 3, process_sides_start.elapsed().as_float_seconds());
-
-                    // Create mesh
-                    let transform = [
-                        [1., 0., 0., 0.],
-                        [0., 1., 0., 0.],
-                        [0., 0., 1., 0.],
-                        [
-                            chunk_x as f32 * world::Chunk::SIZE_X_F32,
-                            chunk_z as f32 * world::Chunk::SIZE_Z_F32,  // Flip Y with Z
-                            chunk_y as f32 * world::Chunk::SIZE_Y_F32,  // Flip Z with Y
-                            1.
-                        ]
-                    ];
-                    let vertex_buffer = glium::VertexBuffer::new(&app.display, &vertices[..]).unwrap();
-
-                    // Save the mesh
-                    self.visible_chunks.insert(chunk_index.clone(), ChunkMesh { transform, vertex_buffer });
-
-                    println!("Finished chunk {} - {:.3}", chunk_index, start_instant.elapsed().as_float_seconds());
-                }
-            }
+        for chunk_index in chunks_in_view {
+            // Make sure doesn't already have chunk
+            if self.visible_chunks.contains_key(&chunk_index) { continue; }
+
+            let start_instant = Instant::now();
+            trace!(target: "mesh", "Starting chunk {}", chunk_index);
+
+            let vertices = self.mesh_chunk(world, &chunk_index);
+
+            // Create mesh
+            let transform = [
+                [1., 0., 0., 0.],
+                [0., 1., 0., 0.],
+                [0., 0., 1., 0.],
+                [
+                    chunk_index.x as f32 * world::Chunk::SIZE_X_F32,
+                    chunk_index.z as f32 * world::Chunk::SIZE_Z_F32,  // Flip Y with Z
+                    chunk_index.y as f32 * world::Chunk::SIZE_Y_F32,  // Flip Z with Y
+                    1.
+                ]
+            ];
+            let span = utils::Span::new("render", "upload_chunk");
+            let upload_start = Instant::now();
+            let vertex_buffer = glium::VertexBuffer::new(facade, &vertices[..]).unwrap();
+            if let Some(ref mut timings) = self.timings { timings.upload.push(upload_start.elapsed().as_float_seconds()); }
+            drop(span);
+
+            // Save the mesh
+            self.visible_chunks.insert(chunk_index.clone(), ChunkMesh { transform, vertex_buffer });
+
+            debug!(target: "mesh", "Finished chunk {} - {:.3}", chunk_index, start_instant.elapsed().as_float_seconds());
         }
     }
 }
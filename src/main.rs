@@ -1,5 +1,6 @@
 #[macro_use] extern crate glium;
 #[macro_use] extern crate lazy_static;
+#[macro_use] extern crate log;
 extern crate image;
 extern crate vecmath;
 
@@ -7,8 +8,68 @@ mod client;
 mod utils;
 mod world;
 
+use std::process;
+
 fn main() {
-    let mut app = utils::App::new("Voxel Test");
-    let voxel_test = client::VoxelTest::new(&mut app);
+    // Parse the options before opening a window so typos fail fast
+    let options = client::Options::from_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    // Start logging with the filter from the options or `RUST_LOG`
+    let log_filter = match options.log_filter.clone() {
+        Some(log_filter) => Ok(log_filter),
+        None => match std::env::var("RUST_LOG") {
+            Ok(config) => utils::LogFilter::from_config(&config).map_err(|err| format!("Invalid RUST_LOG: {}", err)),
+            Err(_) => Ok(utils::LogFilter::default())
+        }
+    };
+    let trace_path = options.trace_path.as_deref();
+    log_filter.and_then(|log_filter| utils::init_logger(log_filter, trace_path)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    if options.print_bindings {
+        print!("{}", options.input_map.to_config());
+        return;
+    }
+
+    // Compare the golden images without opening a window
+    if let Some(ref golden_dir) = options.golden_dir {
+        if let Err(err) = client::run_golden_tests(golden_dir, options.update_golden, options.golden_tolerance) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Render the frames of a camera path without opening a window
+    if let Some(dir) = options.dump_frames.clone() {
+        if let Err(err) = client::dump_frames(options, &dir) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Time the chunk pipeline without opening a window
+    if options.benchmark == Some(client::BenchmarkKind::Mesh) {
+        if let Err(err) = client::run_mesh_benchmark(&options) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let world_delegate = options.create_world_delegate().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    // Don't let vsync cap the frame times when benchmarking
+    let app = utils::App::new("Voxel Test", options.benchmark.is_none());
+    let voxel_test = client::VoxelTest::new(&app.display, world_delegate, &options);
     app.start(Box::new(voxel_test));
 }
@@ -14,6 +14,7 @@ struct AppInitData {
     events_loop: glutin::EventsLoop
 }
 
+#[derive(Default)]
 pub struct WindowState {
     pub is_focused: bool,
     pub is_closing: bool
@@ -21,25 +22,16 @@ pub struct WindowState {
 
 impl WindowState {
     fn process_event(&mut self, event: &glutin::Event) {
-        match *event {
-            glutin::Event::WindowEvent { ref event, .. } => match event {
+        if let glutin::Event::WindowEvent { ref event, .. } = *event {
+            match event {
                 glutin::WindowEvent::CloseRequested => self.is_closing = true,
                 glutin::WindowEvent::Focused(focused) => self.is_focused = *focused,
                 _ => { }
-            },
-            _ => { }
+            }
         }
     }
 }
 
-impl Default for WindowState {
-    fn default() -> WindowState {
-        WindowState {
-            is_focused: false,
-            is_closing: false
-        }
-    }
-}
 
 pub struct App {
     init_data: Option<AppInitData>,
@@ -48,13 +40,14 @@ pub struct App {
 }
 
 impl App {
-    pub fn new(title: &str) -> App {
+    /// Opens a window. Turning off `vsync` lets frames render as fast as they can.
+    pub fn new(title: &str, vsync: bool) -> App {
         // Build events loop
         let events_loop = glutin::EventsLoop::new();
 
         // Build display
         let window = glutin::WindowBuilder::new().with_title(title);
-        let context = glutin::ContextBuilder::new().with_depth_buffer(24).with_vsync(true);
+        let context = glutin::ContextBuilder::new().with_depth_buffer(24).with_vsync(vsync);
         let display = glium::Display::new(window, context, &events_loop).unwrap();
 
         App {
@@ -64,7 +57,7 @@ impl App {
         }
     }
 
-    pub fn start(mut self, mut state: Box<AppState>) {
+    pub fn start(mut self, mut state: Box<dyn AppState>) {
         // Extract the init data from the app
         let AppInitData { mut events_loop } = self.init_data.expect("Failed to get init data for app");
         self.init_data = None;
@@ -1,12 +1,68 @@
 use glium::glutin;
 use std::f32;
 use crate::utils;
+use crate::world;
 use vecmath::*;
 
+/// A part of the view when rendering it as a grid of tiles; see `CameraState::set_tile`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ProjectionTile {
+    /// How many tiles there are along each side.
+    pub count: u32,
+
+    /// The column of the tile from the left and the row from the bottom.
+    pub x: u32,
+    pub y: u32
+}
+
+/// How the camera sees and moves.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraSettings {
+    /// The vertical field of view in degrees, and the one zoomed in to.
+    pub fov: f32,
+    pub zoom_fov: f32,
+
+    /// The distance to the near and far clipping planes.
+    pub near: f32,
+    pub far: f32,
+
+    /// How far the camera turns per pixel the mouse moves, in radians.
+    pub sensitivity: f32,
+
+    /// How quickly the camera gets up to speed and slows back down when flying. The difference
+    /// from the target speed shrinks by this rate per second, so higher is snappier.
+    pub acceleration: f32,
+    pub damping: f32
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            fov: 45.,
+            zoom_fov: 15.,
+            near: 0.1,
+            far: 1024.,
+            sensitivity: 0.0025,
+            acceleration: 10.,
+            damping: 6.
+        }
+    }
+}
+
 pub struct CameraState {
+    settings: CameraSettings,
     aspect_ratio: f32,
+
+    /// The part of the view `get_perspective` is zoomed in on.
+    tile: Option<ProjectionTile>,
+
     position: Vector3<f32>,
     direction: Vector3<f32>,
+    velocity: Vector3<f32>,
+
+    /// The field of view in degrees, which eases towards the zoomed in one while zooming.
+    fov: f32,
+    zooming: bool,
 
     moving_up: bool,
     moving_left: bool,
@@ -16,15 +72,36 @@ pub struct CameraState {
     moving_backward: bool,
     moving_fast: bool,
 
+    /// How far an analog stick is moving the camera to the right and forward, from -1 to 1.
+    analog_movement: [f32; 2],
+
+    /// If the movement keys move the camera; this is off when something else, like the player,
+    /// controls the position.
+    fly: bool,
+
     lock_cursor: bool
 }
 
 impl CameraState {
-    pub fn new(position: Vector3<f32>, direction: Vector3<f32>) -> CameraState {
+    /// How fast the camera flies, and how fast it flies while sprinting.
+    const SPEED: f32 = 8.5;
+    const SPRINT_SPEED: f32 = 20.;
+
+    /// How quickly the field of view changes when zooming in and out; see
+    /// `CameraSettings::acceleration`.
+    const ZOOM_RATE: f32 = 12.;
+
+    pub fn new(position: Vector3<f32>, direction: Vector3<f32>, settings: CameraSettings) -> CameraState {
         CameraState {
+            settings,
             aspect_ratio: 1024.0 / 768.0,
+            tile: None,
             position,
             direction,
+            velocity: [0., 0., 0.],
+
+            fov: settings.fov,
+            zooming: false,
 
             moving_up: false,
             moving_left: false,
@@ -33,6 +110,9 @@ impl CameraState {
             moving_forward: false,
             moving_backward: false,
             moving_fast: false,  // TODO: This
+            analog_movement: [0., 0.],
+
+            fly: true,
 
             lock_cursor: false
         }
@@ -42,12 +122,65 @@ impl CameraState {
         &self.position
     }
 
-    #[allow(dead_code)]  // TODO: Remove
+    /// Returns the position in world space, where Z is up instead of Y.
+    pub fn get_world_position(&self) -> Vector3<f32> {
+        [self.position[0], self.position[2], self.position[1]]
+    }
+
+    /// Returns the direction in world space, where Z is up instead of Y.
+    pub fn get_world_direction(&self) -> Vector3<f32> {
+        [self.direction[0], self.direction[2], self.direction[1]]
+    }
+
+    /// Sets the aspect ratio from the size of the view, ignoring empty views like minimized
+    /// windows.
+    pub fn set_view_size(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 { return; }
+        self.aspect_ratio = width as f32 / height as f32;
+    }
+
+    pub fn is_cursor_locked(&self) -> bool {
+        self.lock_cursor
+    }
+
+    pub fn set_fly(&mut self, fly: bool) {
+        self.fly = fly;
+    }
+
+    /// Converts the held movement keys in to input for a walking player. Walking ignores the pitch
+    /// of the camera, so looking down doesn't slow the player down.
+    pub fn get_player_input(&self) -> world::PlayerInput {
+        if !self.lock_cursor { return world::PlayerInput::default(); }
+
+        // Get the forward and side directions along the ground; there are none when looking
+        // straight up or down
+        let ground_direction = [self.direction[0], 0., self.direction[2]];
+        if vec3_len(ground_direction) < 0.0001 { return world::PlayerInput { jump: self.moving_up, ..Default::default() }; }
+        let forward = vec3_normalized(ground_direction);
+        let side = vec3_cross(forward, [0., 1., 0.]);
+
+        // Add up the keys and the analog movement
+        let axis = |positive: bool, negative: bool, analog: f32| {
+            (if positive { 1. } else { 0. } - if negative { 1. } else { 0. } + analog).clamp(-1., 1.)
+        };
+        let forward_amount = axis(self.moving_forward, self.moving_backward, self.analog_movement[1]);
+        let side_amount = axis(self.moving_right, self.moving_left, self.analog_movement[0]);
+        let walk = [
+            forward[0] * forward_amount + side[0] * side_amount,
+            forward[2] * forward_amount + side[2] * side_amount  // Flip Y with Z
+        ];
+
+        world::PlayerInput {
+            walk,
+            jump: self.moving_up,
+            sprint: self.moving_fast
+        }
+    }
+
     pub fn set_position(&mut self, pos: Vector3<f32>) {
         self.position = pos;
     }
 
-    #[allow(dead_code)]  // TODO: Remove
     pub fn set_direction(&mut self, dir: Vector3<f32>) {
         self.direction = dir;
     }
@@ -67,10 +200,50 @@ impl CameraState {
         ];
     }
 
+    /// Turns the camera right by `yaw` and up by `pitch` radians, stopping just short of looking
+    /// straight up or down.
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        let (current_pitch, current_yaw) = self.get_pitch_yaw();
+        let pitch_boundary = f32::consts::FRAC_PI_2 - 0.0001;
+        self.set_pitch_yaw((current_pitch + pitch).clamp(-pitch_boundary, pitch_boundary), current_yaw + yaw);
+    }
+
+    /// Sets how far an analog stick is moving the camera to the right and forward, from -1 to 1.
+    /// This is added to the movement keys.
+    pub fn set_analog_movement(&mut self, movement: [f32; 2]) {
+        self.analog_movement = movement;
+    }
+
+    /// Makes `get_perspective` only cover one tile of the view, so a large image can be rendered
+    /// a tile at a time.
+    pub fn set_tile(&mut self, tile: Option<ProjectionTile>) {
+        self.tile = tile;
+    }
+
     pub fn get_perspective(&self) -> Matrix4<f32> {
-        let fov = f32::consts::FRAC_PI_2 / 2.0;
-        let zfar = 1024.0;
-        let znear = 0.1;
+        let perspective = self.get_full_perspective();
+        let tile = match self.tile {
+            Some(tile) => tile,
+            None => return perspective
+        };
+
+        // Scale the view up and move the tile to the center; this is applied in clip space, so the
+        // offset is scaled by W
+        let count = tile.count as f32;
+        let tile_matrix = [
+            [count, 0., 0., 0.],
+            [0., count, 0., 0.],
+            [0., 0., 1., 0.],
+            [count - 1. - 2. * tile.x as f32, count - 1. - 2. * tile.y as f32, 0., 1.]
+        ];
+        col_mat4_mul(tile_matrix, perspective)
+    }
+
+    /// Returns the perspective of the whole view, ignoring the tile.
+    pub fn get_full_perspective(&self) -> Matrix4<f32> {
+        let fov = self.fov.to_radians();
+        let zfar = self.settings.far;
+        let znear = self.settings.near;
 
         let f = 1.0 / (fov / 2.0).tan();
 
@@ -117,93 +290,91 @@ impl CameraState {
         window.grab_cursor(self.lock_cursor).unwrap();
         window.hide_cursor(self.lock_cursor);
 
+        // Match the aspect ratio to the window; reading the framebuffer gives the size in pixels
+        // whatever the DPI, and catches the initial size as well as resizes
+        let (width, height) = app.display.get_framebuffer_dimensions();
+        self.set_view_size(width, height);
+
+        // Ease the field of view towards the zoomed in or out one
+        let target_fov = if self.zooming { self.settings.zoom_fov } else { self.settings.fov };
+        self.fov += (target_fov - self.fov) * (1. - (-CameraState::ZOOM_RATE * dt).exp());
+
         // Move the camera
-        if self.lock_cursor {
-            // Calculate move speed
-            let move_speed = if self.moving_fast { 20. } else { 8.5 } * dt;
+        if self.fly {
+            // Find the velocity the held keys are asking for; there's none while the cursor is
+            // unlocked, so the camera coasts to a stop
+            let target_velocity = if self.lock_cursor { self.get_target_velocity() } else { [0., 0., 0.] };
+
+            // Speed up towards the target velocity, or slow down if there's no input. This eases
+            // by the same amount over a second whatever the frame rate.
+            let rate = if target_velocity == [0., 0., 0.] { self.settings.damping } else { self.settings.acceleration };
+            let blend = 1. - (-rate * dt).exp();
+            self.velocity = vec3_add(self.velocity, vec3_scale(vec3_sub(target_velocity, self.velocity), blend));
+
+            // Stop drifting once the camera has almost stopped
+            if vec3_len(self.velocity) < 0.01 && target_velocity == [0., 0., 0.] {
+                self.velocity = [0., 0., 0.];
+            }
 
-            // Normalize the direction
-            let forward = vec3_normalized(self.direction);
+            self.position = vec3_add(self.position, vec3_scale(self.velocity, dt));
+        } else {
+            // The player moves the camera, so don't keep flying when switching back
+            self.velocity = [0., 0., 0.];
+        }
+    }
 
-            // Get up direction
-            let up = [0.0, 1.0, 0.0];
+    /// Returns the velocity the held movement keys and analog stick are asking for.
+    fn get_target_velocity(&self) -> Vector3<f32> {
+        // Normalize the direction
+        let forward = vec3_normalized(self.direction);
 
-            // Get cross product
-            let side = vec3_normalized(vec3_cross(forward, up));
+        // Get up direction
+        let up = [0.0, 1.0, 0.0];
 
-            // Get the up direction
-            let up = vec3_cross(side, forward);
+        // Get cross product
+        let side = vec3_normalized(vec3_cross(forward, up));
 
-            if self.moving_up {
-                self.position[0] += up[0] * move_speed;
-                self.position[1] += up[1] * move_speed;
-                self.position[2] += up[2] * move_speed;
-            }
+        // Get the up direction
+        let up = vec3_cross(side, forward);
 
-            if self.moving_left {
-                self.position[0] -= side[0] * move_speed;
-                self.position[1] -= side[1] * move_speed;
-                self.position[2] -= side[2] * move_speed;
-            }
+        // Add up the keys and the analog movement
+        let axis = |positive: bool, negative: bool| if positive { 1. } else { 0. } - if negative { 1. } else { 0. };
+        let mut movement = [0., 0., 0.];
+        movement = vec3_add(movement, vec3_scale(up, axis(self.moving_up, self.moving_down)));
+        movement = vec3_add(movement, vec3_scale(side, axis(self.moving_right, self.moving_left) + self.analog_movement[0]));
+        movement = vec3_add(movement, vec3_scale(forward, axis(self.moving_forward, self.moving_backward) + self.analog_movement[1]));
 
-            if self.moving_down {
-                self.position[0] -= up[0] * move_speed;
-                self.position[1] -= up[1] * move_speed;
-                self.position[2] -= up[2] * move_speed;
-            }
+        // Don't go faster diagonally, but keep the stick's partial speeds
+        let length = vec3_len(movement);
+        if length > 1. {
+            movement = vec3_scale(movement, 1. / length);
+        }
 
-            if self.moving_right {
-                self.position[0] += side[0] * move_speed;
-                self.position[1] += side[1] * move_speed;
-                self.position[2] += side[2] * move_speed;
-            }
+        let speed = if self.moving_fast { CameraState::SPRINT_SPEED } else { CameraState::SPEED };
+        vec3_scale(movement, speed)
+    }
 
-            if self.moving_forward {
-                self.position[0] += forward[0] * move_speed;
-                self.position[1] += forward[1] * move_speed;
-                self.position[2] += forward[2] * move_speed;
-            }
+    /// Starts or stops moving when a movement action is pressed or released.
+    pub fn process_action(&mut self, action: utils::Action, pressed: bool) {
+        match action {
+            utils::Action::MoveUp => self.moving_up = pressed,
+            utils::Action::MoveDown => self.moving_down = pressed,
+            utils::Action::MoveLeft => self.moving_left = pressed,
+            utils::Action::MoveRight => self.moving_right = pressed,
+            utils::Action::MoveForward => self.moving_forward = pressed,
+            utils::Action::MoveBackward => self.moving_backward = pressed,
+            utils::Action::Sprint => self.moving_fast = pressed,
+            utils::Action::Zoom => self.zooming = pressed,
 
-            if self.moving_backward {
-                self.position[0] -= forward[0] * move_speed;
-                self.position[1] -= forward[1] * move_speed;
-                self.position[2] -= forward[2] * move_speed;
-            }
+            utils::Action::UnlockCursor if pressed => self.lock_cursor = false,
+
+            _ => { }
         }
     }
 
     pub fn process_input(&mut self, event: &glutin::Event) {
         match *event {
             glutin::Event::WindowEvent { ref event, .. } => match *event {
-                glutin::WindowEvent::Resized(size) => {
-                    // Update aspect ratio
-                    self.aspect_ratio = (size.width / size.height) as f32
-                },
-
-                glutin::WindowEvent::KeyboardInput { ref input, .. } => {
-                    // Get key state
-                    let pressed = input.state == glutin::ElementState::Pressed;
-                    let key = match input.virtual_keycode {
-                        Some(key) => key,
-                        None => return,
-                    };
-
-                    // Move camera
-                    match key {
-                        glutin::VirtualKeyCode::E => self.moving_up = pressed,
-                        glutin::VirtualKeyCode::Q => self.moving_down = pressed,
-                        glutin::VirtualKeyCode::A => self.moving_left = pressed,
-                        glutin::VirtualKeyCode::D => self.moving_right = pressed,
-                        glutin::VirtualKeyCode::W => self.moving_forward = pressed,
-                        glutin::VirtualKeyCode::S => self.moving_backward = pressed,
-                        glutin::VirtualKeyCode::LShift | glutin::VirtualKeyCode::RShift => self.moving_fast = pressed,
-
-                        glutin::VirtualKeyCode::Escape if pressed => self.lock_cursor = false,
-
-                        _ => { },
-                    };
-                },
-
                 glutin::WindowEvent::Focused(focused) => {
                     // Change the locked state of the cursor
                     self.lock_cursor = focused
@@ -214,33 +385,14 @@ impl CameraState {
                 _ => { }
             },
 
-            glutin::Event::DeviceEvent { ref event, .. } => match *event {
-                glutin::DeviceEvent::MouseMotion { delta } => {
-                    if !self.lock_cursor { return }
-
-                    let sensitivity = 0.0025;
-
-                    // Calculate pitch and yaw
-                    let (mut pitch, mut yaw) = self.get_pitch_yaw();
+            glutin::Event::DeviceEvent { event: glutin::DeviceEvent::MouseMotion { delta }, .. } => {
+                if !self.lock_cursor { return }
 
-                    // Add the new movement
-                    yaw += delta.0 as f32 * sensitivity;
-                    pitch -= delta.1 as f32 * sensitivity;
+                // Turn slower while zoomed in so the view moves the same on screen
+                let sensitivity = self.settings.sensitivity * self.fov / self.settings.fov;
 
-                    // Cap the pitch
-                    let pitch_boundary= f32::consts::FRAC_PI_2 - 0.0001;
-                    if pitch > pitch_boundary {
-                        pitch = pitch_boundary;
-                    }
-                    if pitch < -pitch_boundary {
-                        pitch = -pitch_boundary;
-                    }
-
-                    // Set new direction
-                    self.set_pitch_yaw(pitch, yaw);
-                },
-
-                _ => { }
+                // Add the new movement
+                self.rotate(delta.0 as f32 * sensitivity, -delta.1 as f32 * sensitivity);
             },
 
             _ => { }
@@ -0,0 +1,251 @@
+use std::fs;
+use vecmath::*;
+
+/// Where the camera is at a point in a path, in world space (Z is up).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraKeyframe {
+    /// Seconds from the start of the path.
+    pub time: f32,
+    pub position: Vector3<f32>,
+    pub direction: Vector3<f32>
+}
+
+/// A path for the camera to fly along, made of keyframes in order of time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>
+}
+
+impl CameraPath {
+    pub fn new() -> CameraPath {
+        CameraPath { keyframes: Vec::new() }
+    }
+
+    pub fn load(path: &str) -> Result<CameraPath, String> {
+        let config = fs::read_to_string(path).map_err(|err| format!("Failed to read camera path `{}`: {}", path, err))?;
+        CameraPath::from_config(&config).map_err(|err| format!("Invalid camera path `{}`: {}", path, err))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_config()).map_err(|err| format!("Failed to save camera path `{}`: {}", path, err))
+    }
+
+    /// Parses a path with a keyframe on each line in the form `time x y z dx dy dz`. Lines
+    /// starting with `#` are ignored.
+    pub fn from_config(config: &str) -> Result<CameraPath, String> {
+        let mut path = CameraPath::new();
+        for (number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let values = line.split_whitespace()
+                .map(|value| value.parse::<f32>().ok().filter(|value| value.is_finite()).ok_or_else(|| error(format!("Invalid number `{}`", value))))
+                .collect::<Result<Vec<f32>, String>>()?;
+            if values.len() != 7 {
+                return Err(error(format!("Expected `time x y z dx dy dz` but found `{}`", line)));
+            }
+
+            path.add_keyframe(CameraKeyframe {
+                time: values[0],
+                position: [values[1], values[2], values[3]],
+                direction: [values[4], values[5], values[6]]
+            }).map_err(error)?;
+        }
+
+        if path.keyframes.is_empty() {
+            return Err("There are no keyframes".to_string());
+        }
+
+        Ok(path)
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut config = String::from("# Each line is a keyframe: `time x y z dx dy dz` in seconds and world space (Z is up)\n");
+        for keyframe in self.keyframes.iter() {
+            let (p, d) = (keyframe.position, keyframe.direction);
+            config.push_str(&format!(
+                "{:.3} {:.3} {:.3} {:.3} {:.4} {:.4} {:.4}\n",
+                keyframe.time, p[0], p[1], p[2], d[0], d[1], d[2]
+            ));
+        }
+        config
+    }
+
+    /// Adds a keyframe to the end of the path. Fails if it's before the last keyframe, doesn't
+    /// have a direction or has a value that isn't finite.
+    pub fn add_keyframe(&mut self, keyframe: CameraKeyframe) -> Result<(), String> {
+        let mut values = keyframe.position.iter().chain(keyframe.direction.iter());
+        if !keyframe.time.is_finite() || values.any(|value| !value.is_finite()) {
+            return Err(format!("Keyframe at {}s has a value that isn't finite", keyframe.time));
+        }
+        if let Some(last) = self.keyframes.last() {
+            if keyframe.time <= last.time {
+                return Err(format!("Keyframe at {}s must come after the one at {}s", keyframe.time, last.time));
+            }
+        }
+        if vec3_len(keyframe.direction) < 0.0001 {
+            return Err(format!("Keyframe at {}s doesn't have a direction", keyframe.time));
+        }
+
+        self.keyframes.push(keyframe);
+        Ok(())
+    }
+
+    /// Returns the time of the last keyframe.
+    pub fn get_duration(&self) -> f32 {
+        self.keyframes.last().map(|keyframe| keyframe.time).unwrap_or(0.)
+    }
+
+    /// Returns the position and direction at a time, which is clamped to the path. Positions
+    /// follow a Catmull-Rom spline through the keyframes so the camera doesn't change direction
+    /// sharply at them, and directions are slerped so the camera turns at a steady rate.
+    pub fn sample(&self, time: f32) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let first = self.keyframes.first()?;
+        if self.keyframes.len() == 1 || time <= first.time {
+            return Some((first.position, vec3_normalized(first.direction)));
+        }
+
+        // Find the keyframes on either side of the time
+        let next = match self.keyframes.iter().position(|keyframe| keyframe.time > time) {
+            Some(next) => next,
+            None => {
+                let last = self.keyframes[self.keyframes.len() - 1];
+                return Some((last.position, vec3_normalized(last.direction)));
+            }
+        };
+        let (start, end) = (self.keyframes[next - 1], self.keyframes[next]);
+        let t = (time - start.time) / (end.time - start.time);
+
+        // Repeat the end keyframes where there isn't one beyond them to steer the spline
+        let before = self.keyframes[next.saturating_sub(2)].position;
+        let after = self.keyframes[(next + 1).min(self.keyframes.len() - 1)].position;
+
+        Some((
+            catmull_rom(before, start.position, end.position, after, t),
+            slerp(start.direction, end.direction, t)
+        ))
+    }
+}
+
+/// Interpolates between `p1` and `p2` on a uniform Catmull-Rom spline, where `p0` and `p3` are
+/// the points before and after them.
+fn catmull_rom(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>, p3: Vector3<f32>, t: f32) -> Vector3<f32> {
+    let (t2, t3) = (t * t, t * t * t);
+    let mut point = [0.; 3];
+    for i in 0..3 {
+        point[i] = 0.5 * (
+            2. * p1[i]
+            + (p2[i] - p0[i]) * t
+            + (2. * p0[i] - 5. * p1[i] + 4. * p2[i] - p3[i]) * t2
+            + (3. * p1[i] - p0[i] - 3. * p2[i] + p3[i]) * t3
+        );
+    }
+    point
+}
+
+/// Rotates from direction `a` to `b` at a constant rate, returning a normalized direction.
+fn slerp(a: Vector3<f32>, b: Vector3<f32>, t: f32) -> Vector3<f32> {
+    let (a, b) = (vec3_normalized(a), vec3_normalized(b));
+    let cos_angle = vec3_dot(a, b).clamp(-1., 1.);
+
+    // Fall back to a straight line when the directions are too close for the angle to be accurate
+    // or opposite, where there's no single way to turn
+    let angle = cos_angle.acos();
+    if angle.sin().abs() < 0.001 {
+        let lerped = vec3_add(vec3_scale(a, 1. - t), vec3_scale(b, t));
+        return if vec3_len(lerped) < 0.0001 { a } else { vec3_normalized(lerped) };
+    }
+
+    let scale_a = ((1. - t) * angle).sin() / angle.sin();
+    let scale_b = (t * angle).sin() / angle.sin();
+    vec3_add(vec3_scale(a, scale_a), vec3_scale(b, scale_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32) -> CameraKeyframe {
+        CameraKeyframe { time, position: [0., 0., 0.], direction: [1., 0., 0.] }
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(vec3_len(vec3_sub(actual, expected)) < 0.0001, "{:?} != {:?}", actual, expected);
+    }
+
+    /// A path that turns a corner, with directions that aren't normalized.
+    fn corner_path() -> CameraPath {
+        let mut path = CameraPath::new();
+        path.add_keyframe(CameraKeyframe { time: 0., position: [0., 0., 100.], direction: [2., 0., 0.] }).unwrap();
+        path.add_keyframe(CameraKeyframe { time: 1., position: [10., 0., 100.], direction: [0., 3., 0.] }).unwrap();
+        path.add_keyframe(CameraKeyframe { time: 3., position: [10., 20., 110.], direction: [0., 1., -1.] }).unwrap();
+        path.add_keyframe(CameraKeyframe { time: 4., position: [0., 20., 110.], direction: [-0.5, 0., 0.] }).unwrap();
+        path
+    }
+
+    #[test]
+    fn rejects_values_that_are_not_finite() {
+        let mut path = CameraPath::new();
+        assert!(path.add_keyframe(keyframe(f32::NAN)).is_err());
+        assert!(path.add_keyframe(CameraKeyframe { position: [0., f32::INFINITY, 0.], ..keyframe(0.) }).is_err());
+        assert!(path.add_keyframe(CameraKeyframe { direction: [f32::NAN, 0., 1.], ..keyframe(0.) }).is_err());
+        assert_eq!(path, CameraPath::new());
+
+        assert!(CameraPath::from_config("0 0 0 0 1 0 0\n1 NaN 0 0 1 0 0").is_err());
+        assert!(CameraPath::from_config("0 0 0 0 1 0 0\ninf 0 0 0 1 0 0").is_err());
+        assert!(CameraPath::from_config("0 0 0 0 1 0 0\n1 0 0 0 1 0 0").is_ok());
+    }
+
+    #[test]
+    fn rejects_keyframes_out_of_order() {
+        let mut path = CameraPath::new();
+        path.add_keyframe(keyframe(1.)).unwrap();
+        assert!(path.add_keyframe(keyframe(1.)).is_err());
+        assert!(path.add_keyframe(keyframe(0.5)).is_err());
+        assert_eq!(path.get_duration(), 1.);
+    }
+
+    #[test]
+    fn sample_passes_through_keyframes() {
+        let path = corner_path();
+        for keyframe in path.keyframes.iter() {
+            let (position, direction) = path.sample(keyframe.time).unwrap();
+            assert_close(position, keyframe.position);
+            assert_close(direction, vec3_normalized(keyframe.direction));
+        }
+    }
+
+    #[test]
+    fn sample_slerps_directions() {
+        let path = corner_path();
+
+        // Halfway between two directions at right angles is 45 degrees to both
+        let half = 0.5f32.sqrt();
+        assert_close(path.sample(0.5).unwrap().1, [half, half, 0.]);
+
+        // A quarter of the way is a quarter of the angle, not a quarter of the distance
+        let quarter = std::f32::consts::FRAC_PI_8;
+        assert_close(path.sample(0.25).unwrap().1, [quarter.cos(), quarter.sin(), 0.]);
+
+        for step in 0..=80 {
+            let direction = path.sample(step as f32 * 0.05).unwrap().1;
+            assert!((vec3_len(direction) - 1.).abs() < 0.0001, "{:?} at {}", direction, step);
+        }
+    }
+
+    #[test]
+    fn sample_clamps_to_the_path() {
+        let path = corner_path();
+        assert_eq!(path.sample(-1.), path.sample(0.));
+        assert_eq!(path.sample(f32::NEG_INFINITY), path.sample(0.));
+        assert_eq!(path.sample(4.5), path.sample(4.));
+        assert_eq!(path.sample(1000.), path.sample(4.));
+
+        // A single keyframe is the whole path
+        let mut path = CameraPath::new();
+        assert_eq!(path.sample(0.), None);
+        path.add_keyframe(CameraKeyframe { time: 2., ..corner_path().keyframes[1] }).unwrap();
+        assert_eq!(path.sample(5.), Some(([10., 0., 100.], [0., 1., 0.])));
+    }
+}
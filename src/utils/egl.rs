@@ -0,0 +1,123 @@
+use glium::SwapBuffersError;
+use libloading::Library;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+type EglDisplay = *mut c_void;
+type EglContextHandle = *mut c_void;
+type EglBoolean = u32;
+type EglInt = i32;
+type EglEnum = u32;
+
+const EGL_NONE: EglInt = 0x3038;
+const EGL_OPENGL_API: EglEnum = 0x30A2;
+const EGL_PLATFORM_SURFACELESS_MESA: EglEnum = 0x31DD;
+
+/// The EGL functions used, loaded from libEGL at runtime so there's no link time dependency on it.
+struct EglFunctions {
+    get_proc_address: unsafe extern "C" fn(*const c_char) -> *const c_void,
+    initialize: unsafe extern "C" fn(EglDisplay, *mut EglInt, *mut EglInt) -> EglBoolean,
+    terminate: unsafe extern "C" fn(EglDisplay) -> EglBoolean,
+    bind_api: unsafe extern "C" fn(EglEnum) -> EglBoolean,
+    create_context: unsafe extern "C" fn(EglDisplay, *mut c_void, EglContextHandle, *const EglInt) -> EglContextHandle,
+    destroy_context: unsafe extern "C" fn(EglDisplay, EglContextHandle) -> EglBoolean,
+    make_current: unsafe extern "C" fn(EglDisplay, *mut c_void, *mut c_void, EglContextHandle) -> EglBoolean,
+    get_current_context: unsafe extern "C" fn() -> EglContextHandle
+}
+
+/// An OpenGL context from Mesa's surfaceless EGL platform. Unlike glutin's contexts, this doesn't
+/// need an X11 or Wayland display, so it works on build machines with only Mesa's software
+/// rasterizer installed. There's no default framebuffer, so everything has to be drawn in to
+/// textures.
+pub struct EglContext {
+    functions: EglFunctions,
+    display: EglDisplay,
+    context: EglContextHandle,
+
+    /// Declared last so the library is unloaded after the context is destroyed.
+    _library: Library
+}
+
+impl EglContext {
+    pub fn new() -> Result<EglContext, String> {
+        let library = Library::new("libEGL.so.1").map_err(|err| format!("Failed to load libEGL: {}", err))?;
+
+        unsafe {
+            // Load the functions
+            macro_rules! load {
+                ($name:expr) => {
+                    *library.get($name).map_err(|err| format!("Failed to load {}: {}", String::from_utf8_lossy(&$name[..$name.len() - 1]), err))?
+                };
+            }
+            let functions = EglFunctions {
+                get_proc_address: load!(b"eglGetProcAddress\0"),
+                initialize: load!(b"eglInitialize\0"),
+                terminate: load!(b"eglTerminate\0"),
+                bind_api: load!(b"eglBindAPI\0"),
+                create_context: load!(b"eglCreateContext\0"),
+                destroy_context: load!(b"eglDestroyContext\0"),
+                make_current: load!(b"eglMakeCurrent\0"),
+                get_current_context: load!(b"eglGetCurrentContext\0")
+            };
+
+            // Get the surfaceless display; this is an extension, so it isn't exported by libEGL
+            let get_platform_display = (functions.get_proc_address)(b"eglGetPlatformDisplayEXT\0".as_ptr() as *const c_char);
+            if get_platform_display.is_null() {
+                return Err("EGL doesn't support platform displays".to_string());
+            }
+            let get_platform_display: unsafe extern "C" fn(EglEnum, *mut c_void, *const EglInt) -> EglDisplay = std::mem::transmute(get_platform_display);
+            let display = get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null());
+            if display.is_null() || (functions.initialize)(display, ptr::null_mut(), ptr::null_mut()) == 0 {
+                return Err("Failed to initialize a surfaceless EGL display".to_string());
+            }
+
+            // Create a desktop OpenGL context without a config, since there's no surface for it
+            let attributes = [EGL_NONE];
+            let context = if (functions.bind_api)(EGL_OPENGL_API) != 0 {
+                (functions.create_context)(display, ptr::null_mut(), ptr::null_mut(), attributes.as_ptr())
+            } else {
+                ptr::null_mut()
+            };
+            if context.is_null() {
+                (functions.terminate)(display);
+                return Err("Failed to create a surfaceless EGL context".to_string());
+            }
+
+            Ok(EglContext { functions, display, context, _library: library })
+        }
+    }
+}
+
+unsafe impl glium::backend::Backend for EglContext {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        let symbol = CString::new(symbol).unwrap();
+        (self.functions.get_proc_address)(symbol.as_ptr())
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    fn is_current(&self) -> bool {
+        unsafe { (self.functions.get_current_context)() == self.context }
+    }
+
+    unsafe fn make_current(&self) {
+        (self.functions.make_current)(self.display, ptr::null_mut(), ptr::null_mut(), self.context);
+    }
+}
+
+impl Drop for EglContext {
+    fn drop(&mut self) {
+        unsafe {
+            (self.functions.make_current)(self.display, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+            (self.functions.destroy_context)(self.display, self.context);
+            (self.functions.terminate)(self.display);
+        }
+    }
+}
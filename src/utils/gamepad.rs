@@ -0,0 +1,243 @@
+use super::GamepadButton;
+use super::GamepadState;
+use std::fs::File;
+use std::io::Read;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// A raw event from the joystick device.
+enum JoystickEvent {
+    Button(u8, bool),
+    Axis(u8, f32),
+
+    /// The device was unplugged or stopped responding.
+    Disconnected
+}
+
+/// Reads a gamepad through the Linux joystick API (`/dev/input/js*`). The device is read on its
+/// own thread, so plugging the gamepad in later or unplugging it doesn't stall the game. On other
+/// platforms the device never opens and the gamepad stays centered.
+pub struct Gamepad {
+    events: mpsc::Receiver<JoystickEvent>,
+    state: GamepadState,
+
+    /// Which buttons are held, including the triggers and d-pad, which are axes on the device.
+    held: Vec<GamepadButton>
+}
+
+impl Gamepad {
+    /// How far a trigger has to be pulled to count as pressed.
+    const TRIGGER_THRESHOLD: f32 = 0.5;
+
+    /// How long to wait before trying to open the device again.
+    const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+    pub fn new(device: &str) -> Gamepad {
+        let (sender, events) = mpsc::channel();
+        let device = device.to_string();
+        thread::spawn(move || Gamepad::read_device(&device, &sender));
+
+        Gamepad {
+            events,
+            state: GamepadState::default(),
+            held: Vec::new()
+        }
+    }
+
+    pub fn get_state(&self) -> &GamepadState {
+        &self.state
+    }
+
+    /// Applies the events read since the last poll and returns the buttons that were pressed or
+    /// released.
+    pub fn poll(&mut self) -> Vec<(GamepadButton, bool)> {
+        let mut changes = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                JoystickEvent::Button(number, pressed) => {
+                    if let Some(button) = Gamepad::button_from_number(number) {
+                        self.set_held(button, pressed, &mut changes);
+                    }
+                },
+                JoystickEvent::Axis(number, value) => self.apply_axis(number, value, &mut changes),
+                JoystickEvent::Disconnected => self.release_all(&mut changes)
+            }
+        }
+        changes
+    }
+
+    /// Maps the axes of the standard Xbox layout used by the Linux `xpad` driver, which most
+    /// controllers follow.
+    fn apply_axis(&mut self, number: u8, value: f32, changes: &mut Vec<(GamepadButton, bool)>) {
+        // The device's Y axes point down and its triggers go from -1 to 1
+        let trigger = |value: f32| (value + 1.) / 2.;
+        match number {
+            0 => self.state.left_stick[0] = value,
+            1 => self.state.left_stick[1] = -value,
+            2 => {
+                self.state.left_trigger = trigger(value);
+                self.set_held(GamepadButton::LeftTrigger, self.state.left_trigger > Gamepad::TRIGGER_THRESHOLD, changes);
+            },
+            3 => self.state.right_stick[0] = value,
+            4 => self.state.right_stick[1] = -value,
+            5 => {
+                self.state.right_trigger = trigger(value);
+                self.set_held(GamepadButton::RightTrigger, self.state.right_trigger > Gamepad::TRIGGER_THRESHOLD, changes);
+            },
+            6 => {
+                self.set_held(GamepadButton::DPadLeft, value < -0.5, changes);
+                self.set_held(GamepadButton::DPadRight, value > 0.5, changes);
+            },
+            7 => {
+                self.set_held(GamepadButton::DPadUp, value < -0.5, changes);
+                self.set_held(GamepadButton::DPadDown, value > 0.5, changes);
+            },
+            _ => { }
+        }
+    }
+
+    /// Maps the buttons of the standard Xbox layout.
+    fn button_from_number(number: u8) -> Option<GamepadButton> {
+        match number {
+            0 => Some(GamepadButton::South),
+            1 => Some(GamepadButton::East),
+            2 => Some(GamepadButton::West),
+            3 => Some(GamepadButton::North),
+            4 => Some(GamepadButton::LeftBumper),
+            5 => Some(GamepadButton::RightBumper),
+            6 => Some(GamepadButton::Select),
+            7 => Some(GamepadButton::Start),
+            9 => Some(GamepadButton::LeftStick),
+            10 => Some(GamepadButton::RightStick),
+            _ => None
+        }
+    }
+
+    /// Records a button as held or not, adding it to the changes if it changed.
+    fn set_held(&mut self, button: GamepadButton, held: bool, changes: &mut Vec<(GamepadButton, bool)>) {
+        let was_held = self.held.contains(&button);
+        if held == was_held { return; }
+
+        if held {
+            self.held.push(button);
+        } else {
+            self.held.retain(|&held_button| held_button != button);
+        }
+        changes.push((button, held));
+    }
+
+    /// Centers the sticks and releases every held button, so nothing stays stuck when the
+    /// gamepad is unplugged.
+    fn release_all(&mut self, changes: &mut Vec<(GamepadButton, bool)>) {
+        self.state = GamepadState::default();
+        for button in self.held.drain(..) {
+            changes.push((button, false));
+        }
+    }
+
+    /// Reads events from the device until the game closes, reopening it whenever it's unplugged.
+    fn read_device(device: &str, sender: &mpsc::Sender<JoystickEvent>) {
+        loop {
+            if let Ok(mut file) = File::open(device) {
+                info!("Opened gamepad {}", device);
+
+                // Each event is a u32 timestamp, an i16 value, a u8 type and a u8 number; see
+                // https://www.kernel.org/doc/Documentation/input/joystick-api.txt
+                let mut event = [0u8; 8];
+                while file.read_exact(&mut event).is_ok() {
+                    let value = i16::from_ne_bytes([event[4], event[5]]);
+                    let number = event[7];
+
+                    // Ignore the flag marking the events sent with the initial state
+                    let event = match event[6] & !0x80 {
+                        0x01 => JoystickEvent::Button(number, value != 0),
+                        0x02 => JoystickEvent::Axis(number, f32::from(value) / f32::from(i16::MAX)),
+                        _ => continue
+                    };
+
+                    // Stop once the game has closed
+                    if sender.send(event).is_err() { return; }
+                }
+
+                warn!("Lost gamepad {}", device);
+                if sender.send(JoystickEvent::Disconnected).is_err() { return; }
+            }
+
+            thread::sleep(Gamepad::RETRY_DELAY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{gamepad_motion, StickSettings};
+
+    /// A gamepad without a device behind it.
+    fn unplugged() -> Gamepad {
+        connected().0
+    }
+
+    /// A gamepad with a sender standing in for the device thread.
+    fn connected() -> (Gamepad, mpsc::Sender<JoystickEvent>) {
+        let (sender, events) = mpsc::channel();
+        let gamepad = Gamepad {
+            events,
+            state: GamepadState::default(),
+            held: Vec::new()
+        };
+        (gamepad, sender)
+    }
+
+    #[test]
+    fn inverts_y_axes() {
+        let mut gamepad = unplugged();
+        let mut changes = Vec::new();
+
+        // Pushing the sticks up reads as negative on the device
+        gamepad.apply_axis(1, -1., &mut changes);
+        gamepad.apply_axis(4, 0.5, &mut changes);
+        assert_eq!(gamepad.get_state().left_stick, [0., 1.]);
+        assert_eq!(gamepad.get_state().right_stick, [0., -0.5]);
+
+        // So pushing up moves forward
+        let motion = gamepad_motion(gamepad.get_state(), &StickSettings::default(), 1.);
+        assert!(motion.movement[1] > 0.);
+        assert!(motion.look[1] < 0.);
+    }
+
+    #[test]
+    fn triggers_press_buttons() {
+        let mut gamepad = unplugged();
+        let mut changes = Vec::new();
+        gamepad.apply_axis(5, 1., &mut changes);
+        gamepad.apply_axis(5, 0.8, &mut changes);
+        gamepad.apply_axis(5, -1., &mut changes);
+
+        assert_eq!(changes, vec![(GamepadButton::RightTrigger, true), (GamepadButton::RightTrigger, false)]);
+        assert_eq!(gamepad.get_state().right_trigger, 0.);
+    }
+
+    #[test]
+    fn unplugging_releases_everything() {
+        let (mut gamepad, sender) = connected();
+        sender.send(JoystickEvent::Button(0, true)).unwrap();
+        sender.send(JoystickEvent::Axis(0, 0.7)).unwrap();
+        sender.send(JoystickEvent::Axis(2, 1.)).unwrap();
+        sender.send(JoystickEvent::Axis(7, -1.)).unwrap();
+        assert_eq!(gamepad.poll(), vec![(GamepadButton::South, true), (GamepadButton::LeftTrigger, true), (GamepadButton::DPadUp, true)]);
+        assert_eq!(gamepad.get_state().left_stick, [0.7, 0.]);
+
+        // Everything held is released and the sticks and triggers go back to the center
+        sender.send(JoystickEvent::Disconnected).unwrap();
+        assert_eq!(gamepad.poll(), vec![(GamepadButton::South, false), (GamepadButton::LeftTrigger, false), (GamepadButton::DPadUp, false)]);
+        assert_eq!(gamepad.get_state().left_stick, [0., 0.]);
+        assert_eq!(gamepad.get_state().left_trigger, 0.);
+
+        // Nothing is released twice, and plugging it back in works like before
+        sender.send(JoystickEvent::Disconnected).unwrap();
+        sender.send(JoystickEvent::Button(0, true)).unwrap();
+        assert_eq!(gamepad.poll(), vec![(GamepadButton::South, true)]);
+    }
+}
@@ -0,0 +1,59 @@
+use super::EglContext;
+use glium::backend::Context;
+use glium::backend::Facade;
+use glium::glutin;
+use std::panic;
+use std::rc::Rc;
+
+/// An OpenGL context without a visible window for rendering off screen, like on build machines
+/// without a GPU.
+pub struct Headless {
+    pub renderer: Rc<Context>,
+
+    /// glutin needs an events loop to create any context, even though it's never polled. This is
+    /// declared last so it's dropped after the context. There's none when rendering through EGL.
+    _events_loop: Option<glutin::EventsLoop>
+}
+
+impl Headless {
+    pub fn new() -> Result<Headless, String> {
+        // Creating the events loop panics if there's no X11 or Wayland display to connect to, like
+        // on a build machine not running Xvfb, so catch it without printing the panic
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| { }));
+        let events_loop = panic::catch_unwind(glutin::EventsLoop::new);
+        panic::set_hook(default_hook);
+
+        // Without a display, render with Mesa's surfaceless EGL platform instead
+        let events_loop = match events_loop {
+            Ok(events_loop) => events_loop,
+            Err(_) => return Headless::new_surfaceless()
+        };
+
+        // Prefer OSMesa, which rasterizes in software, and fall back to a hidden window so it works
+        // with any driver, including llvmpipe
+        let context_builder = || glutin::ContextBuilder::new().with_depth_buffer(24);
+        let context = glutin::Context::new(&events_loop, context_builder(), false)
+            .or_else(|_| glutin::Context::new(&events_loop, context_builder(), true))
+            .map_err(|err| format!("Failed to create a headless OpenGL context: {}", err))?;
+        let renderer = glium::HeadlessRenderer::new(context)
+            .map_err(|err| format!("Failed to create a headless renderer: {:?}", err))?;
+
+        Ok(Headless {
+            renderer: renderer.get_context().clone(),
+            _events_loop: Some(events_loop)
+        })
+    }
+
+    fn new_surfaceless() -> Result<Headless, String> {
+        let context = EglContext::new()
+            .map_err(|err| format!("Failed to connect to a display or create a surfaceless context ({}); try running under `xvfb-run`", err))?;
+        let renderer = unsafe { Context::new(context, false, Default::default()) }
+            .map_err(|err| format!("Failed to create a headless renderer: {:?}", err))?;
+
+        Ok(Headless {
+            renderer,
+            _events_loop: None
+        })
+    }
+}
@@ -0,0 +1,557 @@
+use glium::glutin;
+use glium::glutin::VirtualKeyCode;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+
+/// Something the player can do, independent of the input it's bound to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Sprint,
+    UnlockCursor,
+    BreakBlock,
+    PlaceBlock,
+    NextBlock,
+    PreviousBlock,
+    ToggleDebug,
+    ToggleMovementMode,
+    ToggleTime,
+    TimeBackward,
+    TimeForward,
+    Screenshot,
+    TiledScreenshot,
+    Zoom,
+    TogglePathRecording,
+    TogglePathPlayback,
+    ToggleHud,
+    ToggleConsole
+}
+
+impl Action {
+    pub const ALL: [Action; 24] = [
+        Action::MoveForward, Action::MoveBackward, Action::MoveLeft, Action::MoveRight, Action::MoveUp, Action::MoveDown,
+        Action::Sprint, Action::UnlockCursor, Action::BreakBlock, Action::PlaceBlock, Action::NextBlock, Action::PreviousBlock,
+        Action::ToggleDebug, Action::ToggleMovementMode, Action::ToggleTime, Action::TimeBackward, Action::TimeForward,
+        Action::Screenshot, Action::TiledScreenshot, Action::Zoom, Action::TogglePathRecording, Action::TogglePathPlayback,
+        Action::ToggleHud, Action::ToggleConsole
+    ];
+
+    /// The name used in binding configs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::Sprint => "sprint",
+            Action::UnlockCursor => "unlock_cursor",
+            Action::BreakBlock => "break_block",
+            Action::PlaceBlock => "place_block",
+            Action::NextBlock => "next_block",
+            Action::PreviousBlock => "previous_block",
+            Action::ToggleDebug => "toggle_debug",
+            Action::ToggleMovementMode => "toggle_movement_mode",
+            Action::ToggleTime => "toggle_time",
+            Action::TimeBackward => "time_backward",
+            Action::TimeForward => "time_forward",
+            Action::Screenshot => "screenshot",
+            Action::TiledScreenshot => "tiled_screenshot",
+            Action::Zoom => "zoom",
+            Action::TogglePathRecording => "toggle_path_recording",
+            Action::TogglePathPlayback => "toggle_path_playback",
+            Action::ToggleHud => "toggle_hud",
+            Action::ToggleConsole => "toggle_console",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().cloned().find(|action| action.name() == name)
+    }
+}
+
+/// A button on a gamepad, named by its position so it's the same across controller layouts.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 16] = [
+        GamepadButton::South, GamepadButton::East, GamepadButton::West, GamepadButton::North,
+        GamepadButton::LeftBumper, GamepadButton::RightBumper, GamepadButton::LeftTrigger, GamepadButton::RightTrigger,
+        GamepadButton::Select, GamepadButton::Start, GamepadButton::LeftStick, GamepadButton::RightStick,
+        GamepadButton::DPadUp, GamepadButton::DPadDown, GamepadButton::DPadLeft, GamepadButton::DPadRight
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GamepadButton::South => "south",
+            GamepadButton::East => "east",
+            GamepadButton::West => "west",
+            GamepadButton::North => "north",
+            GamepadButton::LeftBumper => "left_bumper",
+            GamepadButton::RightBumper => "right_bumper",
+            GamepadButton::LeftTrigger => "left_trigger",
+            GamepadButton::RightTrigger => "right_trigger",
+            GamepadButton::Select => "select",
+            GamepadButton::Start => "start",
+            GamepadButton::LeftStick => "left_stick",
+            GamepadButton::RightStick => "right_stick",
+            GamepadButton::DPadUp => "dpad_up",
+            GamepadButton::DPadDown => "dpad_down",
+            GamepadButton::DPadLeft => "dpad_left",
+            GamepadButton::DPadRight => "dpad_right",
+        }
+    }
+}
+
+/// A physical input that can be bound to an action.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(glutin::MouseButton),
+    Gamepad(GamepadButton),
+
+    /// Scrolling only presses these and never holds them.
+    ScrollUp,
+    ScrollDown
+}
+
+impl Input {
+    /// The keys that can be bound; this leaves out media and language keys.
+    const KEYS: [VirtualKeyCode; 98] = [
+        VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4, VirtualKeyCode::Key5,
+        VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9, VirtualKeyCode::Key0,
+        VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E, VirtualKeyCode::F,
+        VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J, VirtualKeyCode::K, VirtualKeyCode::L,
+        VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O, VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R,
+        VirtualKeyCode::S, VirtualKeyCode::T, VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X,
+        VirtualKeyCode::Y, VirtualKeyCode::Z,
+        VirtualKeyCode::F1, VirtualKeyCode::F2, VirtualKeyCode::F3, VirtualKeyCode::F4, VirtualKeyCode::F5, VirtualKeyCode::F6,
+        VirtualKeyCode::F7, VirtualKeyCode::F8, VirtualKeyCode::F9, VirtualKeyCode::F10, VirtualKeyCode::F11, VirtualKeyCode::F12,
+        VirtualKeyCode::Escape, VirtualKeyCode::Insert, VirtualKeyCode::Home, VirtualKeyCode::Delete, VirtualKeyCode::End,
+        VirtualKeyCode::PageDown, VirtualKeyCode::PageUp, VirtualKeyCode::Left, VirtualKeyCode::Up, VirtualKeyCode::Right,
+        VirtualKeyCode::Down, VirtualKeyCode::Back, VirtualKeyCode::Return, VirtualKeyCode::Space, VirtualKeyCode::Tab,
+        VirtualKeyCode::Numpad0, VirtualKeyCode::Numpad1, VirtualKeyCode::Numpad2, VirtualKeyCode::Numpad3,
+        VirtualKeyCode::Numpad4, VirtualKeyCode::Numpad5, VirtualKeyCode::Numpad6, VirtualKeyCode::Numpad7,
+        VirtualKeyCode::Numpad8, VirtualKeyCode::Numpad9, VirtualKeyCode::Add, VirtualKeyCode::Subtract,
+        VirtualKeyCode::Multiply, VirtualKeyCode::Divide, VirtualKeyCode::Decimal, VirtualKeyCode::NumpadEnter,
+        VirtualKeyCode::Apostrophe, VirtualKeyCode::Backslash, VirtualKeyCode::Comma, VirtualKeyCode::Equals,
+        VirtualKeyCode::Grave, VirtualKeyCode::LBracket, VirtualKeyCode::RBracket, VirtualKeyCode::Minus,
+        VirtualKeyCode::Period, VirtualKeyCode::Semicolon, VirtualKeyCode::Slash,
+        VirtualKeyCode::LAlt, VirtualKeyCode::RAlt, VirtualKeyCode::LControl, VirtualKeyCode::RControl,
+        VirtualKeyCode::LShift, VirtualKeyCode::RShift, VirtualKeyCode::LWin, VirtualKeyCode::RWin
+    ];
+
+    /// Parses the name of an input, e.g. `w`, `space`, `mouse_left` or `gamepad_south`.
+    pub fn from_name(name: &str) -> Option<Input> {
+        let name = name.to_lowercase();
+        match name.as_str() {
+            "mouse_left" => return Some(Input::Mouse(glutin::MouseButton::Left)),
+            "mouse_right" => return Some(Input::Mouse(glutin::MouseButton::Right)),
+            "mouse_middle" => return Some(Input::Mouse(glutin::MouseButton::Middle)),
+            "scroll_up" => return Some(Input::ScrollUp),
+            "scroll_down" => return Some(Input::ScrollDown),
+            _ => { }
+        }
+        if let Some(button) = name.strip_prefix("mouse_") {
+            return button.parse().ok().map(|button| Input::Mouse(glutin::MouseButton::Other(button)));
+        }
+        if let Some(button) = name.strip_prefix("gamepad_") {
+            return GamepadButton::ALL.iter().find(|gamepad_button| gamepad_button.name() == button).map(|&button| Input::Gamepad(button));
+        }
+
+        Input::KEYS.iter().find(|&&key| Input::key_name(key) == name).map(|&key| Input::Key(key))
+    }
+
+    /// Names keys by their variant, except for the number keys which are just the number.
+    fn key_name(key: VirtualKeyCode) -> String {
+        let name = format!("{:?}", key).to_lowercase();
+        match name.strip_prefix("key") {
+            Some(number) => number.to_string(),
+            None => name
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::Key(key) => write!(f, "{}", Input::key_name(*key)),
+            Input::Mouse(glutin::MouseButton::Left) => write!(f, "mouse_left"),
+            Input::Mouse(glutin::MouseButton::Right) => write!(f, "mouse_right"),
+            Input::Mouse(glutin::MouseButton::Middle) => write!(f, "mouse_middle"),
+            Input::Mouse(glutin::MouseButton::Other(button)) => write!(f, "mouse_{}", button),
+            Input::Gamepad(button) => write!(f, "gamepad_{}", button.name()),
+            Input::ScrollUp => write!(f, "scroll_up"),
+            Input::ScrollDown => write!(f, "scroll_down"),
+        }
+    }
+}
+
+/// An input, optionally only while shift is held.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Binding {
+    pub input: Input,
+    pub shift: bool
+}
+
+impl Binding {
+    pub fn new(input: Input) -> Binding {
+        Binding { input, shift: false }
+    }
+
+    pub fn with_shift(input: Input) -> Binding {
+        Binding { input, shift: true }
+    }
+
+    /// Parses an input name with an optional `shift+` in front, e.g. `shift+f2`.
+    pub fn from_name(name: &str) -> Option<Binding> {
+        let name = name.trim();
+        match name.to_lowercase().strip_prefix("shift+") {
+            Some(input) => Input::from_name(input).map(Binding::with_shift),
+            None => Input::from_name(name).map(Binding::new)
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.shift { write!(f, "shift+")?; }
+        write!(f, "{}", self.input)
+    }
+}
+
+/// Maps inputs to actions. Each action can have any number of bindings, but each binding can only
+/// be used by one action.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    bindings: Vec<(Action, Binding)>
+}
+
+impl InputMap {
+    /// Loads a binding config on top of the default bindings; see `apply_config`.
+    pub fn load(path: &str) -> Result<InputMap, String> {
+        let config = fs::read_to_string(path).map_err(|err| format!("Failed to read bindings `{}`: {}", path, err))?;
+        let mut input_map = InputMap::default();
+        input_map.apply_config(&config).map_err(|err| format!("Invalid bindings `{}`: {}", path, err))?;
+        Ok(input_map)
+    }
+
+    /// Replaces the bindings of the actions in a config. Each line is in the form
+    /// `action = input, input, ...` and lines starting with `#` are ignored; leaving out the inputs
+    /// unbinds the action. Fails without changing anything if any of the bindings conflict.
+    pub fn apply_config(&mut self, config: &str) -> Result<(), String> {
+        let mut lines = Vec::new();
+        for (number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            // Parse the line
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let inputs = parts.next().ok_or_else(|| error(format!("Expected `action = inputs` but found `{}`", line)))?;
+            let action = Action::from_name(name).ok_or_else(|| error(format!("Unknown action `{}`", name)))?;
+            let bindings = inputs.split(',')
+                .map(|input| input.trim())
+                .filter(|input| !input.is_empty())
+                .map(|input| Binding::from_name(input).ok_or_else(|| error(format!("Unknown input `{}`", input))))
+                .collect::<Result<Vec<Binding>, String>>()?;
+
+            lines.push((number, action, bindings));
+        }
+
+        // Unbind all of the actions first so bindings can be swapped between them
+        let mut input_map = self.clone();
+        input_map.bindings.retain(|(action, _)| !lines.iter().any(|(_, line_action, _)| line_action == action));
+        for (number, action, bindings) in lines {
+            input_map.bind(action, &bindings).map_err(|err| format!("line {}: {}", number + 1, err))?;
+        }
+
+        *self = input_map;
+        Ok(())
+    }
+
+    /// Writes the bindings in the format read by `apply_config`.
+    pub fn to_config(&self) -> String {
+        let mut config = String::from("# Each line binds an action to inputs, e.g. `move_up = space, gamepad_south`\n");
+        for &action in Action::ALL.iter() {
+            let inputs: Vec<String> = self.get_bindings(action).iter().map(|binding| binding.to_string()).collect();
+            config.push_str(&format!("{} = {}\n", action.name(), inputs.join(", ")));
+        }
+        config
+    }
+
+    pub fn get_bindings(&self, action: Action) -> Vec<Binding> {
+        self.bindings.iter().filter(|(bound_action, _)| *bound_action == action).map(|&(_, binding)| binding).collect()
+    }
+
+    /// Returns the action using a binding.
+    pub fn get_action(&self, binding: &Binding) -> Option<Action> {
+        self.bindings.iter().find(|(_, bound)| bound == binding).map(|&(action, _)| action)
+    }
+
+    /// Replaces the bindings of an action. Fails if another action already uses one of the
+    /// bindings.
+    pub fn bind(&mut self, action: Action, bindings: &[Binding]) -> Result<(), String> {
+        // Check for conflicts before changing anything
+        let mut seen = HashSet::new();
+        for binding in bindings {
+            match self.get_action(binding) {
+                Some(other) if other != action => return Err(format!("`{}` is already bound to `{}`", binding, other.name())),
+                _ => { }
+            }
+            if !seen.insert(binding) {
+                return Err(format!("`{}` is bound to `{}` more than once", binding, action.name()));
+            }
+        }
+
+        self.bindings.retain(|(bound_action, _)| *bound_action != action);
+        self.bindings.extend(bindings.iter().map(|&binding| (action, binding)));
+        Ok(())
+    }
+
+    /// Returns the actions an input changes and if they were pressed or released. Bindings with
+    /// shift take priority while shift is held, but bindings without it still work, so holding
+    /// shift to sprint doesn't stop the other keys. Releasing an input releases every action bound
+    /// to it so actions don't get stuck when shift changes while they're held.
+    pub fn map_input(&self, input: Input, pressed: bool, shift: bool) -> Vec<(Action, bool)> {
+        let bound: Vec<(Action, Binding)> = self.bindings.iter().filter(|(_, binding)| binding.input == input).cloned().collect();
+        if !pressed {
+            return bound.iter().map(|&(action, _)| (action, false)).collect();
+        }
+
+        let with_shift = shift && bound.iter().any(|(_, binding)| binding.shift);
+        bound.iter()
+            .filter(|(_, binding)| binding.shift == with_shift)
+            .map(|&(action, _)| (action, true))
+            .collect()
+    }
+
+    /// Returns the actions a gamepad button changes; see `map_input`.
+    pub fn map_gamepad(&self, button: GamepadButton, pressed: bool) -> Vec<(Action, bool)> {
+        self.map_input(Input::Gamepad(button), pressed, false)
+    }
+
+    /// Returns the actions a window or device event changes; see `map_input`.
+    pub fn map_event(&self, event: &glutin::Event) -> Vec<(Action, bool)> {
+        match *event {
+            glutin::Event::WindowEvent { ref event, .. } => match *event {
+                glutin::WindowEvent::KeyboardInput { ref input, .. } => match input.virtual_keycode {
+                    Some(key) => self.map_input(Input::Key(key), input.state == glutin::ElementState::Pressed, input.modifiers.shift),
+                    None => Vec::new()
+                },
+
+                glutin::WindowEvent::MouseInput { state, button, modifiers, .. } => {
+                    self.map_input(Input::Mouse(button), state == glutin::ElementState::Pressed, modifiers.shift)
+                },
+
+                glutin::WindowEvent::MouseWheel { delta, modifiers, .. } => {
+                    let scroll = match delta {
+                        glutin::MouseScrollDelta::LineDelta(_, y) => y,
+                        glutin::MouseScrollDelta::PixelDelta(position) => position.y as f32
+                    };
+                    if scroll > 0. {
+                        self.map_input(Input::ScrollUp, true, modifiers.shift)
+                    } else if scroll < 0. {
+                        self.map_input(Input::ScrollDown, true, modifiers.shift)
+                    } else {
+                        Vec::new()
+                    }
+                },
+
+                _ => Vec::new()
+            },
+
+            _ => Vec::new()
+        }
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> InputMap {
+        let key = |key: VirtualKeyCode| Binding::new(Input::Key(key));
+        let gamepad = |button: GamepadButton| Binding::new(Input::Gamepad(button));
+        let defaults = [
+            (Action::MoveForward, key(VirtualKeyCode::W)),
+            (Action::MoveBackward, key(VirtualKeyCode::S)),
+            (Action::MoveLeft, key(VirtualKeyCode::A)),
+            (Action::MoveRight, key(VirtualKeyCode::D)),
+            (Action::MoveUp, key(VirtualKeyCode::E)),
+            (Action::MoveUp, key(VirtualKeyCode::Space)),
+            (Action::MoveDown, key(VirtualKeyCode::Q)),
+            (Action::Sprint, key(VirtualKeyCode::LShift)),
+            (Action::Sprint, key(VirtualKeyCode::RShift)),
+            (Action::UnlockCursor, key(VirtualKeyCode::Escape)),
+            (Action::BreakBlock, Binding::new(Input::Mouse(glutin::MouseButton::Left))),
+            (Action::PlaceBlock, Binding::new(Input::Mouse(glutin::MouseButton::Right))),
+            (Action::NextBlock, Binding::new(Input::ScrollUp)),
+            (Action::PreviousBlock, Binding::new(Input::ScrollDown)),
+            (Action::ToggleDebug, key(VirtualKeyCode::Grave)),
+            (Action::ToggleMovementMode, key(VirtualKeyCode::F)),
+            (Action::ToggleTime, key(VirtualKeyCode::T)),
+            (Action::TimeBackward, key(VirtualKeyCode::LBracket)),
+            (Action::TimeForward, key(VirtualKeyCode::RBracket)),
+            (Action::Screenshot, key(VirtualKeyCode::F2)),
+            (Action::TiledScreenshot, Binding::with_shift(Input::Key(VirtualKeyCode::F2))),
+            (Action::Zoom, key(VirtualKeyCode::C)),
+            (Action::TogglePathRecording, key(VirtualKeyCode::F6)),
+            (Action::TogglePathPlayback, key(VirtualKeyCode::F7)),
+            (Action::ToggleHud, key(VirtualKeyCode::F3)),
+            (Action::ToggleConsole, key(VirtualKeyCode::Slash)),
+            (Action::MoveUp, gamepad(GamepadButton::South)),
+            (Action::MoveDown, gamepad(GamepadButton::East)),
+            (Action::Sprint, gamepad(GamepadButton::LeftStick)),
+            (Action::BreakBlock, gamepad(GamepadButton::RightTrigger)),
+            (Action::PlaceBlock, gamepad(GamepadButton::LeftTrigger)),
+            (Action::NextBlock, gamepad(GamepadButton::RightBumper)),
+            (Action::PreviousBlock, gamepad(GamepadButton::LeftBumper)),
+            (Action::ToggleMovementMode, gamepad(GamepadButton::North)),
+            (Action::Zoom, gamepad(GamepadButton::RightStick)),
+        ];
+
+        InputMap { bindings: defaults.to_vec() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: VirtualKeyCode) -> Binding {
+        Binding::new(Input::Key(key))
+    }
+
+    #[test]
+    fn binding_names_round_trip() {
+        let mut inputs: Vec<Input> = Input::KEYS.iter().map(|&key| Input::Key(key)).collect();
+        inputs.extend(GamepadButton::ALL.iter().map(|&button| Input::Gamepad(button)));
+        inputs.extend([
+            Input::Mouse(glutin::MouseButton::Left), Input::Mouse(glutin::MouseButton::Right),
+            Input::Mouse(glutin::MouseButton::Middle), Input::Mouse(glutin::MouseButton::Other(4)),
+            Input::ScrollUp, Input::ScrollDown
+        ].iter());
+
+        for &input in inputs.iter() {
+            for &binding in [Binding::new(input), Binding::with_shift(input)].iter() {
+                assert_eq!(Binding::from_name(&binding.to_string()), Some(binding), "{}", binding);
+            }
+        }
+
+        // Names are case insensitive and number keys are just the number
+        assert_eq!(Binding::from_name(" Shift+F2 "), Some(Binding::with_shift(Input::Key(VirtualKeyCode::F2))));
+        assert_eq!(Binding::from_name("1"), Some(key(VirtualKeyCode::Key1)));
+        assert_eq!(key(VirtualKeyCode::Key1).to_string(), "1");
+        assert_eq!(Binding::from_name("shift+"), None);
+        assert_eq!(Binding::from_name("mouse_x"), None);
+        assert_eq!(Binding::from_name("gamepad_paddle"), None);
+        assert_eq!(Binding::from_name("playpause"), None);
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let mut input_map = InputMap::default();
+        input_map.bind(Action::Zoom, &[key(VirtualKeyCode::Z), Binding::with_shift(Input::Key(VirtualKeyCode::C))]).unwrap();
+        input_map.bind(Action::ToggleHud, &[]).unwrap();
+
+        let mut loaded = InputMap::default();
+        loaded.apply_config(&input_map.to_config()).unwrap();
+        for &action in Action::ALL.iter() {
+            assert_eq!(loaded.get_bindings(action), input_map.get_bindings(action), "{}", action.name());
+        }
+    }
+
+    #[test]
+    fn config_swaps_and_unbinds() {
+        let mut input_map = InputMap::default();
+        input_map.apply_config("# Swap up and down\n\n  move_up = q\nmove_down = e, space\ntoggle_hud =\n").unwrap();
+
+        assert_eq!(input_map.get_bindings(Action::MoveUp), [key(VirtualKeyCode::Q)]);
+        assert_eq!(input_map.get_bindings(Action::MoveDown), [key(VirtualKeyCode::E), key(VirtualKeyCode::Space)]);
+        assert!(input_map.get_bindings(Action::ToggleHud).is_empty());
+
+        // Actions left out of the config keep their bindings
+        assert_eq!(input_map.get_bindings(Action::MoveForward), [key(VirtualKeyCode::W)]);
+    }
+
+    #[test]
+    fn config_errors() {
+        let check = |config: &str, expected: &str| {
+            let mut input_map = InputMap::default();
+            let err = input_map.apply_config(config).unwrap_err();
+            assert!(err.starts_with(expected), "`{}` gave `{}`", config, err);
+
+            // Nothing changes when the config fails
+            assert_eq!(input_map.bindings, InputMap::default().bindings);
+        };
+
+        check("zoom = z\nfly", "line 2: Expected `action = inputs`");
+        check("\n# Comment\nteleport = t", "line 3: Unknown action `teleport`");
+        check("zoom = z, hyperspace", "line 1: Unknown input `hyperspace`");
+        check("zoom = w", "line 1: `w` is already bound to `move_forward`");
+        check("zoom = z\nscreenshot = z", "line 2: `z` is already bound to `zoom`");
+        check("zoom = z, z", "line 1: `z` is bound to `zoom` more than once");
+
+        assert!(InputMap::load("does/not/exist.cfg").is_err());
+    }
+
+    #[test]
+    fn bind_checks_conflicts() {
+        let mut input_map = InputMap::default();
+
+        // Another action's binding fails without changing anything
+        let err = input_map.bind(Action::Zoom, &[key(VirtualKeyCode::Z), key(VirtualKeyCode::W)]).unwrap_err();
+        assert_eq!(err, "`w` is already bound to `move_forward`");
+        assert_eq!(input_map.get_bindings(Action::Zoom), [key(VirtualKeyCode::C), Binding::new(Input::Gamepad(GamepadButton::RightStick))]);
+
+        // Rebinding an action to its own binding is fine, and so is the shifted version of another
+        input_map.bind(Action::Zoom, &[key(VirtualKeyCode::C), Binding::with_shift(Input::Key(VirtualKeyCode::W))]).unwrap();
+        assert_eq!(input_map.get_action(&Binding::with_shift(Input::Key(VirtualKeyCode::W))), Some(Action::Zoom));
+        assert_eq!(input_map.get_action(&Binding::new(Input::Gamepad(GamepadButton::RightStick))), None);
+    }
+
+    #[test]
+    fn shift_bindings_take_priority() {
+        let input_map = InputMap::default();
+        let f2 = Input::Key(VirtualKeyCode::F2);
+
+        assert_eq!(input_map.map_input(f2, true, false), [(Action::Screenshot, true)]);
+        assert_eq!(input_map.map_input(f2, true, true), [(Action::TiledScreenshot, true)]);
+
+        // Releasing releases both, in case shift changed while it was held
+        let released = input_map.map_input(f2, false, false);
+        assert_eq!(released.len(), 2);
+        assert!(released.contains(&(Action::Screenshot, false)));
+        assert!(released.contains(&(Action::TiledScreenshot, false)));
+
+        // Inputs without shift bindings still work while shift is held
+        assert_eq!(input_map.map_input(Input::Key(VirtualKeyCode::W), true, true), [(Action::MoveForward, true)]);
+        assert_eq!(input_map.map_input(Input::Key(VirtualKeyCode::LShift), true, true), [(Action::Sprint, true)]);
+        assert!(input_map.map_input(Input::Key(VirtualKeyCode::Z), true, false).is_empty());
+        assert_eq!(input_map.map_gamepad(GamepadButton::South, true), [(Action::MoveUp, true)]);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::utils::AsFloatSeconds;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Which messages get logged, in the form `level,target=level,...`, e.g. `info,mesh=debug`. A
+/// target also matches the targets under it, so `voxel_test::client=warn` covers every module in
+/// the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    default_level: LevelFilter,
+    targets: Vec<(String, LevelFilter)>
+}
+
+impl LogFilter {
+    pub fn from_config(config: &str) -> Result<LogFilter, String> {
+        let mut filter = LogFilter::default();
+        for directive in config.split(',').map(|directive| directive.trim()).filter(|directive| !directive.is_empty()) {
+            let parse_level = |level: &str| level.parse::<LevelFilter>()
+                .map_err(|_| format!("Unknown log level `{}`; expected off, error, warn, info, debug or trace", level));
+
+            let mut parts = directive.splitn(2, '=');
+            let first = parts.next().unwrap_or("").trim();
+            match parts.next() {
+                Some(level) => filter.targets.push((first.to_string(), parse_level(level.trim())?)),
+                None => filter.default_level = parse_level(first)?
+            }
+        }
+
+        // Check the most specific targets first
+        filter.targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(filter)
+    }
+
+    /// Returns the most verbose level logged for a target.
+    pub fn get_level(&self, target: &str) -> LevelFilter {
+        self.targets.iter()
+            .find(|(filter_target, _)| target == filter_target || target.starts_with(&format!("{}::", filter_target)))
+            .map(|&(_, level)| level)
+            .unwrap_or(self.default_level)
+    }
+
+    /// Returns the most verbose level logged for any target.
+    fn get_max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|&(_, level)| level).fold(self.default_level, |max, level| max.max(level))
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> LogFilter {
+        LogFilter {
+            default_level: LevelFilter::Info,
+            targets: Vec::new()
+        }
+    }
+}
+
+/// Writes log messages to stderr with the time since the game started, and spans to a Chrome
+/// trace file if there is one.
+struct Logger {
+    filter: LogFilter,
+    start: Instant,
+    trace: Option<Mutex<File>>
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.get_level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) { return; }
+
+        let elapsed = self.start.elapsed();
+        eprintln!(
+            "{:>4}.{:03} {:<5} {}: {}",
+            elapsed.as_secs(), elapsed.subsec_millis(), record.level(), record.target(), record.args()
+        );
+    }
+
+    fn flush(&self) { }
+}
+
+lazy_static! {
+    /// The logger once `init_logger` has been called; `log` only gives it back as a `dyn Log`.
+    static ref LOGGER: Mutex<Option<&'static Logger>> = Mutex::new(None);
+}
+
+/// Whether spans are written to a trace, so spans don't have to lock `LOGGER` to find out.
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+/// Starts logging with a filter, and writing spans to a Chrome trace file at `trace_path` if
+/// it's given. The trace can be opened in `chrome://tracing` or Perfetto.
+pub fn init_logger(filter: LogFilter, trace_path: Option<&str>) -> Result<(), String> {
+    // Start the trace; the closing bracket is optional in the trace format, so events can be
+    // written as they end and the file is still valid if the game exits suddenly
+    let trace = match trace_path {
+        Some(path) => {
+            let mut file = File::create(path).map_err(|err| format!("Failed to create trace `{}`: {}", path, err))?;
+            file.write_all(b"[\n").map_err(|err| format!("Failed to write trace `{}`: {}", path, err))?;
+            Some(Mutex::new(file))
+        },
+        None => None
+    };
+
+    let max_level = filter.get_max_level();
+    let tracing = trace.is_some();
+    let logger: &'static Logger = Box::leak(Box::new(Logger { filter, start: Instant::now(), trace }));
+    log::set_logger(logger).map_err(|err| format!("Failed to start logging: {}", err))?;
+    log::set_max_level(max_level);
+    *LOGGER.lock().unwrap() = Some(logger);
+    TRACING.store(tracing, Ordering::Release);
+
+    Ok(())
+}
+
+thread_local! {
+    /// A small number for each thread for the trace, since `ThreadId` can't be turned in to one.
+    static THREAD_NUMBER: Cell<usize> = const { Cell::new(0) };
+}
+
+static NEXT_THREAD_NUMBER: AtomicUsize = AtomicUsize::new(1);
+
+fn get_thread_number() -> usize {
+    THREAD_NUMBER.with(|number| {
+        if number.get() == 0 {
+            number.set(NEXT_THREAD_NUMBER.fetch_add(1, Ordering::Relaxed));
+        }
+        number.get()
+    })
+}
+
+/// Times a stage of work until it's dropped, then logs how long it took at the trace level and
+/// adds it to the Chrome trace.
+pub struct Span {
+    target: &'static str,
+    name: &'static str,
+    start: Instant
+}
+
+impl Span {
+    pub fn new(target: &'static str, name: &'static str) -> Span {
+        Span { target, name, start: Instant::now() }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+        trace!(target: self.target, "{} - {:.3}ms", self.name, duration.as_float_seconds() * 1000.);
+
+        // Add the span to the trace
+        if !TRACING.load(Ordering::Acquire) { return; }
+        let logger = match *LOGGER.lock().unwrap() {
+            Some(logger) => logger,
+            None => return
+        };
+        if let Some(ref trace) = logger.trace {
+            let start = self.start.duration_since(logger.start);
+            let event = format!(
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}},\n",
+                self.name, self.target, start.as_micros(), duration.as_micros(), get_thread_number()
+            );
+
+            // Don't stop the game if the trace can't be written
+            let _ = trace.lock().unwrap().write_all(event.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_filters() {
+        let filter = LogFilter::from_config(" warn, mesh=debug ,, voxel_test::client = error").unwrap();
+        assert_eq!(filter.get_level("render"), LevelFilter::Warn);
+        assert_eq!(filter.get_level("mesh"), LevelFilter::Debug);
+        assert_eq!(filter.get_level("voxel_test::client::hud"), LevelFilter::Error);
+        assert_eq!(filter.get_level("voxel_test::utils"), LevelFilter::Warn);
+        assert_eq!(filter.get_max_level(), LevelFilter::Debug);
+
+        assert_eq!(LogFilter::from_config("").unwrap(), LogFilter::default());
+        assert_eq!(LogFilter::from_config("").unwrap().get_level("mesh"), LevelFilter::Info);
+        assert_eq!(LogFilter::from_config("OFF").unwrap().get_max_level(), LevelFilter::Off);
+    }
+
+    #[test]
+    fn rejects_unknown_levels() {
+        assert!(LogFilter::from_config("loud").is_err());
+        assert!(LogFilter::from_config("info,mesh=loud").is_err());
+        assert!(LogFilter::from_config("info,mesh=").is_err());
+    }
+
+    #[test]
+    fn matches_the_most_specific_target() {
+        // The order in the config doesn't matter
+        for config in ["mesh=debug,mesh::x=trace", "mesh::x=trace,mesh=debug"].iter() {
+            let filter = LogFilter::from_config(config).unwrap();
+            assert_eq!(filter.get_level("mesh"), LevelFilter::Debug);
+            assert_eq!(filter.get_level("mesh::y"), LevelFilter::Debug);
+            assert_eq!(filter.get_level("mesh::x"), LevelFilter::Trace);
+            assert_eq!(filter.get_level("mesh::x::z"), LevelFilter::Trace);
+        }
+
+        // Targets only match whole path segments
+        let filter = LogFilter::from_config("mesh=trace").unwrap();
+        assert_eq!(filter.get_level("meshes"), LevelFilter::Info);
+        assert_eq!(filter.get_level("mesh_x"), LevelFilter::Info);
+        assert_eq!(filter.get_level("render::mesh"), LevelFilter::Info);
+    }
+}
@@ -35,15 +35,15 @@ pub trait ChunkClamp {
 
 impl ChunkClamp for f32 {
     fn chunk_clamp_x(self) -> Self {
-        self.min(world::Chunk::SIZE_X_F32 - 1.).max(0.)
+        self.clamp(0., world::Chunk::SIZE_X_F32 - 1.)
     }
 
     fn chunk_clamp_y(self) -> Self {
-        self.min(world::Chunk::SIZE_Y_F32 - 1.).max(0.)
+        self.clamp(0., world::Chunk::SIZE_Y_F32 - 1.)
     }
 
     fn chunk_clamp_z(self) -> Self {
-        self.min(world::Chunk::SIZE_Z_F32 - 1.).max(0.)
+        self.clamp(0., world::Chunk::SIZE_Z_F32 - 1.)
     }
 }
 
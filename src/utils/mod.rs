@@ -1,7 +1,23 @@
 mod app;
 mod camera;
+mod camera_path;
+mod egl;
+mod gamepad;
+mod headless;
+mod input;
+mod logger;
 mod misc;
+mod motion;
+mod random;
 
 pub use app::*;
 pub use camera::*;
+pub use camera_path::*;
+pub use egl::*;
+pub use gamepad::*;
+pub use headless::*;
+pub use input::*;
+pub use logger::*;
 pub use misc::*;
+pub use motion::*;
+pub use random::*;
@@ -0,0 +1,123 @@
+/// The analog state of a gamepad. Sticks go from -1 to 1 with +Y pointing up and triggers go
+/// from 0 to 1.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct GamepadState {
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    pub left_trigger: f32,
+    pub right_trigger: f32
+}
+
+/// How the sticks respond to being pushed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StickSettings {
+    /// How far a stick has to be pushed, from 0 to 1, before it does anything. This hides sticks
+    /// that don't center perfectly.
+    pub dead_zone: f32,
+
+    /// The exponent applied to how far a stick is pushed past the dead zone. Values above 1 give
+    /// finer control near the center.
+    pub curve: f32,
+
+    /// How fast the camera turns with the look stick pushed all the way, in radians per second.
+    pub look_speed: f32
+}
+
+impl Default for StickSettings {
+    fn default() -> StickSettings {
+        StickSettings {
+            dead_zone: 0.15,
+            curve: 2.,
+            look_speed: 3.
+        }
+    }
+}
+
+impl StickSettings {
+    /// Applies the dead zone and curve to a stick. The dead zone is radial so diagonals aren't
+    /// snapped to the axes, and the rest of the range is stretched back out to 0 to 1 so there's
+    /// no jump at the edge of the dead zone.
+    pub fn shape(&self, stick: [f32; 2]) -> [f32; 2] {
+        let length = stick[0].hypot(stick[1]);
+        if length <= self.dead_zone || length == 0. { return [0., 0.]; }
+
+        let amount = ((length - self.dead_zone) / (1. - self.dead_zone)).min(1.).powf(self.curve);
+        [stick[0] / length * amount, stick[1] / length * amount]
+    }
+}
+
+/// The motion from a gamepad for a single step.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct GamepadMotion {
+    /// How far to move to the right and forward, from -1 to 1.
+    pub movement: [f32; 2],
+
+    /// How far to turn right and up, in radians.
+    pub look: [f32; 2]
+}
+
+/// Works out the motion for a step of `dt` seconds; the left stick moves and the right stick looks.
+/// This only depends on the state passed in, so it works the same with recorded or made up input
+/// as with a real device.
+pub fn gamepad_motion(state: &GamepadState, settings: &StickSettings, dt: f32) -> GamepadMotion {
+    let look = settings.shape(state.right_stick);
+    GamepadMotion {
+        movement: settings.shape(state.left_stick),
+        look: [look[0] * settings.look_speed * dt, look[1] * settings.look_speed * dt]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        assert!((actual[0] - expected[0]).abs() < 1e-5 && (actual[1] - expected[1]).abs() < 1e-5, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    fn moving(left_stick: [f32; 2]) -> GamepadState {
+        GamepadState { left_stick, ..GamepadState::default() }
+    }
+
+    #[test]
+    fn dead_zone() {
+        let settings = StickSettings::default();
+        assert_close(gamepad_motion(&moving([0.1, 0.1]), &settings, 1.).movement, [0., 0.]);
+        assert_close(gamepad_motion(&moving([0., 0.]), &settings, 1.).movement, [0., 0.]);
+
+        // The range past the dead zone starts at 0 instead of jumping
+        let edge = gamepad_motion(&moving([settings.dead_zone + 0.001, 0.]), &settings, 1.).movement;
+        assert!(edge[0] > 0. && edge[0] < 0.001);
+    }
+
+    #[test]
+    fn response_curve() {
+        let settings = StickSettings { dead_zone: 0., curve: 2., look_speed: 1. };
+        assert_close(gamepad_motion(&moving([0.5, 0.]), &settings, 1.).movement, [0.25, 0.]);
+        assert_close(gamepad_motion(&moving([-1., 0.]), &settings, 1.).movement, [-1., 0.]);
+
+        let linear = StickSettings { curve: 1., ..settings };
+        assert_close(gamepad_motion(&moving([0.5, 0.]), &linear, 1.).movement, [0.5, 0.]);
+    }
+
+    #[test]
+    fn diagonals_are_clamped() {
+        // Square gamepads report the corners past the unit circle
+        let settings = StickSettings { dead_zone: 0.1, curve: 1., look_speed: 1. };
+        let movement = gamepad_motion(&moving([1., 1.]), &settings, 1.).movement;
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(movement, [diagonal, diagonal]);
+    }
+
+    #[test]
+    fn look_keeps_the_stick_direction() {
+        // Pushing up looks up; `Gamepad` flips the device's Y axes when it reads them
+        let settings = StickSettings { dead_zone: 0., curve: 1., look_speed: 2. };
+        let state = GamepadState { right_stick: [0., 1.], ..GamepadState::default() };
+        assert_close(gamepad_motion(&state, &settings, 0.5).look, [0., 1.]);
+
+        let state = GamepadState { right_stick: [0., -1.], ..GamepadState::default() };
+        assert_close(gamepad_motion(&state, &settings, 0.5).look, [0., -1.]);
+        assert_close(gamepad_motion(&state, &settings, 0.5).movement, [0., 0.]);
+    }
+}
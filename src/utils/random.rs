@@ -0,0 +1,60 @@
+use crate::world;
+
+/// Small, deterministic xorshift random number generator. World generation needs the exact same
+/// numbers every time a chunk is regenerated, on every platform, so this is kept in-tree instead
+/// of depending on a crate whose algorithm could change between versions.
+#[derive(Clone)]
+pub struct Random {
+    state: u64
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Scramble the seed so similar seeds don't produce similar sequences; xorshift also can't
+        // have a state of zero
+        let state = Random::mix(seed);
+        Random { state: if state == 0 { 0x9E37_79B9_7F4A_7C15 } else { state } }
+    }
+
+    /// Creates a generator unique to a chunk, so generation doesn't depend on the order chunks are
+    /// loaded in. The salt separates different users of the same chunk (e.g. ores vs. trees).
+    pub fn for_chunk(seed: u32, index: &world::ChunkIndex, salt: u32) -> Random {
+        let mut hash = Random::mix(u64::from(seed) ^ (u64::from(salt) << 32));
+        hash = Random::mix(hash ^ u64::from(index.x));
+        hash = Random::mix(hash ^ u64::from(index.y));
+        hash = Random::mix(hash ^ u64::from(index.z));
+        Random::new(hash)
+    }
+
+    /// SplitMix64 finalizer; see http://xorshift.di.unimi.it/splitmix64.c
+    fn mix(value: u64) -> u64 {
+        let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns an integer in `[min, max)`; returns `min` if the range is empty.
+    pub fn range(&mut self, min: u32, max: u32) -> u32 {
+        if max <= min { return min; }
+        min + (self.next_u64() % u64::from(max - min)) as u32
+    }
+
+    /// Returns true with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
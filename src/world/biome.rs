@@ -0,0 +1,41 @@
+use std::fmt;
+
+/// The climate zone a column of the world belongs to. The delegate decides which biome is where;
+/// everything else should only ever query it through `World::biome_at`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Snowy,
+    Mountains
+}
+
+impl Biome {
+    pub const COUNT: usize = 5;
+
+    /// Every biome, in the same order as their discriminants so `biome as usize` indexes it.
+    pub const ALL: [Biome; Biome::COUNT] = [
+        Biome::Plains,
+        Biome::Forest,
+        Biome::Desert,
+        Biome::Snowy,
+        Biome::Mountains
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Biome::Plains => "plains",
+            Biome::Forest => "forest",
+            Biome::Desert => "desert",
+            Biome::Snowy => "snowy",
+            Biome::Mountains => "mountains"
+        }
+    }
+}
+
+impl fmt::Display for Biome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.name())
+    }
+}
@@ -1,34 +1,37 @@
+use std::fmt;
+
 pub type BlockSides = u8;  // 0b000000 flags for each side
 pub type BlockEdges = u32;  // 0b00000000000 flags for each edge
 pub type BlockCorners = u8;  // 0b0000000 flags for each corner
 
 lazy_static! {
-    static ref BLOCK_CONFIG: Vec<BlockConfig> = {  // TODO: Move this to a TOML file
-        let mut bc = Vec::new();
-
-        bc.push(BlockConfig::invisible("air"));
-        bc.push(BlockConfig::new("brick_stone", false, BlockConfig::texture_all((0, 0))));
-        bc.push(BlockConfig::new("rails_straight_wood", true, BlockConfig::texture_all((1, 0))));
-        bc.push(BlockConfig::new("stone_diamond", false, BlockConfig::texture_all((2, 0))));
-        bc.push(BlockConfig::new("furnace", false, BlockConfig::texture_sides((2, 5), (3, 0), (2, 5))));
-        bc.push(BlockConfig::new("stone_gravel", false, BlockConfig::texture_all((4, 0))));
-        bc.push(BlockConfig::new("dirt_grass", false, BlockConfig::texture_sides((4, 6), (5, 0), (5, 1))));
-        bc.push(BlockConfig::new("wood_red", false, BlockConfig::texture_all((0, 1))));
-        bc.push(BlockConfig::new("rails_curve", true, BlockConfig::texture_all((1, 1))));
-        bc.push(BlockConfig::new("stone_coal_alt", false, BlockConfig::texture_all((2, 1))));
-        bc.push(BlockConfig::new("mushroom_tan", true, BlockConfig::texture_all((3, 1))));
-        bc.push(BlockConfig::new("dirt_gravel", false, BlockConfig::texture_all((4, 1))));
-        bc.push(BlockConfig::new("dirt", false, BlockConfig::texture_all((5, 1))));
-        bc.push(BlockConfig::new("wood", false, BlockConfig::texture_all((0, 2))));
-        bc.push(BlockConfig::new("rails_curve_wood", true, BlockConfig::texture_all((1, 2))));
-        bc.push(BlockConfig::new("stone_coal", false, BlockConfig::texture_all((2, 2))));
-        bc.push(BlockConfig::new("mushroom_red", true, BlockConfig::texture_all((3, 2))));
-        bc.push(BlockConfig::new("grass_large", true, BlockConfig::texture_all((4, 2))));
-
-        bc.push(BlockConfig::new("stone", false, BlockConfig::texture_all((2, 5))));
-
-        bc
-    };
+    static ref BLOCK_CONFIG: Vec<BlockConfig> = vec![  // TODO: Move this to a TOML file
+        BlockConfig::invisible("air"),
+        BlockConfig::new("brick_stone", false, BlockConfig::texture_all((0, 0))),
+        BlockConfig::new("rails_straight_wood", true, BlockConfig::texture_all((1, 0))),
+        BlockConfig::new("stone_diamond", false, BlockConfig::texture_all((2, 0))),
+        BlockConfig::new("furnace", false, BlockConfig::texture_sides((2, 5), (3, 0), (2, 5))).emitting(13),
+        BlockConfig::new("stone_gravel", false, BlockConfig::texture_all((4, 0))),
+        BlockConfig::new("dirt_grass", false, BlockConfig::texture_sides((4, 6), (5, 0), (5, 1))),
+        BlockConfig::new("wood_red", false, BlockConfig::texture_all((0, 1))),
+        BlockConfig::new("rails_curve", true, BlockConfig::texture_all((1, 1))),
+        BlockConfig::new("stone_coal_alt", false, BlockConfig::texture_all((2, 1))),
+        BlockConfig::new("mushroom_tan", true, BlockConfig::texture_all((3, 1))),
+        BlockConfig::new("dirt_gravel", false, BlockConfig::texture_all((4, 1))),
+        BlockConfig::new("dirt", false, BlockConfig::texture_all((5, 1))),
+        BlockConfig::new("wood", false, BlockConfig::texture_all((0, 2))),
+        BlockConfig::new("rails_curve_wood", true, BlockConfig::texture_all((1, 2))),
+        BlockConfig::new("stone_coal", false, BlockConfig::texture_all((2, 2))),
+        BlockConfig::new("mushroom_red", true, BlockConfig::texture_all((3, 2))),
+        BlockConfig::new("grass_large", true, BlockConfig::texture_all((4, 2))),
+
+        BlockConfig::new("stone", false, BlockConfig::texture_all((2, 5))),
+        BlockConfig::new("sand", false, BlockConfig::texture_all((2, 7))),
+        BlockConfig::new("snow", false, BlockConfig::texture_all((2, 6))),
+        BlockConfig::new("dirt_sand", false, BlockConfig::texture_sides((2, 7), (4, 14), (5, 1))),
+        BlockConfig::new("dirt_snow", false, BlockConfig::texture_sides((2, 6), (4, 13), (5, 1))),
+        BlockConfig::new("leaves", false, BlockConfig::texture_all((3, 7)))
+    ];
 }
 
 type BlockTexturePosition = (usize, usize);
@@ -50,6 +53,9 @@ struct BlockConfig {
     /// The index of the texture to use on each side of the block. The indexes correspond to
     /// `Chunk::SIDE_DIRS`.
     textures: [BlockTexturePosition; 6],
+
+    /// The level of block light the block gives off, from 0 to `Light::MAX`.
+    light_emission: u8,
 }
 
 impl BlockConfig {
@@ -58,7 +64,8 @@ impl BlockConfig {
             name,
             is_invisible: false,
             is_transparent,
-            textures
+            textures,
+            light_emission: 0
         }
     }
 
@@ -67,10 +74,16 @@ impl BlockConfig {
             name,
             is_invisible: true,
             is_transparent: true,
-            textures: BlockConfig::texture_all((0, 0))
+            textures: BlockConfig::texture_all((0, 0)),
+            light_emission: 0
         }
     }
 
+    pub fn emitting(mut self, light_emission: u8) -> BlockConfig {
+        self.light_emission = light_emission;
+        self
+    }
+
     pub fn texture_all(position: BlockTexturePosition) -> [BlockTexturePosition; 6] {
         [position, position, position, position, position, position]
     }
@@ -87,23 +100,39 @@ impl Block {
     pub const AIR: Block = Block(0);
 
     pub fn from_id(id: &str) -> Block {
-        for (i, block) in BLOCK_CONFIG.iter().enumerate() {
-            if block.name == id {
-                return Block(i as u8);
-            }
+        match Block::try_from_id(id) {
+            Some(block) => block,
+            None => panic!("Unknown block ID {}", id)
         }
+    }
+
+    pub fn try_from_id(id: &str) -> Option<Block> {
+        BLOCK_CONFIG.iter().position(|block| block.name == id).map(|i| Block(i as u8))
+    }
+
+    /// Iterates over every registered block, including air.
+    pub fn all() -> impl Iterator<Item=Block> {
+        (0..BLOCK_CONFIG.len()).map(|i| Block(i as u8))
+    }
+}
 
-        panic!("Unknown block ID {}", id);
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "Block({})", self.id())
     }
 }
 
 impl Block {
     fn get_config(&self) -> &BlockConfig { &BLOCK_CONFIG[self.0 as usize] }
 
+    pub fn id(&self) -> &'static str { self.get_config().name }
+
     pub fn is_transparent(&self) -> bool { self.get_config().is_transparent }
 
     pub fn is_invisible(&self) -> bool { self.get_config().is_invisible }
 
+    pub fn light_emission(&self) -> u8 { self.get_config().light_emission }
+
     pub fn texture_pos(&self, side: usize) -> BlockTexturePosition {
         self.get_config().textures[side]
     }
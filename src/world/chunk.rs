@@ -2,6 +2,7 @@ use crate::world::block::Block;
 use crate::world::block::BlockSides;
 use crate::world::block::BlockEdges;
 use crate::world::block::BlockCorners;
+use crate::world::light::Light;
 use std::fmt;
 
 #[derive(Debug)]
@@ -29,12 +30,23 @@ type ChunkData = BlockDataArray<Block>;
 type BlockSidesData = BlockDataArray<BlockSides>;
 type BlockEdgeData = BlockDataArray<BlockEdges>;
 type BlockCornerData = BlockDataArray<BlockCorners>;
+type LightData = BlockDataArray<Light>;
 
 const N: DeltaDir = DeltaDir::Negative;
 const Z: DeltaDir = DeltaDir::Zero;
 const P: DeltaDir = DeltaDir::Positive;
 
 pub struct Chunk {
+    /// The block data; this is `None` until a visible block is placed, so chunks that are entirely
+    /// air (like the sky) don't allocate anything.
+    storage: Option<Box<ChunkStorage>>,
+
+    /// The light of each block; this is `None` while every block is lit by the open sky, which is
+    /// kept separate from the block data since sky chunks are empty but chunks in caves are dark.
+    light: Option<Box<LightData>>,
+}
+
+pub struct ChunkStorage {
     data: Box<ChunkData>,
     sides: Box<BlockSidesData>,
     edges: Box<BlockEdgeData>,
@@ -53,24 +65,68 @@ impl Chunk {
     pub const SIZE_Z_F32: f32 = Chunk::SIZE_Z as f32;
 
     pub fn empty() -> Chunk {
-        Chunk {
-            data: Box::new([[[Block::AIR; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X]),
-            sides: Box::new([[[0b000000; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X]),
-            edges: Box::new([[[0b00000000000; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X]),
-            corners: Box::new([[[0b0000000; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X])
-        }
+        Chunk { storage: None, light: None }
     }
-    #[allow(dead_code)] // TODO: Remove
+
     pub fn get_block(&self, position: &ChunkBlockIndex) -> &Block {
-        &self.data[position.x][position.y][position.z]
+        match self.storage {
+            Some(ref storage) => &storage.data[position.x][position.y][position.z],
+            None => &Block::AIR
+        }
     }
 
     pub fn set_block(&mut self, position: &ChunkBlockIndex, block: Block) {
-        self.data[position.x][position.y][position.z] = block;
+        // Don't allocate just to store air
+        if self.storage.is_none() && block == Block::AIR { return; }
+
+        self.storage_mut().data[position.x][position.y][position.z] = block;
+    }
+
+    /// Sets the block only if the existing block is invisible. Returns if the block was placed.
+    pub fn set_block_if_invisible(&mut self, position: &ChunkBlockIndex, block: Block) -> bool {
+        if !self.get_block(position).is_invisible() { return false; }
+
+        self.set_block(position, block);
+        true
+    }
+
+    pub fn get_light(&self, position: &ChunkBlockIndex) -> Light {
+        match self.light {
+            Some(ref light) => light[position.x][position.y][position.z],
+            None => Light::SKY
+        }
+    }
+
+    pub fn set_light(&mut self, position: &ChunkBlockIndex, light: Light) {
+        // Don't allocate just to store sunlight
+        if self.light.is_none() && light == Light::SKY { return; }
+
+        let data = self.light.get_or_insert_with(|| Box::new([[[Light::SKY; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X]));
+        data[position.x][position.y][position.z] = light;
+    }
+
+    /// Returns the block data, or `None` if the chunk is entirely air.
+    pub fn storage(&self) -> Option<&ChunkStorage> {
+        self.storage.as_deref()
+    }
+
+    fn storage_mut(&mut self) -> &mut ChunkStorage {
+        self.storage.get_or_insert_with(|| Box::new(ChunkStorage::new()))
     }
 }
 
-impl Chunk {
+impl ChunkStorage {
+    fn new() -> ChunkStorage {
+        ChunkStorage {
+            data: Box::new([[[Block::AIR; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X]),
+            sides: Box::new([[[0b000000; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X]),
+            edges: Box::new([[[0b00000000000; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X]),
+            corners: Box::new([[[0b0000000; Chunk::SIZE_Z]; Chunk::SIZE_Y]; Chunk::SIZE_X])
+        }
+    }
+}
+
+impl ChunkStorage {
     pub fn data(&self) -> &ChunkData {
         &self.data
     }
@@ -89,13 +145,18 @@ impl Chunk {
 }
 
 impl Chunk {
-    pub fn block_count(&self) -> u16 {
+    pub fn block_count(&self) -> u32 {
+        let storage = match self.storage {
+            Some(ref storage) => storage,
+            None => return 0
+        };
+
         // Count each non-invisible block
         let mut count = 0;
         for x in 0..Chunk::SIZE_X {
             for y in 0..Chunk::SIZE_Y {
                 for z in 0..Chunk::SIZE_Z {
-                    if !self.data[x][y][z].is_invisible() {
+                    if !storage.data[x][y][z].is_invisible() {
                         count += 1;
                     }
                 }
@@ -143,17 +204,29 @@ impl Chunk {
         [N, P, P],  // 7: LTF
     ];
 
-    pub fn process_sides(&mut self) -> () {  // TODO: Rename this to `clean_sides` and make `process_sides_for_index` get called every time a block changes
+    /// Finds which sides, edges, and corners of each block are exposed. `neighbor_block` returns the
+    /// blocks just outside of the chunk, given a position relative to the chunk, or `None` if that
+    /// chunk isn't loaded.
+    pub fn process_sides<F>(&mut self, neighbor_block: F) where F: Fn(i64, i64, i64) -> Option<Block> {  // TODO: Rename this to `clean_sides` and make `process_sides_for_index` get called every time a block changes
+        // Nothing to show in an empty chunk
+        if let Some(ref mut storage) = self.storage {
+            storage.process_sides(&neighbor_block);
+        }
+    }
+}
+
+impl ChunkStorage {
+    fn process_sides<F>(&mut self, neighbor_block: &F) where F: Fn(i64, i64, i64) -> Option<Block> {
         for x in 0..Chunk::SIZE_X {
             for y in 0..Chunk::SIZE_Y {
                 for z in 0..Chunk::SIZE_Z {
-                    self.process_sides_for_index(x, y, z);
+                    self.process_sides_for_index(x, y, z, neighbor_block);
                 }
             }
         }
     }
 
-    fn process_sides_for_index(&mut self, x: usize, y: usize, z: usize) {
+    fn process_sides_for_index<F>(&mut self, x: usize, y: usize, z: usize, neighbor_block: &F) where F: Fn(i64, i64, i64) -> Option<Block> {
         let mut sides = 0b000000;
         let mut edges = 0b00000000000;
         let mut corners = 0b00000000;
@@ -163,15 +236,14 @@ impl Chunk {
             for side in 0..6 {
                 let dir = &Chunk::SIDE_DIRS[side];
 
-                if let Some(block) = self.get_block_from_dir(x, y, z, dir) {
+                if let Some(block) = self.get_block_from_dir(x, y, z, dir, neighbor_block) {
                     // Show the side if there is no visible block there
                     if block.is_transparent() {
                         sides |= 1 << side;
                     }
                 } else {
-                    // TODO: Need to check the next chunk over if it's at an edge
-                    // HACK: Show the edge of the chunk by default so we don't have to check the
-                    // block in the next chunk over
+                    // Show the side if the next chunk over isn't loaded yet; this chunk gets
+                    // processed again once it is
                     sides |= 1 << side;
                 }
             }
@@ -180,13 +252,13 @@ impl Chunk {
             for edge in 0..12 {
                 let dir = &Chunk::EDGE_DIRS[edge];
 
-                if let Some(block) = self.get_block_from_dir(x, y, z, dir) {
+                if let Some(block) = self.get_block_from_dir(x, y, z, dir, neighbor_block) {
                     // Show the edge if there is no visible block there
                     if block.is_transparent() {
                         edges |= 1 << edge;
                     }
                 } else {
-                    // HACK: See above
+                    // See above
                     edges |= 1 << edge;
                 }
             }
@@ -195,13 +267,13 @@ impl Chunk {
             for corner in 0..8 {
                 let dir = &Chunk::CORNER_DIRS[corner];
 
-                if let Some(block) = self.get_block_from_dir(x, y, z, dir) {
+                if let Some(block) = self.get_block_from_dir(x, y, z, dir, neighbor_block) {
                     // Show the edge if there is no visible block there
                     if block.is_transparent() {
                         corners |= 1 << corner;
                     }
                 } else {
-                    // HACK: See above
+                    // See above
                     corners |= 1 << corner;
                 }
             }
@@ -213,17 +285,20 @@ impl Chunk {
         self.corners[x][y][z] = corners as u8;
     }
 
-    fn get_block_from_dir(&self, x: usize, y: usize, z: usize, dir: &[DeltaDir; 3]) -> Option<Block> {
+    fn get_block_from_dir<F>(&self, x: usize, y: usize, z: usize, dir: &[DeltaDir; 3], neighbor_block: &F) -> Option<Block> where F: Fn(i64, i64, i64) -> Option<Block> {
         // Get the new X, Y, and Z position
-        let dx = if let Some(x) = dir[0].checked_add_usize(x) { x } else { return None; };
-        let dy = if let Some(y) = dir[1].checked_add_usize(y) { y } else { return None; };
-        let dz = if let Some(z) = dir[2].checked_add_usize(z) { z } else { return None; };
-
-        // Check that the block is not outside of the chunk
-        if dx >= Chunk::SIZE_X || dy >= Chunk::SIZE_Y || dz >= Chunk::SIZE_Z { return None; }
+        let dx = x as i64 + dir[0].delta();
+        let dy = y as i64 + dir[1].delta();
+        let dz = z as i64 + dir[2].delta();
+
+        // Look in the next chunk over if the block is outside of this one
+        let in_chunk = |value: i64, size: usize| value >= 0 && value < size as i64;
+        if !in_chunk(dx, Chunk::SIZE_X) || !in_chunk(dy, Chunk::SIZE_Y) || !in_chunk(dz, Chunk::SIZE_Z) {
+            return neighbor_block(dx, dy, dz);
+        }
 
         // Return the block
-        Some(self.data[dx][dy][dz])
+        Some(self.data[dx as usize][dy as usize][dz as usize])
     }
 }
 
@@ -233,11 +308,11 @@ enum DeltaDir {
 }
 
 impl DeltaDir {
-    fn checked_add_usize(&self, base: usize) -> Option<usize> {
+    fn delta(&self) -> i64 {
         match self {
-            DeltaDir::Negative => base.checked_sub(1),
-            DeltaDir::Zero => Some(base),
-            DeltaDir::Positive => base.checked_add(1)
+            DeltaDir::Negative => -1,
+            DeltaDir::Zero => 0,
+            DeltaDir::Positive => 1
         }
     }
 }
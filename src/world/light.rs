@@ -0,0 +1,394 @@
+use super::Block;
+use super::Chunk;
+use super::ChunkBlockIndex;
+use super::ChunkIndex;
+use super::World;
+use super::WorldBlockIndex;
+use std::collections::VecDeque;
+
+/// The two kinds of light a block holds. Sky light comes straight down from the sky without getting
+/// any dimmer, block light comes from blocks like furnaces. Both get one level dimmer with each
+/// block they spread through.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LightChannel {
+    Sky,
+    Block
+}
+
+impl LightChannel {
+    const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+    /// Returns the level of light that spreads to the next block over.
+    fn spread_level(self, level: u8, down: bool) -> u8 {
+        // Sunlight goes straight down forever
+        if self == LightChannel::Sky && down && level == Light::MAX {
+            Light::MAX
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+/// The sky and block light levels of a block, packed in to a byte.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Light(u8);
+
+impl Light {
+    pub const MAX: u8 = 15;
+
+    /// No light at all.
+    pub const DARK: Light = Light(0);
+
+    /// Full sunlight and no block light; this is the light of the open sky.
+    pub const SKY: Light = Light(Light::MAX << 4);
+
+    pub fn get(self, channel: LightChannel) -> u8 {
+        match channel {
+            LightChannel::Sky => self.0 >> 4,
+            LightChannel::Block => self.0 & 0xF
+        }
+    }
+
+    pub fn with(self, channel: LightChannel, level: u8) -> Light {
+        let level = level.min(Light::MAX);
+        match channel {
+            LightChannel::Sky => Light((self.0 & 0xF) | level << 4),
+            LightChannel::Block => Light((self.0 & 0xF0) | level)
+        }
+    }
+}
+
+/// Looks up blocks and light around a chunk, including the blocks just over its borders, without
+/// looking up the neighboring chunks for every block.
+pub struct LightSampler<'a> {
+    /// The chunk and its neighbors, indexed by `x + y * 3 + z * 9` where 1 is the chunk itself.
+    chunks: [Option<&'a Chunk>; 27]
+}
+
+impl<'a> LightSampler<'a> {
+    /// Returns the block and light at a position relative to the chunk, which can be up to one
+    /// chunk outside of it. Returns `None` if that chunk isn't loaded.
+    pub fn get(&self, x: i64, y: i64, z: i64) -> Option<(Block, Light)> {
+        let (chunk_x, x) = LightSampler::split(x, Chunk::SIZE_X);
+        let (chunk_y, y) = LightSampler::split(y, Chunk::SIZE_Y);
+        let (chunk_z, z) = LightSampler::split(z, Chunk::SIZE_Z);
+        let chunk = self.chunks[chunk_x + chunk_y * 3 + chunk_z * 9]?;
+
+        let index = ChunkBlockIndex::new(x, y, z);
+        Some((*chunk.get_block(&index), chunk.get_light(&index)))
+    }
+
+    /// Splits a position in to which of the three chunks it's in and the position in that chunk.
+    fn split(value: i64, size: usize) -> (usize, usize) {
+        let size = size as i64;
+        if value < 0 {
+            (0, (value + size) as usize)
+        } else if value >= size {
+            (2, (value - size) as usize)
+        } else {
+            (1, value as usize)
+        }
+    }
+}
+
+impl World {
+    const NEIGHBORS: [[i64; 3]; 6] = [
+        [ 1,  0,  0],
+        [-1,  0,  0],
+        [ 0,  1,  0],
+        [ 0, -1,  0],
+        [ 0,  0,  1],
+        [ 0,  0, -1],  // Down
+    ];
+
+    pub fn light_sampler(&self, index: &ChunkIndex) -> LightSampler<'_> {
+        let mut chunks = [None; 27];
+        for (i, chunk) in chunks.iter_mut().enumerate() {
+            let offset = [(i % 3) as i32 - 1, (i / 3 % 3) as i32 - 1, (i / 9) as i32 - 1];
+            *chunk = index.offset(0, offset[0])
+                .and_then(|index| index.offset(1, offset[1]))
+                .and_then(|index| index.offset(2, offset[2]))
+                .and_then(|index| self.chunks.get(&index));
+        }
+
+        LightSampler { chunks }
+    }
+
+    /// Lights a chunk that was just created and spreads its light in to the loaded chunks around
+    /// it. Chunks above that aren't loaded yet are assumed to be open sky; if that turns out to be
+    /// wrong, the sunlight gets taken back out when they load.
+    pub fn light_chunk(&mut self, index: &ChunkIndex) {
+        let origin = [
+            index.x as i64 * Chunk::SIZE_X as i64,
+            index.y as i64 * Chunk::SIZE_Y as i64,
+            index.z as i64 * Chunk::SIZE_Z as i64
+        ];
+        let to_world = |x: usize, y: usize, z: usize| [origin[0] + x as i64, origin[1] + y as i64, origin[2] + z as i64];
+
+        // Find which columns get sunlight from the chunk above
+        let mut sunlit = [[true; Chunk::SIZE_Y]; Chunk::SIZE_X];
+        if let Some(above) = index.offset(2, 1).and_then(|above| self.chunks.get(&above)) {
+            for (x, column) in sunlit.iter_mut().enumerate() {
+                for (y, sunlit) in column.iter_mut().enumerate() {
+                    *sunlit = above.get_light(&ChunkBlockIndex::new(x, y, 0)).get(LightChannel::Sky) == Light::MAX;
+                }
+            }
+        }
+
+        // Shine the sunlight down each column and light up the blocks that emit light
+        let mut sky_spread = VecDeque::new();
+        let mut block_spread = VecDeque::new();
+        let chunk = self.chunks.get_mut(index).unwrap();
+        for (x, row) in sunlit.iter().enumerate() {
+            for (y, &sunlit) in row.iter().enumerate() {
+                let mut sun = sunlit;
+                for z in (0..Chunk::SIZE_Z).rev() {
+                    let position = ChunkBlockIndex::new(x, y, z);
+                    let block = *chunk.get_block(&position);
+                    if !block.is_transparent() { sun = false; }
+
+                    let mut light = Light::DARK;
+                    if sun {
+                        light = light.with(LightChannel::Sky, Light::MAX);
+                    }
+                    if block.light_emission() > 0 {
+                        light = light.with(LightChannel::Block, block.light_emission());
+                        block_spread.push_back(to_world(x, y, z));
+                    }
+                    chunk.set_light(&position, light);
+                }
+            }
+        }
+
+        // Spread the sunlight sideways in to the shade, like under overhangs and in to caves
+        for x in 0..Chunk::SIZE_X {
+            for y in 0..Chunk::SIZE_Y {
+                for z in 0..Chunk::SIZE_Z {
+                    if chunk.get_light(&ChunkBlockIndex::new(x, y, z)).get(LightChannel::Sky) != Light::MAX { continue; }
+
+                    let is_shaded = |x: usize, y: usize| {
+                        let index = ChunkBlockIndex::new(x, y, z);
+                        chunk.get_block(&index).is_transparent() && chunk.get_light(&index).get(LightChannel::Sky) < Light::MAX
+                    };
+                    let shaded_neighbor =
+                        (x > 0 && is_shaded(x - 1, y)) || (x + 1 < Chunk::SIZE_X && is_shaded(x + 1, y)) ||
+                        (y > 0 && is_shaded(x, y - 1)) || (y + 1 < Chunk::SIZE_Y && is_shaded(x, y + 1));
+                    if shaded_neighbor {
+                        sky_spread.push_back(to_world(x, y, z));
+                    }
+                }
+            }
+        }
+
+        // Take back the sunlight the chunk below assumed it was getting from the open sky
+        let mut sky_removal = VecDeque::new();
+        if let Some(below_index) = index.offset(2, -1) {
+            let chunk = &self.chunks[index];
+            if let Some(below) = self.chunks.get(&below_index) {
+                for x in 0..Chunk::SIZE_X {
+                    for y in 0..Chunk::SIZE_Y {
+                        let top = ChunkBlockIndex::new(x, y, Chunk::SIZE_Z - 1);
+                        let was_sunlit = below.get_light(&top).get(LightChannel::Sky) == Light::MAX;
+                        let is_sunlit = chunk.get_light(&ChunkBlockIndex::new(x, y, 0)).get(LightChannel::Sky) == Light::MAX;
+                        if was_sunlit && !is_sunlit {
+                            let [x, y, z] = to_world(x, y, 0);
+                            sky_removal.push_back(([x, y, z - 1], Light::MAX));
+                        }
+                    }
+                }
+            }
+        }
+        for &(position, _) in sky_removal.iter() {
+            self.set_light_channel(position, LightChannel::Sky, 0);
+        }
+        self.remove_light(LightChannel::Sky, sky_removal, &mut sky_spread);
+
+        // Spread light across the borders with the loaded chunks around it
+        for axis in 0..3 {
+            for &delta in [-1, 1].iter() {
+                let neighbor_index = match index.offset(axis, delta) {
+                    Some(neighbor_index) if self.chunks.contains_key(&neighbor_index) => neighbor_index,
+                    _ => continue
+                };
+                self.seed_border(index, &neighbor_index, axis, delta, &mut sky_spread, &mut block_spread);
+            }
+        }
+
+        self.spread_light(LightChannel::Sky, sky_spread);
+        self.spread_light(LightChannel::Block, block_spread);
+    }
+
+    /// Updates the light after blocks changed whether they let light through or how much light
+    /// they give off.
+    pub fn relight_blocks(&mut self, indices: &[WorldBlockIndex]) {
+        for &channel in LightChannel::ALL.iter() {
+            // Take out the light that was shining through the blocks
+            let mut removal = VecDeque::new();
+            let mut spread = VecDeque::new();
+            for index in indices {
+                let position = [index.x as i64, index.y as i64, index.z as i64];
+                if let Some((_, light)) = self.light_at(position) {
+                    removal.push_back((position, light.get(channel)));
+                    self.set_light_channel(position, channel, 0);
+                }
+            }
+            self.remove_light(channel, removal, &mut spread);
+
+            // Add the light back in
+            for index in indices {
+                let position = [index.x as i64, index.y as i64, index.z as i64];
+                let block = match self.light_at(position) {
+                    Some((block, _)) => block,
+                    None => continue
+                };
+
+                if channel == LightChannel::Block && block.light_emission() > 0 {
+                    self.set_light_channel(position, channel, block.light_emission());
+                    spread.push_back(position);
+                }
+                if block.is_transparent() {
+                    // Let the light around the block flow back in to it
+                    for neighbor in World::NEIGHBORS.iter() {
+                        spread.push_back([position[0] + neighbor[0], position[1] + neighbor[1], position[2] + neighbor[2]]);
+                    }
+                }
+            }
+            self.spread_light(channel, spread);
+        }
+    }
+
+    /// Queues the blocks on both sides of the border between two chunks that will light up the
+    /// other side.
+    fn seed_border(&self, index: &ChunkIndex, neighbor_index: &ChunkIndex, axis: usize, delta: i32, sky_spread: &mut VecDeque<[i64; 3]>, block_spread: &mut VecDeque<[i64; 3]>) {
+        let chunk = &self.chunks[index];
+        let neighbor = &self.chunks[neighbor_index];
+        let sizes = [Chunk::SIZE_X, Chunk::SIZE_Y, Chunk::SIZE_Z];
+        let origin = [
+            index.x as i64 * Chunk::SIZE_X as i64,
+            index.y as i64 * Chunk::SIZE_Y as i64,
+            index.z as i64 * Chunk::SIZE_Z as i64
+        ];
+
+        // Walk the layer of blocks on each side of the border
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let (layer, neighbor_layer) = if delta > 0 { (sizes[axis] - 1, 0) } else { (0, sizes[axis] - 1) };
+        for i in 0..sizes[a] {
+            for j in 0..sizes[b] {
+                let mut position = [0; 3];
+                position[axis] = layer;
+                position[a] = i;
+                position[b] = j;
+                let mut neighbor_position = position;
+                neighbor_position[axis] = neighbor_layer;
+
+                let chunk_block_index = ChunkBlockIndex::new(position[0], position[1], position[2]);
+                let neighbor_block_index = ChunkBlockIndex::new(neighbor_position[0], neighbor_position[1], neighbor_position[2]);
+                let (block, light) = (*chunk.get_block(&chunk_block_index), chunk.get_light(&chunk_block_index));
+                let (neighbor_block, neighbor_light) = (*neighbor.get_block(&neighbor_block_index), neighbor.get_light(&neighbor_block_index));
+
+                let world_position = [origin[0] + position[0] as i64, origin[1] + position[1] as i64, origin[2] + position[2] as i64];
+                let mut neighbor_world_position = world_position;
+                neighbor_world_position[axis] += delta as i64;
+
+                for &channel in LightChannel::ALL.iter() {
+                    let queue = if channel == LightChannel::Sky { &mut *sky_spread } else { &mut *block_spread };
+
+                    // Queue whichever side is brighter than what's on the other side
+                    let down = axis == 2 && delta < 0;
+                    if neighbor_block.is_transparent() && neighbor_light.get(channel) < channel.spread_level(light.get(channel), down) {
+                        queue.push_back(world_position);
+                    }
+                    let up = axis == 2 && delta > 0;
+                    if block.is_transparent() && light.get(channel) < channel.spread_level(neighbor_light.get(channel), up) {
+                        queue.push_back(neighbor_world_position);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Spreads light out from each queued block in to the transparent blocks around it that are
+    /// darker. Unloaded chunks are skipped; they pick up the light when they're created.
+    fn spread_light(&mut self, channel: LightChannel, mut queue: VecDeque<[i64; 3]>) {
+        while let Some(position) = queue.pop_front() {
+            let level = match self.light_at(position) {
+                Some((_, light)) => light.get(channel),
+                None => continue
+            };
+            if level <= 1 { continue; }
+
+            for (i, neighbor) in World::NEIGHBORS.iter().enumerate() {
+                let neighbor = [position[0] + neighbor[0], position[1] + neighbor[1], position[2] + neighbor[2]];
+                let (block, light) = match self.light_at(neighbor) {
+                    Some(value) => value,
+                    None => continue
+                };
+
+                let new_level = channel.spread_level(level, i == 5);
+                if block.is_transparent() && light.get(channel) < new_level {
+                    self.set_light_channel(neighbor, channel, new_level);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Darkens the blocks that were lit by the removed light, which has already been set to zero.
+    /// The blocks around the darkened area that are lit by something else get added to `spread` so
+    /// their light can flow back in.
+    fn remove_light(&mut self, channel: LightChannel, mut queue: VecDeque<([i64; 3], u8)>, spread: &mut VecDeque<[i64; 3]>) {
+        while let Some((position, level)) = queue.pop_front() {
+            for (i, neighbor) in World::NEIGHBORS.iter().enumerate() {
+                let neighbor = [position[0] + neighbor[0], position[1] + neighbor[1], position[2] + neighbor[2]];
+                let neighbor_level = match self.light_at(neighbor) {
+                    Some((_, light)) => light.get(channel),
+                    None => continue
+                };
+                if neighbor_level == 0 { continue; }
+
+                // Anything dimmer was lit by the removed light, as is the sunlight straight below it
+                let is_sunlight_below = channel == LightChannel::Sky && i == 5 && level == Light::MAX;
+                if neighbor_level < level || is_sunlight_below {
+                    self.set_light_channel(neighbor, channel, 0);
+                    queue.push_back((neighbor, neighbor_level));
+                } else {
+                    spread.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Returns the block and light at a world position, or `None` if it's outside of the world or
+    /// in a chunk that isn't loaded.
+    fn light_at(&self, position: [i64; 3]) -> Option<(Block, Light)> {
+        let index = World::light_index(position)?;
+        let chunk = self.chunks.get(&index.get_chunk_index())?;
+        let chunk_block_index = index.get_chunk_block_index();
+        Some((*chunk.get_block(&chunk_block_index), chunk.get_light(&chunk_block_index)))
+    }
+
+    fn set_light_channel(&mut self, position: [i64; 3], channel: LightChannel, level: u8) {
+        let index = match World::light_index(position) {
+            Some(index) => index,
+            None => return
+        };
+        let chunk_index = index.get_chunk_index();
+        let chunk_block_index = index.get_chunk_block_index();
+        let chunk = match self.chunks.get_mut(&chunk_index) {
+            Some(chunk) => chunk,
+            None => return
+        };
+
+        let light = chunk.get_light(&chunk_block_index);
+        if light.get(channel) == level { return; }
+        chunk.set_light(&chunk_block_index, light.with(channel, level));
+        self.mark_modified(&chunk_index, &chunk_block_index);
+    }
+
+    fn light_index(position: [i64; 3]) -> Option<WorldBlockIndex> {
+        let in_world = |value: i64| value >= 0 && value <= i64::from(u32::MAX);
+        if !position.iter().all(|&value| in_world(value)) { return None; }
+
+        Some(WorldBlockIndex::new(position[0] as u32, position[1] as u32, position[2] as u32))
+    }
+}
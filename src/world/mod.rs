@@ -1,12 +1,22 @@
+mod biome;
 mod block;
 mod chunk;
+mod light;
+mod player;
+mod raycast;
 
+pub use biome::*;
 pub use chunk::*;
 pub use block::*;
+pub use light::*;
+pub use player::*;
+pub use raycast::*;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt;
+use std::ops::RangeInclusive;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct WorldBlockIndex {
     pub x: u32,
     pub y: u32,
@@ -53,6 +63,19 @@ impl ChunkIndex {
     pub fn new(x: u32, y: u32, z: u32) -> ChunkIndex {
         ChunkIndex { x, y, z }
     }
+
+    /// Returns the index moved along an axis (0 is X, 1 is Y, 2 is Z), or `None` if it would leave
+    /// the world.
+    pub fn offset(&self, axis: usize, delta: i32) -> Option<ChunkIndex> {
+        let mut index = self.clone();
+        let value = match axis {
+            0 => &mut index.x,
+            1 => &mut index.y,
+            _ => &mut index.z
+        };
+        *value = if delta < 0 { value.checked_sub(delta.unsigned_abs())? } else { value.checked_add(delta as u32)? };
+        Some(index)
+    }
 }
 
 impl fmt::Display for ChunkIndex {
@@ -61,42 +84,285 @@ impl fmt::Display for ChunkIndex {
     }
 }
 
+/// A block the delegate wants to place outside of the chunk it's generating, such as the leaves of a
+/// tree that hang over a chunk border. These only ever replace invisible blocks so they can't eat in
+/// to terrain, regardless of which chunk happened to generate first.
+pub struct DeferredBlock {
+    pub index: WorldBlockIndex,
+    pub block: Block
+}
+
 pub trait WorldDelegate {
-    fn create_chunk(&self, index: &ChunkIndex) -> Chunk;
+    fn create_chunk(&self, index: &ChunkIndex, deferred: &mut Vec<DeferredBlock>) -> Chunk;
+
+    /// Returns the biome of the column at the given world X and Y, if the delegate has biomes.
+    fn biome_at(&self, _x: u32, _y: u32) -> Option<Biome> { None }
+
+    /// Returns the world Z of the top of the terrain at the given world X and Y, if the delegate
+    /// knows it without generating the chunk.
+    fn surface_height(&self, _x: u32, _y: u32) -> Option<u32> { None }
 }
 
 pub struct World {
     chunks: HashMap<ChunkIndex, Chunk>,
-    delegate: Box<WorldDelegate>
+    delegate: Box<dyn WorldDelegate>,
+
+    /// Deferred blocks waiting for their chunk to be created.
+    pending_blocks: HashMap<ChunkIndex, Vec<DeferredBlock>>,
+
+    /// Chunks that changed after they were created and need to be rendered again.
+    modified_chunks: HashSet<ChunkIndex>
 }
 
 impl World {
     #[allow(dead_code)]  // TODO: Remove
     pub const WORLD_CENTER_INDEX: u32 = 2147483648;  // (2 ^ 32) / 2
 
-    pub fn new(delegate: Box<WorldDelegate>) -> World {
+    pub fn new(delegate: Box<dyn WorldDelegate>) -> World {
         World {
             chunks: HashMap::new(),
-            delegate
+            delegate,
+            pending_blocks: HashMap::new(),
+            modified_chunks: HashSet::new()
         }
     }
 
     pub fn get_or_create_chunk(&mut self, index: &ChunkIndex) -> &mut Chunk {
         // Create new chunk if needed
-        if !self.chunks.contains_key(&index) {
+        if !self.chunks.contains_key(index) {
             // Create the chunk
-            let chunk = self.delegate.create_chunk(index);
+            let mut deferred = Vec::new();
+            let mut chunk = self.delegate.create_chunk(index, &mut deferred);
+
+            // Apply blocks that neighboring chunks placed in this chunk before it existed
+            if let Some(pending) = self.pending_blocks.remove(index) {
+                for deferred_block in pending {
+                    chunk.set_block_if_invisible(&deferred_block.index.get_chunk_block_index(), deferred_block.block);
+                }
+            }
 
             // Insert the chunk
             self.chunks.insert(index.clone(), chunk);
+
+            // Light the chunk
+            self.light_chunk(index);
+
+            // Neighboring chunks read this chunk's blocks and light along their borders
+            for neighbor in World::neighbor_chunks(index, [-1..=1, -1..=1, -1..=1]) {
+                if self.chunks.get(&neighbor).is_some_and(|chunk| chunk.storage().is_some()) {
+                    self.modified_chunks.insert(neighbor);
+                }
+            }
+
+            // Place the blocks that spilled over in to other chunks
+            self.place_deferred_blocks(deferred);
+        }
+
+        self.chunks.get_mut(index).unwrap()
+    }
+
+    pub fn get_chunk(&self, index: &ChunkIndex) -> Option<&Chunk> {
+        self.chunks.get(index)
+    }
+
+    /// Returns the number of chunks that have been generated.
+    pub fn get_chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the number of deferred blocks waiting for their chunk to be created.
+    pub fn get_pending_block_count(&self) -> usize {
+        self.pending_blocks.values().map(|blocks| blocks.len()).sum()
+    }
+
+    /// Finds the exposed sides of the blocks in a chunk, looking at the blocks in the neighboring
+    /// chunks along its borders.
+    pub fn process_sides(&mut self, index: &ChunkIndex) {
+        // Take the chunk out so the neighbors can be read while it's changed
+        let mut chunk = match self.chunks.remove(index) {
+            Some(chunk) => chunk,
+            None => return
+        };
+        {
+            let sampler = self.light_sampler(index);
+            chunk.process_sides(|x, y, z| sampler.get(x, y, z).map(|(block, _)| block));
         }
+        self.chunks.insert(index.clone(), chunk);
+    }
 
-        self.chunks.get_mut(&index).unwrap()
+    /// Returns the block at the index without generating the chunk; unloaded chunks are air.
+    pub fn get_block(&self, index: &WorldBlockIndex) -> Block {
+        match self.get_chunk(&index.get_chunk_index()) {
+            Some(chunk) => *chunk.get_block(&index.get_chunk_block_index()),
+            None => Block::AIR
+        }
     }
 
     pub fn set_block(&mut self, index: &WorldBlockIndex, block: Block) {
-        let chunk = self.get_or_create_chunk(&index.get_chunk_index());
-        chunk.set_block(&index.get_chunk_block_index(), block);
+        let chunk_index = index.get_chunk_index();
+        let chunk_block_index = index.get_chunk_block_index();
+        let chunk = self.get_or_create_chunk(&chunk_index);
+        let old_block = *chunk.get_block(&chunk_block_index);
+        chunk.set_block(&chunk_block_index, block);
+        self.mark_modified(&chunk_index, &chunk_block_index);
+
+        // Update the light if the block lets a different amount through
+        if World::changes_light(old_block, block) {
+            self.relight_blocks(std::slice::from_ref(index));
+        }
+    }
+
+    /// Sets every block in the box between two corners, which must be sorted so the first is the
+    /// lowest. Unlike setting each block, every chunk is only marked once and the light is updated
+    /// once for the whole box.
+    pub fn fill_box(&mut self, block: Block, lower: &WorldBlockIndex, upper: &WorldBlockIndex) {
+        // Create the chunks first, so the blocks they place in their neighbors can't land in the
+        // box after it's filled
+        let (lower_chunk, upper_chunk) = (lower.get_chunk_index(), upper.get_chunk_index());
+        let mut chunk_indices = Vec::new();
+        for x in lower_chunk.x..=upper_chunk.x {
+            for y in lower_chunk.y..=upper_chunk.y {
+                for z in lower_chunk.z..=upper_chunk.z {
+                    let chunk_index = ChunkIndex::new(x, y, z);
+                    self.get_or_create_chunk(&chunk_index);
+                    chunk_indices.push(chunk_index);
+                }
+            }
+        }
+
+        let mut relight = Vec::new();
+        for chunk_index in chunk_indices {
+            // Clip the box to the chunk
+            let origin = [chunk_index.x * Chunk::SIZE_X_U32, chunk_index.y * Chunk::SIZE_Y_U32, chunk_index.z * Chunk::SIZE_Z_U32];
+            let chunk_lower = ChunkBlockIndex::new(
+                (lower.x.max(origin[0]) - origin[0]) as usize,
+                (lower.y.max(origin[1]) - origin[1]) as usize,
+                (lower.z.max(origin[2]) - origin[2]) as usize
+            );
+            let chunk_upper = ChunkBlockIndex::new(
+                (upper.x.min(origin[0] + Chunk::SIZE_X_U32 - 1) - origin[0]) as usize,
+                (upper.y.min(origin[1] + Chunk::SIZE_Y_U32 - 1) - origin[1]) as usize,
+                (upper.z.min(origin[2] + Chunk::SIZE_Z_U32 - 1) - origin[2]) as usize
+            );
+
+            // Set the blocks, keeping the ones that change the light
+            let chunk = self.chunks.get_mut(&chunk_index).unwrap();
+            for x in chunk_lower.x..=chunk_upper.x {
+                for y in chunk_lower.y..=chunk_upper.y {
+                    for z in chunk_lower.z..=chunk_upper.z {
+                        let chunk_block_index = ChunkBlockIndex::new(x, y, z);
+                        let old_block = *chunk.get_block(&chunk_block_index);
+                        chunk.set_block(&chunk_block_index, block);
+                        if World::changes_light(old_block, block) {
+                            relight.push(WorldBlockIndex::new(origin[0] + x as u32, origin[1] + y as u32, origin[2] + z as u32));
+                        }
+                    }
+                }
+            }
+
+            self.mark_box_modified(&chunk_index, &chunk_lower, &chunk_upper);
+        }
+
+        if !relight.is_empty() {
+            self.relight_blocks(&relight);
+        }
+    }
+
+    /// Returns if replacing a block changes how much light goes through it or comes from it.
+    fn changes_light(old_block: Block, block: Block) -> bool {
+        old_block.is_transparent() != block.is_transparent() || old_block.light_emission() != block.light_emission()
+    }
+
+    /// Marks a chunk to be rendered again after a block in it changed, along with the neighboring
+    /// chunks if the block is on the edge.
+    fn mark_modified(&mut self, chunk_index: &ChunkIndex, chunk_block_index: &ChunkBlockIndex) {
+        self.mark_box_modified(chunk_index, chunk_block_index, chunk_block_index);
+    }
+
+    /// Marks a chunk to be rendered again after the blocks in a box in it changed, along with the
+    /// neighboring chunks the box touches the edge of.
+    fn mark_box_modified(&mut self, chunk_index: &ChunkIndex, lower: &ChunkBlockIndex, upper: &ChunkBlockIndex) {
+        // Blocks on the edge of a chunk change how the neighboring chunks look too, including the
+        // ones diagonal to it since they use the block for ambient occlusion
+        let edge_range = |lower: usize, upper: usize, size: usize| {
+            let low = if lower == 0 { -1 } else { 0 };
+            let high = if upper == size - 1 { 1 } else { 0 };
+            low..=high
+        };
+        let ranges = [
+            edge_range(lower.x, upper.x, Chunk::SIZE_X),
+            edge_range(lower.y, upper.y, Chunk::SIZE_Y),
+            edge_range(lower.z, upper.z, Chunk::SIZE_Z)
+        ];
+        for neighbor in World::neighbor_chunks(chunk_index, ranges) {
+            if self.chunks.contains_key(&neighbor) {
+                self.modified_chunks.insert(neighbor);
+            }
+        }
+
+        self.modified_chunks.insert(chunk_index.clone());
+    }
+
+    /// Returns the chunks offset from a chunk by every combination of the deltas on each axis,
+    /// skipping the chunk itself and any outside of the world.
+    fn neighbor_chunks(index: &ChunkIndex, ranges: [RangeInclusive<i32>; 3]) -> Vec<ChunkIndex> {
+        let mut neighbors = Vec::new();
+        for dx in ranges[0].clone() {
+            for dy in ranges[1].clone() {
+                for dz in ranges[2].clone() {
+                    if dx == 0 && dy == 0 && dz == 0 { continue; }
+
+                    let neighbor = index.offset(0, dx).and_then(|index| index.offset(1, dy)).and_then(|index| index.offset(2, dz));
+                    neighbors.extend(neighbor);
+                }
+            }
+        }
+
+        neighbors
+    }
+
+    /// Returns the chunks that have changed since the last call.
+    pub fn take_modified_chunks(&mut self) -> HashSet<ChunkIndex> {
+        std::mem::take(&mut self.modified_chunks)
+    }
+
+    fn place_deferred_blocks(&mut self, deferred: Vec<DeferredBlock>) {
+        let mut placed = Vec::new();
+        for deferred_block in deferred {
+            let chunk_index = deferred_block.index.get_chunk_index();
+            let chunk_block_index = deferred_block.index.get_chunk_block_index();
+            let changed = match self.chunks.get_mut(&chunk_index) {
+                // Write directly in to chunks that already exist
+                Some(chunk) => chunk.set_block_if_invisible(&chunk_block_index, deferred_block.block),
+                None => {
+                    // Hold on to the block until the chunk gets created
+                    self.pending_blocks.entry(chunk_index).or_default().push(deferred_block);
+                    continue;
+                }
+            };
+
+            if changed {
+                self.mark_modified(&chunk_index, &chunk_block_index);
+
+                // Only blocks that change the light need relighting, since they replaced air
+                if !deferred_block.block.is_transparent() || deferred_block.block.light_emission() > 0 {
+                    placed.push(deferred_block.index);
+                }
+            }
+        }
+
+        if !placed.is_empty() {
+            self.relight_blocks(&placed);
+        }
+    }
+
+    /// Returns the biome of the column containing a block, if the delegate has biomes.
+    pub fn biome_at(&self, index: &WorldBlockIndex) -> Option<Biome> {
+        self.delegate.biome_at(index.x, index.y)
+    }
+
+    pub fn surface_height(&self, x: u32, y: u32) -> Option<u32> {
+        self.delegate.surface_height(x, y)
     }
 }
 
@@ -119,9 +385,9 @@ impl World {
                 for z in lower.z..=upper.z {
                     // Get ellipsoid distance from the center
                     let dist =
-                        ((x as f64 - cx as f64) / rx).powi(2) +
-                        ((y as f64 - cy as f64) / ry).powi(2) +
-                        ((z as f64 - cz as f64) / rz).powi(2);
+                        ((x as f64 - cx) / rx).powi(2) +
+                        ((y as f64 - cy) / ry).powi(2) +
+                        ((z as f64 - cz) / rz).powi(2);
 
                     // Check if distance is within ellipsoid
                     if dist <= 1. {
@@ -132,3 +398,118 @@ impl World {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates empty chunks; the chunk at the origin also places a furnace in the far corner of
+    /// the chunk diagonal to it.
+    struct CornerDelegate;
+
+    impl CornerDelegate {
+        fn furnace_index() -> WorldBlockIndex {
+            WorldBlockIndex::new(Chunk::SIZE_X as u32 * 2 - 1, Chunk::SIZE_Y as u32 * 2 - 1, 0)
+        }
+    }
+
+    impl WorldDelegate for CornerDelegate {
+        fn create_chunk(&self, index: &ChunkIndex, deferred: &mut Vec<DeferredBlock>) -> Chunk {
+            if *index == ChunkIndex::new(0, 0, 0) {
+                deferred.push(DeferredBlock { index: CornerDelegate::furnace_index(), block: Block::from_id("furnace") });
+            }
+
+            Chunk::empty()
+        }
+    }
+
+    /// Creates chunks of nothing but air.
+    struct AirDelegate;
+
+    impl WorldDelegate for AirDelegate {
+        fn create_chunk(&self, _index: &ChunkIndex, _deferred: &mut Vec<DeferredBlock>) -> Chunk {
+            Chunk::empty()
+        }
+    }
+
+    #[test]
+    fn deferred_blocks_update_loaded_chunks() {
+        let mut world = World::new(Box::new(CornerDelegate));
+        for &(x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)].iter() {
+            world.get_or_create_chunk(&ChunkIndex::new(x, y, 0));
+        }
+        world.take_modified_chunks();
+
+        world.get_or_create_chunk(&ChunkIndex::new(0, 0, 0));
+        let furnace = CornerDelegate::furnace_index();
+        assert_eq!(world.get_block(&furnace), Block::from_id("furnace"));
+
+        // The furnace is on the corner of its chunk, so the chunks on its edges and corner need to
+        // be rendered again too
+        let modified = world.take_modified_chunks();
+        for &(x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)].iter() {
+            assert!(modified.contains(&ChunkIndex::new(x, y, 0)), "chunk {}, {} was not modified", x, y);
+        }
+
+        // The furnace lights up the loaded chunk
+        let light = world.get_chunk(&furnace.get_chunk_index()).unwrap().get_light(&furnace.get_chunk_block_index());
+        assert_eq!(light.get(LightChannel::Block), Block::from_id("furnace").light_emission());
+    }
+
+    #[test]
+    fn fill_box_lights_like_setting_each_block() {
+        // A world filled a box at a time, and one filled a block at a time that relights after each
+        let mut filled = World::new(Box::new(AirDelegate));
+        let mut reference = World::new(Box::new(AirDelegate));
+        for world in [&mut filled, &mut reference].iter_mut() {
+            for x in 0..3 {
+                for y in 0..3 {
+                    world.get_or_create_chunk(&ChunkIndex::new(x, y, 0));
+                }
+            }
+            world.take_modified_chunks();
+        }
+
+        // A roof over the border between four chunks, a room carved out of it, and a light in it
+        let boxes = [
+            (Block::from_id("stone"), WorldBlockIndex::new(24, 24, 10), WorldBlockIndex::new(40, 40, 14)),
+            (Block::AIR, WorldBlockIndex::new(26, 26, 11), WorldBlockIndex::new(38, 38, 12)),
+            (Block::from_id("furnace"), WorldBlockIndex::new(30, 31, 11), WorldBlockIndex::new(35, 31, 11))
+        ];
+        for &(block, ref lower, ref upper) in boxes.iter() {
+            filled.fill_box(block, lower, upper);
+            for x in lower.x..=upper.x {
+                for y in lower.y..=upper.y {
+                    for z in lower.z..=upper.z {
+                        reference.set_block(&WorldBlockIndex::new(x, y, z), block);
+                    }
+                }
+            }
+        }
+
+        // Both end up with the same blocks and light
+        for x in 0..Chunk::SIZE_X as u32 * 3 {
+            for y in 0..Chunk::SIZE_Y as u32 * 3 {
+                for z in 0..Chunk::SIZE_Z as u32 {
+                    let index = WorldBlockIndex::new(x, y, z);
+                    let light = |world: &World| world.get_chunk(&index.get_chunk_index()).unwrap().get_light(&index.get_chunk_block_index());
+                    assert_eq!(filled.get_block(&index), reference.get_block(&index), "{}", index);
+                    assert_eq!(light(&filled), light(&reference), "{}", index);
+                }
+            }
+        }
+
+        // The roof shades the ground and the furnace lights the room
+        let light = |index: WorldBlockIndex| filled.get_chunk(&index.get_chunk_index()).unwrap().get_light(&index.get_chunk_block_index());
+        assert!(light(WorldBlockIndex::new(32, 32, 5)).get(LightChannel::Sky) < Light::MAX);
+        assert_eq!(light(WorldBlockIndex::new(32, 32, 15)).get(LightChannel::Sky), Light::MAX);
+        assert_eq!(light(WorldBlockIndex::new(32, 32, 11)).get(LightChannel::Block), Block::from_id("furnace").light_emission() - 1);
+
+        // Only the four chunks the box is in are rendered again, since it doesn't reach the edges
+        // next to the others
+        let modified = filled.take_modified_chunks();
+        assert_eq!(modified, reference.take_modified_chunks());
+        let expected: HashSet<ChunkIndex> = [(0, 0), (1, 0), (0, 1), (1, 1)].iter().map(|&(x, y)| ChunkIndex::new(x, y, 0)).collect();
+        assert_eq!(modified, expected);
+    }
+}
@@ -0,0 +1,333 @@
+use super::World;
+use super::WorldBlockIndex;
+use vecmath::Vector3;
+
+/// An axis aligned bounding box in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn translated(&self, offset: Vector3<f32>) -> Aabb {
+        Aabb {
+            min: [self.min[0] + offset[0], self.min[1] + offset[1], self.min[2] + offset[2]],
+            max: [self.max[0] + offset[0], self.max[1] + offset[1], self.max[2] + offset[2]]
+        }
+    }
+
+    /// Determines if the box overlaps the block at the given position.
+    pub fn intersects_block(&self, x: i64, y: i64, z: i64) -> bool {
+        let block = [x as f32, y as f32, z as f32];
+        (0..3).all(|axis| self.min[axis] < block[axis] + 1. - Aabb::EPSILON && self.max[axis] > block[axis] + Aabb::EPSILON)
+    }
+
+    /// Moves the box along one axis as far as it can go towards `delta` without entering a solid
+    /// block, and returns how far it moved. Blocks the box already overlaps are ignored so it can't
+    /// get stuck inside of them.
+    pub fn sweep_axis<F>(&self, axis: usize, delta: f32, is_solid: &F) -> f32 where F: Fn(i64, i64, i64) -> bool {
+        if delta == 0. { return 0.; }
+
+        // Find the blocks the box covers on the other two axes
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let a_range = (self.min[a] + Aabb::EPSILON).floor() as i64..=(self.max[a] - Aabb::EPSILON).floor() as i64;
+        let b_range = (self.min[b] + Aabb::EPSILON).floor() as i64..=(self.max[b] - Aabb::EPSILON).floor() as i64;
+
+        // Find the layers of blocks the face of the box passes through
+        let (face, start, end) = if delta > 0. {
+            let face = self.max[axis];
+            (face, (face - Aabb::EPSILON).floor() as i64 + 1, (face + delta).floor() as i64)
+        } else {
+            let face = self.min[axis];
+            (face, (face + delta).floor() as i64, (face + Aabb::EPSILON).floor() as i64 - 1)
+        };
+
+        // Check each layer from the nearest to the farthest, stopping at the first solid block
+        let layers: Box<dyn Iterator<Item=i64>> = if delta > 0. { Box::new(start..=end) } else { Box::new((start..=end).rev()) };
+        for layer in layers {
+            for i in a_range.clone() {
+                for j in b_range.clone() {
+                    let mut block = [0; 3];
+                    block[axis] = layer;
+                    block[a] = i;
+                    block[b] = j;
+                    if is_solid(block[0], block[1], block[2]) {
+                        // Stop at the near face of the block
+                        return if delta > 0. {
+                            (layer as f32 - face).max(0.)
+                        } else {
+                            (layer as f32 + 1. - face).min(0.)
+                        };
+                    }
+                }
+            }
+        }
+
+        delta
+    }
+
+    /// Leeway so boxes resting exactly on a block boundary don't count as overlapping it.
+    const EPSILON: f32 = 0.0001;
+}
+
+/// Whether the player flies through blocks or walks with gravity and collisions.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MovementMode {
+    Fly,
+    Walk
+}
+
+/// The input driving the player for a single step.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PlayerInput {
+    /// The direction to walk in on the X and Y axes; this doesn't need to be normalized.
+    pub walk: [f32; 2],
+    pub jump: bool,
+    pub sprint: bool
+}
+
+/// A walking player that collides with the world.
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// The center of the bottom of the player's bounding box.
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub on_ground: bool
+}
+
+impl Player {
+    pub const WIDTH: f32 = 0.6;
+    pub const HEIGHT: f32 = 1.8;
+    pub const EYE_HEIGHT: f32 = 1.62;
+
+    /// How high of a ledge the player can walk up without jumping.
+    pub const STEP_HEIGHT: f32 = 1.;
+
+    const GRAVITY: f32 = 28.;
+    const JUMP_SPEED: f32 = 9.;
+    const WALK_SPEED: f32 = 4.3;
+    const SPRINT_SPEED: f32 = 7.;
+    const TERMINAL_VELOCITY: f32 = 60.;
+
+    pub fn new(position: Vector3<f32>) -> Player {
+        Player {
+            position,
+            velocity: [0., 0., 0.],
+            on_ground: false
+        }
+    }
+
+    pub fn get_aabb(&self) -> Aabb {
+        let half_width = Player::WIDTH / 2.;
+        Aabb::new(
+            [self.position[0] - half_width, self.position[1] - half_width, self.position[2]],
+            [self.position[0] + half_width, self.position[1] + half_width, self.position[2] + Player::HEIGHT]
+        )
+    }
+
+    pub fn get_eye_position(&self) -> Vector3<f32> {
+        [self.position[0], self.position[1], self.position[2] + Player::EYE_HEIGHT]
+    }
+
+    /// Advances the player by one step. `is_solid` takes a block position, which may be outside of
+    /// the world.
+    pub fn update<F>(&mut self, input: &PlayerInput, dt: f32, is_solid: F) where F: Fn(i64, i64, i64) -> bool {
+        // Walking sets the horizontal speed directly so the player stops as soon as they let go
+        let walk_length = input.walk[0].hypot(input.walk[1]);
+        let speed = if input.sprint { Player::SPRINT_SPEED } else { Player::WALK_SPEED };
+        if walk_length > 0. {
+            self.velocity[0] = input.walk[0] / walk_length * speed;
+            self.velocity[1] = input.walk[1] / walk_length * speed;
+        } else {
+            self.velocity[0] = 0.;
+            self.velocity[1] = 0.;
+        }
+
+        // Jump and fall
+        if input.jump && self.on_ground {
+            self.velocity[2] = Player::JUMP_SPEED;
+        }
+        self.velocity[2] = (self.velocity[2] - Player::GRAVITY * dt).max(-Player::TERMINAL_VELOCITY);
+
+        // Move the player
+        let motion = [self.velocity[0] * dt, self.velocity[1] * dt, self.velocity[2] * dt];
+        let (moved, hit_ground) = self.move_with_step(motion, &is_solid);
+        self.position = [self.position[0] + moved[0], self.position[1] + moved[1], self.position[2] + moved[2]];
+        self.on_ground = hit_ground;
+
+        // Stop on anything the player ran in to
+        for axis in 0..3 {
+            if (moved[axis] - motion[axis]).abs() > 0.00001 {
+                self.velocity[axis] = 0.;
+            }
+        }
+    }
+
+    /// Moves the bounding box, walking up ledges if the player is on the ground. Returns how far
+    /// the box moved and if it landed on something.
+    fn move_with_step<F>(&self, motion: Vector3<f32>, is_solid: &F) -> (Vector3<f32>, bool) where F: Fn(i64, i64, i64) -> bool {
+        let aabb = self.get_aabb();
+        let (moved, hit_ground) = Player::move_aabb(&aabb, motion, is_solid);
+
+        // Only step if the player is walking on the ground and hit a wall
+        let blocked = (moved[0] - motion[0]).abs() > 0.00001 || (moved[1] - motion[1]).abs() > 0.00001;
+        if !self.on_ground || !blocked {
+            return (moved, hit_ground);
+        }
+
+        // Try moving up, across and back down again
+        let up = aabb.sweep_axis(2, Player::STEP_HEIGHT, is_solid);
+        let raised = aabb.translated([0., 0., up]);
+        let (across, _) = Player::move_aabb(&raised, [motion[0], motion[1], 0.], is_solid);
+        let stepped = raised.translated(across);
+        let down = stepped.sweep_axis(2, -up + motion[2].min(0.), is_solid);
+        let step_moved = [across[0], across[1], up + down];
+
+        // Use the step if it got the player further
+        let distance = |m: Vector3<f32>| m[0] * m[0] + m[1] * m[1];
+        if distance(step_moved) > distance(moved) + 0.00001 {
+            (step_moved, true)
+        } else {
+            (moved, hit_ground)
+        }
+    }
+
+    /// Moves the box one axis at a time, vertical first, so it slides along walls. Returns how far
+    /// the box moved and if it was stopped while moving down.
+    fn move_aabb<F>(aabb: &Aabb, motion: Vector3<f32>, is_solid: &F) -> (Vector3<f32>, bool) where F: Fn(i64, i64, i64) -> bool {
+        let mut current = *aabb;
+        let mut moved = [0.; 3];
+        for &axis in [2, 0, 1].iter() {
+            let mut delta = [0.; 3];
+            delta[axis] = current.sweep_axis(axis, motion[axis], is_solid);
+            current = current.translated(delta);
+            moved[axis] = delta[axis];
+        }
+
+        let hit_ground = motion[2] < 0. && moved[2] > motion[2];
+        (moved, hit_ground)
+    }
+}
+
+impl World {
+    /// Determines if a position blocks movement. Blocks outside of the world are solid so nothing
+    /// can fall out of it.
+    pub fn is_solid(&self, x: i64, y: i64, z: i64) -> bool {
+        let in_world = |value: i64| value >= 0 && value <= i64::from(u32::MAX);
+        if !in_world(x) || !in_world(y) || !in_world(z) { return true; }
+
+        !self.get_block(&WorldBlockIndex::new(x as u32, y as u32, z as u32)).is_transparent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUND: i64 = 10;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "expected {}, got {}", expected, actual);
+    }
+
+    /// A player standing on flat ground.
+    fn standing_player(x: f32) -> Player {
+        let mut player = Player::new([x, 0.5, GROUND as f32]);
+        player.on_ground = true;
+        player
+    }
+
+    #[test]
+    fn sweep_axis_stops_at_blocks() {
+        let is_solid = |_: i64, _: i64, z: i64| z < GROUND;
+        let aabb = Aabb::new([0.2, 0.2, 12.], [0.8, 0.8, 13.]);
+        assert_close(aabb.sweep_axis(2, -5., &is_solid), -2.);
+        assert_close(aabb.sweep_axis(2, -1.5, &is_solid), -1.5);
+        assert_close(aabb.sweep_axis(2, 5., &is_solid), 5.);
+        assert_close(aabb.sweep_axis(0, 0., &is_solid), 0.);
+
+        // Blocks the box is already inside of don't stop it
+        let inside = Aabb::new([0.2, 0.2, 9.5], [0.8, 0.8, 10.5]);
+        assert_close(inside.sweep_axis(2, 2., &is_solid), 2.);
+    }
+
+    #[test]
+    fn falls_and_lands() {
+        let is_solid = |_: i64, _: i64, z: i64| z < GROUND;
+        let mut player = Player::new([0.5, 0.5, 15.]);
+        for _ in 0..120 {
+            player.update(&PlayerInput::default(), 1. / 60., is_solid);
+        }
+
+        assert_close(player.position[2], GROUND as f32);
+        assert!(player.on_ground);
+        assert_close(player.velocity[2], 0.);
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let is_solid = |x: i64, _: i64, z: i64| z < GROUND || x >= 3;
+        let player = standing_player(2.5);
+        let (moved, _) = player.move_with_step([1., 1., 0.], &is_solid);
+
+        assert_close(moved[0], 3. - 2.5 - Player::WIDTH / 2.);
+        assert_close(moved[1], 1.);
+        assert_close(moved[2], 0.);
+    }
+
+    #[test]
+    fn steps_up_one_block_ledges() {
+        let is_solid = |x: i64, _: i64, z: i64| z < GROUND || (x >= 3 && z < GROUND + 1);
+        let player = standing_player(2.5);
+        let (moved, hit_ground) = player.move_with_step([0.5, 0., -0.01], &is_solid);
+
+        assert_close(moved[0], 0.5);
+        assert_close(moved[2], 1.);
+        assert!(hit_ground);
+
+        // Players in the air don't step
+        let mut falling = player.clone();
+        falling.on_ground = false;
+        let (moved, _) = falling.move_with_step([0.5, 0., -0.01], &is_solid);
+        assert_close(moved[0], 3. - 2.5 - Player::WIDTH / 2.);
+    }
+
+    #[test]
+    fn does_not_step_up_two_block_ledges() {
+        let is_solid = |x: i64, _: i64, z: i64| z < GROUND || (x >= 3 && z < GROUND + 2);
+        let player = standing_player(2.5);
+        let (moved, _) = player.move_with_step([0.5, 0., -0.01], &is_solid);
+
+        assert_close(moved[0], 3. - 2.5 - Player::WIDTH / 2.);
+        assert_close(moved[2], 0.);
+    }
+
+    #[test]
+    fn hits_ceilings() {
+        let ceiling = GROUND + 2;
+        let is_solid = |_: i64, _: i64, z: i64| z < GROUND || z >= ceiling;
+        let mut player = standing_player(0.5);
+        player.update(&PlayerInput { jump: true, ..PlayerInput::default() }, 0.1, is_solid);
+
+        assert_close(player.position[2] + Player::HEIGHT, ceiling as f32);
+        assert_close(player.velocity[2], 0.);
+        assert!(!player.on_ground);
+    }
+
+    #[test]
+    fn large_steps_do_not_tunnel() {
+        // A single layer of blocks with nothing below it
+        let is_solid = |_: i64, _: i64, z: i64| z == GROUND;
+        let mut player = Player::new([0.5, 0.5, 12.]);
+        player.velocity[2] = -Player::TERMINAL_VELOCITY;
+        player.update(&PlayerInput::default(), 1., is_solid);
+
+        assert_close(player.position[2], GROUND as f32 + 1.);
+        assert!(player.on_ground);
+    }
+}
@@ -0,0 +1,222 @@
+use super::World;
+use super::WorldBlockIndex;
+use vecmath::Vector3;
+
+/// The result of `World::raycast`.
+#[derive(Debug, Clone)]
+pub struct RaycastHit {
+    /// The block that was hit.
+    pub index: WorldBlockIndex,
+
+    /// The normal of the face the ray entered the block through. This is zero if the ray started
+    /// inside of the block.
+    pub normal: [i32; 3],
+
+    /// The distance along the ray to where it entered the block.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// The block on the other side of the face that was hit; this is where a block would be placed.
+    /// Returns `None` if that is outside of the world.
+    pub fn adjacent_index(&self) -> Option<WorldBlockIndex> {
+        let offset = |value: u32, delta: i32| if delta < 0 { value.checked_sub(1) } else { value.checked_add(delta as u32) };
+        Some(WorldBlockIndex::new(
+            offset(self.index.x, self.normal[0])?,
+            offset(self.index.y, self.normal[1])?,
+            offset(self.index.z, self.normal[2])?
+        ))
+    }
+}
+
+impl World {
+    /// Returns the first visible block along a ray. The origin and direction are in world space
+    /// (Z is up), the direction does not need to be normalized. Unloaded chunks are treated as
+    /// air and are never generated.
+    pub fn raycast(&self, origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32) -> Option<RaycastHit> {
+        raycast_blocks(origin, direction, max_distance, |index| !self.get_block(index).is_invisible())
+    }
+}
+
+/// Walks the voxel grid along the ray using the DDA algorithm from "A Fast Voxel Traversal
+/// Algorithm for Ray Tracing" by Amanatides and Woo, stopping at the first block `is_hit` accepts.
+pub fn raycast_blocks<F>(origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32, mut is_hit: F) -> Option<RaycastHit> where F: FnMut(&WorldBlockIndex) -> bool {
+    // Normalize the direction so distances along the ray are in blocks
+    let length = (direction[0] as f64).hypot(direction[1] as f64).hypot(direction[2] as f64);
+    if length == 0. || max_distance < 0. { return None; }
+    let direction = [direction[0] as f64 / length, direction[1] as f64 / length, direction[2] as f64 / length];
+    let origin = [origin[0] as f64, origin[1] as f64, origin[2] as f64];
+
+    // The block the ray is currently in
+    let mut block = [origin[0].floor(), origin[1].floor(), origin[2].floor()];
+
+    // For each axis, the direction to step, how far along the ray the next block boundary is, and
+    // how far along the ray it is between boundaries
+    let mut step = [0.; 3];
+    let mut next_boundary = [f64::INFINITY; 3];
+    let mut boundary_delta = [f64::INFINITY; 3];
+    for axis in 0..3 {
+        if direction[axis] > 0. {
+            step[axis] = 1.;
+            next_boundary[axis] = (block[axis] + 1. - origin[axis]) / direction[axis];
+            boundary_delta[axis] = 1. / direction[axis];
+        } else if direction[axis] < 0. {
+            step[axis] = -1.;
+            next_boundary[axis] = (block[axis] - origin[axis]) / direction[axis];
+            boundary_delta[axis] = -1. / direction[axis];
+        }
+    }
+
+    let mut distance = 0.;
+    let mut normal = [0; 3];
+    loop {
+        // Stop once the ray leaves the world
+        let in_world = block.iter().all(|&value| value >= 0. && value <= u32::MAX as f64);
+        if !in_world { return None; }
+
+        // Check the block
+        let index = WorldBlockIndex::new(block[0] as u32, block[1] as u32, block[2] as u32);
+        if is_hit(&index) {
+            return Some(RaycastHit { index, normal, distance: distance as f32 });
+        }
+
+        // Step across the closest boundary
+        let axis = if next_boundary[0] < next_boundary[1] {
+            if next_boundary[0] < next_boundary[2] { 0 } else { 2 }
+        } else if next_boundary[1] < next_boundary[2] { 1 } else { 2 };
+        distance = next_boundary[axis];
+        if distance > max_distance as f64 { return None; }
+        block[axis] += step[axis];
+        next_boundary[axis] += boundary_delta[axis];
+        normal = [0; 3];
+        normal[axis] = -step[axis] as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Chunk;
+
+    /// Casts a ray, recording every block it visits on the way.
+    fn cast<F>(origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32, is_solid: F) -> (Option<RaycastHit>, Vec<WorldBlockIndex>) where F: Fn(&WorldBlockIndex) -> bool {
+        let mut visited = Vec::new();
+        let hit = raycast_blocks(origin, direction, max_distance, |index| {
+            visited.push(index.clone());
+            is_solid(index)
+        });
+        (hit, visited)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn axis_aligned() {
+        let (hit, visited) = cast([0.5, 0.5, 0.5], [1., 0., 0.], 100., |index| index.x == 5);
+        let hit = hit.unwrap();
+        assert_eq!(hit.index, WorldBlockIndex::new(5, 0, 0));
+        assert_eq!(hit.normal, [-1, 0, 0]);
+        assert_close(hit.distance, 4.5);
+        assert_eq!(hit.adjacent_index(), Some(WorldBlockIndex::new(4, 0, 0)));
+        assert_eq!(visited.len(), 6);
+    }
+
+    #[test]
+    fn unnormalized_direction() {
+        let hit = raycast_blocks([0.5, 0.5, 0.5], [0., 20., 0.], 100., |index| index.y == 3).unwrap();
+        assert_eq!(hit.index, WorldBlockIndex::new(0, 3, 0));
+        assert_eq!(hit.normal, [0, -1, 0]);
+        assert_close(hit.distance, 2.5);
+    }
+
+    #[test]
+    fn negative_directions() {
+        let hit = raycast_blocks([10.5, 10.5, 10.5], [0., 0., -1.], 100., |index| index.z == 3).unwrap();
+        assert_eq!(hit.index, WorldBlockIndex::new(10, 10, 3));
+        assert_eq!(hit.normal, [0, 0, 1]);
+        assert_close(hit.distance, 6.5);
+        assert_eq!(hit.adjacent_index(), Some(WorldBlockIndex::new(10, 10, 4)));
+
+        let hit = raycast_blocks([10.5, 10.5, 10.5], [-1., 0., 0.], 100., |index| index.x == 0).unwrap();
+        assert_eq!(hit.index, WorldBlockIndex::new(0, 10, 10));
+        assert_eq!(hit.normal, [1, 0, 0]);
+        assert_eq!(hit.adjacent_index(), Some(WorldBlockIndex::new(1, 10, 10)));
+    }
+
+    #[test]
+    fn diagonal() {
+        let (hit, visited) = cast([0.2, 0.5, 0.7], [1., 2., 3.], 100., |index| index.z >= 5);
+        let hit = hit.unwrap();
+
+        // The ray reaches Z 5 at 0.7 + 3t = 5
+        let t = 4.3 / 3.;
+        assert_eq!(hit.index, WorldBlockIndex::new(1, 3, 5));
+        assert_eq!(hit.normal, [0, 0, -1]);
+        assert_close(hit.distance, t * 14f32.sqrt());
+        assert_eq!(hit.adjacent_index(), Some(WorldBlockIndex::new(1, 3, 4)));
+
+        // Each step moves to a block sharing a face with the last one
+        for pair in visited.windows(2) {
+            let delta = (pair[1].x as i64 - pair[0].x as i64).abs() +
+                (pair[1].y as i64 - pair[0].y as i64).abs() +
+                (pair[1].z as i64 - pair[0].z as i64).abs();
+            assert_eq!(delta, 1, "{:?} to {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn crosses_chunk_borders() {
+        let border = Chunk::SIZE_X as u32;
+
+        let (hit, visited) = cast([border as f32 - 0.5, 0.5, 0.5], [1., 0., 0.], 100., |index| index.x == border + 1);
+        let hit = hit.unwrap();
+        assert_eq!(hit.index, WorldBlockIndex::new(border + 1, 0, 0));
+        assert_eq!(hit.normal, [-1, 0, 0]);
+        assert_close(hit.distance, 1.5);
+        assert_eq!(visited, vec![
+            WorldBlockIndex::new(border - 1, 0, 0),
+            WorldBlockIndex::new(border, 0, 0),
+            WorldBlockIndex::new(border + 1, 0, 0)
+        ]);
+
+        let hit = raycast_blocks([border as f32 + 0.5, 0.5, 0.5], [-1., 0., 0.], 100., |index| index.x == border - 2).unwrap();
+        assert_eq!(hit.index, WorldBlockIndex::new(border - 2, 0, 0));
+        assert_eq!(hit.normal, [1, 0, 0]);
+        assert_close(hit.distance, 1.5);
+    }
+
+    #[test]
+    fn max_distance() {
+        assert!(raycast_blocks([0.5, 0.5, 0.5], [1., 0., 0.], 4.4, |index| index.x == 5).is_none());
+        assert!(raycast_blocks([0.5, 0.5, 0.5], [1., 0., 0.], 4.6, |index| index.x == 5).is_some());
+        assert!(raycast_blocks([0.5, 0.5, 0.5], [1., 0., 0.], -1., |_| true).is_none());
+    }
+
+    #[test]
+    fn origin_inside_block() {
+        let hit = raycast_blocks([2.5, 2.5, 2.5], [1., 0., 0.], 100., |_| true).unwrap();
+        assert_eq!(hit.index, WorldBlockIndex::new(2, 2, 2));
+        assert_eq!(hit.normal, [0, 0, 0]);
+        assert_close(hit.distance, 0.);
+        assert_eq!(hit.adjacent_index(), Some(WorldBlockIndex::new(2, 2, 2)));
+    }
+
+    #[test]
+    fn zero_direction() {
+        let (hit, visited) = cast([2.5, 2.5, 2.5], [0., 0., 0.], 100., |_| true);
+        assert!(hit.is_none());
+        assert!(visited.is_empty());
+    }
+
+    #[test]
+    fn leaves_world() {
+        // Nothing is hit once the ray goes below zero
+        assert!(raycast_blocks([2.5, 2.5, 2.5], [-1., -1., -1.], 100., |_| false).is_none());
+
+        // There's no block to place against past the edge of the world
+        let hit = RaycastHit { index: WorldBlockIndex::new(0, 5, 5), normal: [-1, 0, 0], distance: 0. };
+        assert_eq!(hit.adjacent_index(), None);
+    }
+}